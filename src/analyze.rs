@@ -1,8 +1,9 @@
+use crate::dir_tree::DirTree;
 use crate::indexer::{ScanObserver, ScannedFile};
 use anyhow::Result;
 use serde::Serialize;
 use std::cmp::Reverse;
-use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::collections::{BTreeMap, BinaryHeap, HashMap, HashSet};
 use std::path::{Path, PathBuf};
 
 #[derive(Debug, Serialize)]
@@ -68,9 +69,8 @@ pub struct BrowseIndexBuilder {
     filter: Option<PathBuf>,
     total_scanned: u64,
     root_totals: HashMap<PathBuf, u64>,
-    dir_totals: HashMap<PathBuf, u64>,
+    dir_totals: DirTotals,
     file_sizes: HashMap<PathBuf, u64>,
-    dirs: HashSet<PathBuf>,
 }
 
 impl BrowseIndexBuilder {
@@ -83,20 +83,18 @@ impl BrowseIndexBuilder {
             filter,
             total_scanned: 0,
             root_totals,
-            dir_totals: HashMap::new(),
+            dir_totals: DirTotals::default(),
             file_sizes: HashMap::new(),
-            dirs: HashSet::new(),
         }
     }
 
-    pub fn finalize(mut self) -> BrowseIndex {
+    pub fn finalize(self) -> BrowseIndex {
+        let mut dir_totals = self.dir_totals.finish();
         for (root, size) in &self.root_totals {
-            self.dir_totals.entry(root.clone()).or_insert(*size);
-            self.dirs.insert(root.clone());
+            dir_totals.entry(root.clone()).or_insert(*size);
         }
         if let Some(filter) = &self.filter {
-            self.dir_totals.entry(filter.clone()).or_insert(0);
-            self.dirs.insert(filter.clone());
+            dir_totals.entry(filter.clone()).or_insert(0);
         }
 
         let mut root_entries = self
@@ -114,10 +112,10 @@ impl BrowseIndexBuilder {
                 .then_with(|| a.path.to_string_lossy().cmp(&b.path.to_string_lossy()))
         });
         let mut children: HashMap<PathBuf, Vec<BrowseEntry>> = HashMap::new();
-        for dir in &self.dirs {
+        for (dir, size) in &dir_totals {
             if let Some(parent) = dir.parent() {
-                if self.dir_totals.contains_key(parent) {
-                    let size = self.dir_totals.get(dir).copied().unwrap_or(0);
+                if dir_totals.contains_key(parent) {
+                    let size = *size;
                     children
                         .entry(parent.to_path_buf())
                         .or_default()
@@ -131,7 +129,7 @@ impl BrowseIndexBuilder {
         }
         for (path, size) in &self.file_sizes {
             if let Some(parent) = path.parent() {
-                if self.dir_totals.contains_key(parent) {
+                if dir_totals.contains_key(parent) {
                     children
                         .entry(parent.to_path_buf())
                         .or_default()
@@ -154,7 +152,7 @@ impl BrowseIndexBuilder {
         BrowseIndex {
             total_scanned: self.total_scanned,
             root_entries,
-            dir_totals: self.dir_totals,
+            dir_totals,
             file_sizes: self.file_sizes,
             children,
         }
@@ -182,19 +180,7 @@ impl BrowseIndexBuilder {
         self.total_scanned += size;
         *self.root_totals.entry(root_path.to_path_buf()).or_insert(0) += size;
         self.file_sizes.insert(file_path.to_path_buf(), size);
-
-        let mut current = file_path.parent();
-        while let Some(dir) = current {
-            if !dir.starts_with(limit) {
-                break;
-            }
-            *self.dir_totals.entry(dir.to_path_buf()).or_insert(0) += size;
-            self.dirs.insert(dir.to_path_buf());
-            if dir == limit {
-                break;
-            }
-            current = dir.parent();
-        }
+        self.dir_totals.add(limit, file_path, size);
     }
 }
 
@@ -203,7 +189,7 @@ pub struct Analyzer {
     top_dir_limit: usize,
     total_scanned: u64,
     root_totals: HashMap<PathBuf, u64>,
-    dir_sizes: HashMap<PathBuf, u64>,
+    dir_sizes: DirTotals,
     top_files: TopN,
 }
 
@@ -214,14 +200,14 @@ impl Analyzer {
             top_dir_limit: top_dirs,
             total_scanned: 0,
             root_totals: HashMap::new(),
-            dir_sizes: HashMap::new(),
+            dir_sizes: DirTotals::default(),
             top_files: TopN::new(top_files),
         }
     }
 
     pub fn finalize(self) -> AnalysisResult {
         let mut dir_top = TopN::new(self.top_dir_limit);
-        for (path, size) in self.dir_sizes {
+        for (path, size) in self.dir_sizes.finish() {
             dir_top.push(path.to_string_lossy().to_string(), size);
         }
         let mut root_entries = self
@@ -264,18 +250,71 @@ impl Analyzer {
         *self.root_totals.entry(root_path.to_path_buf()).or_insert(0) += size;
         self.top_files
             .push(file_path.to_string_lossy().to_string(), size);
+        self.dir_sizes.add(limit, file_path, size);
+    }
+}
+
+/// Directory sizes summed over an interned `DirTree`. A file only adds to its
+/// own directory; `finish` rolls each directory's total into its parent,
+/// stopping at the file's limit (its root, or the filter inside that root),
+/// so every directory is visited once rather than once per file below it.
+#[derive(Default)]
+struct DirTotals {
+    tree: DirTree,
+    /// Per limit node, the sizes added directly to each directory node.
+    own: HashMap<u32, BTreeMap<u32, u64>>,
+    /// Files mostly arrive grouped by directory.
+    last_dir: Option<(PathBuf, u32)>,
+}
 
-        let mut current = file_path.parent();
-        while let Some(dir) = current {
-            if !dir.starts_with(limit) {
-                break;
+impl DirTotals {
+    fn add(&mut self, limit: &Path, file_path: &Path, size: u64) {
+        let Some(dir) = file_path.parent() else {
+            return;
+        };
+        let node = match &self.last_dir {
+            Some((last, node)) if last == dir => *node,
+            _ => {
+                let node = self.tree.intern(&tree_key(dir));
+                self.last_dir = Some((dir.to_path_buf(), node));
+                node
             }
-            *self.dir_sizes.entry(dir.to_path_buf()).or_insert(0) += size;
-            if dir == limit {
-                break;
+        };
+        let limit = self.tree.intern(&tree_key(limit));
+        *self.own.entry(limit).or_default().entry(node).or_insert(0) += size;
+    }
+
+    fn finish(self) -> HashMap<PathBuf, u64> {
+        let mut out = HashMap::new();
+        for (limit, mut sizes) in self.own {
+            // Children are interned after their parents, so the highest node
+            // is always complete by the time it is popped.
+            while let Some((node, size)) = sizes.pop_last() {
+                if !self.tree.is_within(node, limit) {
+                    continue;
+                }
+                let path = match self.tree.path(node) {
+                    path if path.is_empty() => PathBuf::from("/"),
+                    path => PathBuf::from(path),
+                };
+                *out.entry(path).or_insert(0) += size;
+                let parent = self.tree.parent(node).filter(|_| node != limit);
+                if let Some(parent) = parent {
+                    *sizes.entry(parent).or_insert(0) += size;
+                }
             }
-            current = dir.parent();
         }
+        out
+    }
+}
+
+/// `dir` in the `/`-joined form `DirTree` interns; the filesystem root is
+/// the empty top-level node.
+fn tree_key(dir: &Path) -> String {
+    let dir = dir.to_string_lossy();
+    match dir.trim_end_matches('/') {
+        "" if dir.starts_with('/') => String::new(),
+        trimmed => trimmed.to_string(),
     }
 }
 
@@ -332,7 +371,7 @@ pub fn analyze_store_with_progress(
         roots.insert(root.id, PathBuf::from(&root.path));
     }
     let mut processed = 0usize;
-    for file in &store.data.files {
+    for file in store.files() {
         if file.status != "active" || file.is_dir {
             continue;
         }
@@ -344,7 +383,8 @@ pub fn analyze_store_with_progress(
         if size == 0 {
             continue;
         }
-        let file_path = Path::new(&file.abs_path);
+        let abs_path = file.abs_path();
+        let file_path = Path::new(abs_path.as_ref());
         analyzer.ingest_file(root_path, file_path, size);
         processed += 1;
         if processed % 50_000 == 0 {
@@ -379,7 +419,7 @@ pub fn browse_index_from_store_with_progress(
         roots_by_id.insert(root.id, PathBuf::from(&root.path));
     }
     let mut processed = 0usize;
-    for file in &store.data.files {
+    for file in store.files() {
         if file.status != "active" || file.is_dir {
             continue;
         }
@@ -391,7 +431,8 @@ pub fn browse_index_from_store_with_progress(
         if size == 0 {
             continue;
         }
-        let file_path = Path::new(&file.abs_path);
+        let abs_path = file.abs_path();
+        let file_path = Path::new(abs_path.as_ref());
         builder.ingest_file(root_path, file_path, size);
         processed += 1;
         if processed % 50_000 == 0 {
@@ -438,7 +479,7 @@ fn analyze_store_with_cache(
     let mut top_files_acc = TopN::new(top_files);
     let mut processed = 0usize;
 
-    for file in &store.data.files {
+    for file in store.files() {
         if file.status != "active" || file.is_dir {
             continue;
         }
@@ -450,7 +491,8 @@ fn analyze_store_with_cache(
         if size == 0 {
             continue;
         }
-        let file_path = Path::new(&file.abs_path);
+        let abs_path = file.abs_path();
+        let file_path = Path::new(abs_path.as_ref());
         if let Some(filter) = &filter {
             if !file_path.starts_with(filter) {
                 continue;
@@ -458,7 +500,7 @@ fn analyze_store_with_cache(
         }
         total_scanned += size;
         *root_totals.entry(root_path.to_path_buf()).or_insert(0) += size;
-        top_files_acc.push(file.abs_path.clone(), size);
+        top_files_acc.push(file.abs_path().into_owned(), size);
 
         processed += 1;
         if processed % 50_000 == 0 {
@@ -525,7 +567,7 @@ fn browse_index_from_cache(
     let mut file_sizes: HashMap<PathBuf, u64> = HashMap::new();
     let mut processed = 0usize;
 
-    for file in &store.data.files {
+    for file in store.files() {
         if file.status != "active" || file.is_dir {
             continue;
         }
@@ -537,7 +579,8 @@ fn browse_index_from_cache(
         if size == 0 {
             continue;
         }
-        let file_path = Path::new(&file.abs_path);
+        let abs_path = file.abs_path();
+        let file_path = Path::new(abs_path.as_ref());
         if let Some(filter) = &filter {
             if !file_path.starts_with(filter) {
                 continue;
@@ -546,7 +589,7 @@ fn browse_index_from_cache(
 
         total_scanned += size;
         *root_totals.entry(root_path.to_path_buf()).or_insert(0) += size;
-        file_sizes.insert(PathBuf::from(&file.abs_path), size);
+        file_sizes.insert(PathBuf::from(file.abs_path().as_ref()), size);
 
         processed += 1;
         if processed % 50_000 == 0 {
@@ -751,6 +794,8 @@ mod tests {
                 size: 100,
                 mtime: 0,
                 ext: Some("txt".to_string()),
+                dev: None,
+                ino: None,
             },
             ScannedFile {
                 rel_path: "b.txt".to_string(),
@@ -760,6 +805,8 @@ mod tests {
                 size: 300,
                 mtime: 0,
                 ext: Some("txt".to_string()),
+                dev: None,
+                ino: None,
             },
             ScannedFile {
                 rel_path: "c.txt".to_string(),
@@ -769,6 +816,8 @@ mod tests {
                 size: 200,
                 mtime: 0,
                 ext: Some("txt".to_string()),
+                dev: None,
+                ino: None,
             },
         ];
         for file in &files {
@@ -781,15 +830,17 @@ mod tests {
         assert_eq!(result.top_files.len(), 2);
         assert_eq!(result.top_files[0].size, 300);
         assert_eq!(result.top_files[1].size, 200);
-        assert!(!result.top_dirs.is_empty());
+        let dirs: Vec<_> = result
+            .top_dirs
+            .iter()
+            .map(|d| (d.path.as_str(), d.size))
+            .collect();
+        assert_eq!(dirs, vec![("/root", 600), ("/root/sub", 500)]);
     }
 
     #[test]
     fn analyze_store_uses_existing_index() {
-        let mut store = Store {
-            path: PathBuf::from("/tmp/catalog.bin"),
-            data: StoreData::new(),
-        };
+        let mut store = Store::from_data(PathBuf::from("/tmp/catalog.bin"), StoreData::new());
         store.data.roots.push(RootEntry {
             id: 1,
             path: "/root".to_string(),
@@ -797,6 +848,8 @@ mod tests {
             preset_name: None,
             last_indexed_at: Some(Utc::now().to_rfc3339()),
             one_filesystem: true,
+            volume: None,
+            scan: None,
         });
         store.data.files.push(FileEntry {
             id: 1,
@@ -810,6 +863,10 @@ mod tests {
             ext: Some("bin".to_string()),
             status: "active".to_string(),
             last_seen_run: 1,
+            dev: None,
+            ino: None,
+            prev_path: None,
+            deleted_at: None,
         });
         let result = analyze_store_with_progress(&store, None, 5, 5, None);
         assert_eq!(result.total_scanned, 1024);
@@ -821,10 +878,7 @@ mod tests {
 
     #[test]
     fn analyze_store_respects_filter() {
-        let mut store = Store {
-            path: PathBuf::from("/tmp/catalog.bin"),
-            data: StoreData::new(),
-        };
+        let mut store = Store::from_data(PathBuf::from("/tmp/catalog.bin"), StoreData::new());
         store.data.roots.push(RootEntry {
             id: 1,
             path: "/root".to_string(),
@@ -832,6 +886,8 @@ mod tests {
             preset_name: None,
             last_indexed_at: Some(Utc::now().to_rfc3339()),
             one_filesystem: true,
+            volume: None,
+            scan: None,
         });
         store.data.files.push(FileEntry {
             id: 1,
@@ -845,6 +901,10 @@ mod tests {
             ext: Some("bin".to_string()),
             status: "active".to_string(),
             last_seen_run: 1,
+            dev: None,
+            ino: None,
+            prev_path: None,
+            deleted_at: None,
         });
         store.data.files.push(FileEntry {
             id: 2,
@@ -858,6 +918,10 @@ mod tests {
             ext: Some("bin".to_string()),
             status: "active".to_string(),
             last_seen_run: 1,
+            dev: None,
+            ino: None,
+            prev_path: None,
+            deleted_at: None,
         });
 
         let result = analyze_store_with_progress(&store, Some(PathBuf::from("/root/keep")), 5, 5, None);
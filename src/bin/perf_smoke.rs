@@ -1,8 +1,10 @@
 use anyhow::{Context, Result};
 use catalog::analyze;
-use catalog::config::{Config, OutputMode};
+use catalog::config::Config;
 use catalog::indexer;
-use catalog::store::Store;
+use catalog::query;
+use catalog::search::{self, SortOrder, StatusFilter};
+use catalog::store::{FileEntry, RootEntry, Store, StoreData};
 use std::env;
 use std::fs::{self, File};
 use std::path::{Path, PathBuf};
@@ -16,6 +18,11 @@ fn main() -> Result<()> {
     let max_index = env_duration("CATALOG_PERF_MAX_INDEX_SECS", default_max_index_secs());
     let max_analyze = env_duration("CATALOG_PERF_MAX_ANALYZE_SECS", default_max_analyze_secs());
     let max_browse = env_duration("CATALOG_PERF_MAX_BROWSE_SECS", default_max_browse_secs());
+    let max_search = Duration::from_millis(env_u64(
+        "CATALOG_PERF_MAX_SEARCH_MS",
+        default_max_search_ms(),
+    ));
+    let search_sizes = env_sizes("CATALOG_PERF_SEARCH_SIZES", default_search_sizes());
 
     let base = temp_dir("perf_smoke");
     let root = base.join("root");
@@ -25,12 +32,10 @@ fn main() -> Result<()> {
     let expected_total_size = total_files as u64 * file_size;
 
     let cfg = Config {
-        version: 1,
-        output: OutputMode::Plain,
         include_hidden: true,
-        one_filesystem: true,
-        roots: vec![root.to_string_lossy().to_string()],
+        roots: vec![root.to_string_lossy().to_string().into()],
         excludes: Vec::new(),
+        ..Config::default()
     };
 
     let store_path = base.join("store.bin");
@@ -103,6 +108,22 @@ fn main() -> Result<()> {
         anyhow::bail!("browse exceeded budget: {:?} > {:?}", browse_elapsed, max_browse);
     }
 
+    for &entries in &search_sizes {
+        let (index_elapsed, median) = search_latency(&cfg, &base, entries)?;
+        println!(
+            "  search @ {} entries: median {:?} (path index built in {:?})",
+            entries, median, index_elapsed
+        );
+        if median > max_search {
+            anyhow::bail!(
+                "search at {} entries exceeded budget: {:?} > {:?}",
+                entries,
+                median,
+                max_search
+            );
+        }
+    }
+
     if env::var("CATALOG_PERF_KEEP").is_err() {
         let _ = fs::remove_dir_all(&base);
     } else {
@@ -112,6 +133,88 @@ fn main() -> Result<()> {
     Ok(())
 }
 
+/// Each has results in any synthetic store of at least 20k entries.
+const SEARCH_QUERIES: &[&str] = &[
+    "invoice_0004241",
+    "name:report ext:pdf proj_0004",
+    "summary_0012342 OR budget_0054327",
+    "draft module_007 -ext:md",
+    "proj_0009/src",
+];
+
+const WORDS: &[&str] = &[
+    "report", "invoice", "photo", "notes", "build", "draft", "summary", "budget",
+];
+const EXTS: &[&str] = &["pdf", "txt", "rs", "jpg", "md"];
+
+/// Times `SEARCH_QUERIES` against a synthetic in-memory store of `entries`
+/// files and returns (path index build time, median query latency).
+fn search_latency(cfg: &Config, base: &Path, entries: usize) -> Result<(Duration, Duration)> {
+    let mut data = StoreData::new();
+    let root_id = data.next_root_id();
+    let root = "/perf".to_string();
+    data.roots.push(RootEntry {
+        id: root_id,
+        path: root.clone(),
+        added_at: String::new(),
+        preset_name: None,
+        last_indexed_at: None,
+        one_filesystem: true,
+        volume: None,
+        scan: None,
+    });
+    data.files.reserve(entries);
+    for i in 0..entries {
+        let word = WORDS[i % WORDS.len()];
+        let ext = EXTS[i % EXTS.len()];
+        let rel_path = format!(
+            "proj_{:04}/src/module_{:03}/{}_{:07}.{}",
+            i / 2000,
+            (i / 50) % 40,
+            word,
+            i,
+            ext
+        );
+        let id = data.next_file_id();
+        data.files.push(FileEntry {
+            id,
+            root_id,
+            abs_path: format!("{}/{}", root, rel_path),
+            rel_path,
+            is_dir: false,
+            is_symlink: false,
+            size: (i % 4096) as i64,
+            mtime: 1_700_000_000 + i as i64,
+            ext: Some(ext.to_string()),
+            status: "active".to_string(),
+            last_seen_run: 1,
+            dev: None,
+            ino: None,
+            prev_path: None,
+            deleted_at: None,
+        });
+    }
+
+    // Never saved: the index is built in memory on first use.
+    let store = Store::from_data(base.join(format!("search_{}.bin", entries)), data);
+    let start = Instant::now();
+    drop(store.path_index());
+    let index_elapsed = start.elapsed();
+
+    let mut timings = Vec::new();
+    for text in SEARCH_QUERIES {
+        let expr = query::build(text, &query::Flags::default())?;
+        let start = Instant::now();
+        let results = search::search(&store, cfg, &expr, SortOrder::Score, StatusFilter::Active)?;
+        timings.push(start.elapsed());
+        if results.is_empty() {
+            anyhow::bail!("search '{}' returned no results at {} entries", text, entries);
+        }
+    }
+    timings.sort();
+    Ok((index_elapsed, timings[timings.len() / 2]))
+}
+
 fn populate_tree(root: &Path, dirs: usize, files_per_dir: usize, file_size: u64) -> Result<usize> {
     let mut total_files = 0;
     for dir_idx in 0..dirs {
@@ -160,6 +263,17 @@ fn env_u64(name: &str, default: u64) -> u64 {
         .unwrap_or(default)
 }
 
+fn env_sizes(name: &str, default: &[usize]) -> Vec<usize> {
+    match env::var(name) {
+        Ok(v) => v
+            .split(',')
+            .filter_map(|s| s.trim().parse::<usize>().ok())
+            .filter(|n| *n > 0)
+            .collect(),
+        Err(_) => default.to_vec(),
+    }
+}
+
 fn env_duration(name: &str, default_secs: u64) -> Duration {
     Duration::from_secs(env_u64(name, default_secs))
 }
@@ -180,6 +294,24 @@ fn default_max_analyze_secs() -> u64 {
     }
 }
 
+fn default_max_search_ms() -> u64 {
+    if cfg!(debug_assertions) {
+        1000
+    } else {
+        100
+    }
+}
+
+/// Synthetic store sizes searched; debug builds take minutes to build the
+/// path index of the large ones.
+fn default_search_sizes() -> &'static [usize] {
+    if cfg!(debug_assertions) {
+        &[20_000]
+    } else {
+        &[500_000, 5_000_000]
+    }
+}
+
 fn default_max_browse_secs() -> u64 {
     if cfg!(debug_assertions) {
         6
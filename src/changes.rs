@@ -0,0 +1,139 @@
+use crate::config::Config;
+use crate::output::human_size;
+use crate::store::{ChangeEntry, StoreData};
+use anyhow::Result;
+use chrono::{Local, TimeZone};
+
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+pub enum ChangeKind {
+    Created,
+    Modified,
+    Moved,
+    Deleted,
+}
+
+impl ChangeKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChangeKind::Created => "created",
+            ChangeKind::Modified => "modified",
+            ChangeKind::Moved => "moved",
+            ChangeKind::Deleted => "deleted",
+        }
+    }
+}
+
+/// Drops journal records older than `change_retention_days` (0 keeps them
+/// forever). Returns how many were removed.
+pub fn prune(store: &mut StoreData, cfg: &Config, now: i64) -> usize {
+    if cfg.change_retention_days == 0 {
+        return 0;
+    }
+    let cutoff = now - i64::from(cfg.change_retention_days) * 86400;
+    let before = store.changes.len();
+    store.changes.retain(|c| c.at >= cutoff);
+    before - store.changes.len()
+}
+
+/// Journal records matching every given filter, newest run first and by path
+/// within a run.
+pub fn query(
+    store: &StoreData,
+    since: Option<i64>,
+    run: Option<i64>,
+    kind: Option<ChangeKind>,
+) -> Vec<ChangeEntry> {
+    let mut out: Vec<ChangeEntry> = store
+        .changes
+        .iter()
+        .filter(|c| since.is_none_or(|since| c.at >= since))
+        .filter(|c| run.is_none_or(|run| c.run_id == run))
+        .filter(|c| kind.is_none_or(|kind| c.kind == kind.as_str()))
+        .cloned()
+        .collect();
+    out.sort_by(|a, b| b.run_id.cmp(&a.run_id).then_with(|| a.path.cmp(&b.path)));
+    out
+}
+
+pub fn print_changes(changes: &[ChangeEntry], json: bool) -> Result<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(changes)?);
+        return Ok(());
+    }
+    if changes.is_empty() {
+        println!("No changes.");
+    }
+    for c in changes {
+        let at = Local
+            .timestamp_opt(c.at, 0)
+            .single()
+            .map(|d| d.format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_else(|| "-".to_string());
+        let detail = match c.kind.as_str() {
+            "moved" => c
+                .prev_path
+                .as_ref()
+                .map(|prev| format!("  (from {})", prev)),
+            "modified" if !c.is_dir => match (c.old_size, c.new_size) {
+                (Some(old), Some(new)) if old != new => {
+                    Some(format!("  ({} -> {})", human_size(old), human_size(new)))
+                }
+                _ => None,
+            },
+            "created" if !c.is_dir => c.new_size.map(|size| format!("  ({})", human_size(size))),
+            _ => None,
+        };
+        println!(
+            "{}  {}  {:<8}  {}{}",
+            c.run_id,
+            at,
+            c.kind,
+            c.path,
+            detail.unwrap_or_default()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: i64 = 86400;
+
+    fn change(at: i64) -> ChangeEntry {
+        ChangeEntry {
+            run_id: 1,
+            at,
+            file_id: 1,
+            root_id: 1,
+            kind: "created".to_string(),
+            path: "/r/a.txt".to_string(),
+            prev_path: None,
+            is_dir: false,
+            old_size: None,
+            new_size: Some(1),
+            old_mtime: None,
+            new_mtime: Some(at),
+        }
+    }
+
+    #[test]
+    fn prune_follows_the_configured_retention() {
+        let now = 1_000 * DAY;
+        let mut store = StoreData::new();
+        store.changes = vec![change(now - 40 * DAY), change(now - 10 * DAY), change(now)];
+
+        let mut cfg = Config {
+            change_retention_days: 0,
+            ..Config::default()
+        };
+        assert_eq!(prune(&mut store, &cfg, now), 0);
+        cfg.change_retention_days = 30;
+        assert_eq!(prune(&mut store, &cfg, now), 1);
+        cfg.change_retention_days = 7;
+        assert_eq!(prune(&mut store, &cfg, now), 1);
+        assert_eq!(store.changes.len(), 1);
+        assert_eq!(store.changes[0].at, now);
+    }
+}
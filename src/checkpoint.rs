@@ -0,0 +1,76 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Progress of an index run that has not finished, saved next to the store
+/// together with the entries it indexed so far. The next run picks up from
+/// here instead of starting over.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// Run the saved entries were seen in; only valid while it is still the
+    /// store's `last_run_id`.
+    pub run_id: i64,
+    /// `next_file_id` when the run started; entries with ids from here on
+    /// were created by the run.
+    pub first_file_id: i64,
+    pub full: bool,
+    pub one_filesystem: bool,
+    /// Roots the run finished.
+    pub done: Vec<i64>,
+    /// The root the run was in the middle of.
+    pub partial: Option<PartialRoot>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PartialRoot {
+    pub root_id: i64,
+    /// When the scan of the root started, for its scan record.
+    pub started_at: i64,
+    /// Directories (relative to the root, `""` for the root itself) that
+    /// still have to be walked; everything else was indexed.
+    pub pending: Vec<String>,
+}
+
+/// `catalog.resume`, next to the store.
+pub fn checkpoint_path(store_path: &Path) -> PathBuf {
+    store_path.with_extension("resume")
+}
+
+/// The saved checkpoint, if any. An unreadable one is ignored: the run it
+/// describes is simply indexed again.
+pub fn load(store_path: &Path) -> Option<Checkpoint> {
+    let path = checkpoint_path(store_path);
+    let data = fs::read_to_string(&path).ok()?;
+    match serde_json::from_str(&data) {
+        Ok(checkpoint) => Some(checkpoint),
+        Err(err) => {
+            tracing::warn!("ignoring unreadable checkpoint {}: {}", path.display(), err);
+            None
+        }
+    }
+}
+
+/// Writes the checkpoint (temp file, then rename). Save the store first, so a
+/// checkpoint never claims more than the store holds.
+pub fn save(store_path: &Path, checkpoint: &Checkpoint) -> Result<()> {
+    let path = checkpoint_path(store_path);
+    let tmp = path.with_extension("resume.tmp");
+    let data = serde_json::to_string(checkpoint).context("failed to serialize checkpoint")?;
+    fs::write(&tmp, data)
+        .with_context(|| format!("failed to write checkpoint: {}", tmp.display()))?;
+    fs::rename(&tmp, &path)
+        .with_context(|| format!("failed to write checkpoint: {}", path.display()))?;
+    Ok(())
+}
+
+pub fn clear(store_path: &Path) -> Result<()> {
+    let path = checkpoint_path(store_path);
+    match fs::remove_file(&path) {
+        Ok(()) => Ok(()),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(err) => {
+            Err(err).with_context(|| format!("failed to remove checkpoint: {}", path.display()))
+        }
+    }
+}
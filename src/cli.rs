@@ -1,3 +1,5 @@
+pub use crate::changes::ChangeKind;
+pub use crate::search::{SortOrder, StatusFilter};
 use clap::{Parser, Subcommand, ValueEnum};
 
 #[derive(Parser)]
@@ -12,6 +14,9 @@ pub struct Cli {
     /// Enable debug logging
     #[arg(long, global = true)]
     pub debug: bool,
+    /// Wait for another catalog process to release the store instead of failing
+    #[arg(long, global = true)]
+    pub wait: bool,
     #[command(subcommand)]
     pub command: Commands,
 }
@@ -29,6 +34,10 @@ pub enum Commands {
     Add {
         #[arg(required = true)]
         paths: Vec<String>,
+        /// Show what indexing the new roots would pick up instead of adding
+        /// them
+        #[arg(long)]
+        preview: bool,
     },
     /// Remove roots
     Rm {
@@ -37,13 +46,25 @@ pub enum Commands {
     },
     /// Index configured roots
     Index {
+        /// List every directory instead of trusting unchanged directory mtimes
         #[arg(long)]
         full: bool,
         #[arg(long)]
         one_filesystem: bool,
+        /// Walk without touching the store and report what would be indexed
+        /// and what each exclude rule leaves out
+        #[arg(long)]
+        dry_run: bool,
+        /// Only preview these configured roots
+        #[arg(long, requires = "dry_run")]
+        root: Vec<String>,
     },
+    /// Explain why a path is or isn't indexed
+    Explain { path: String },
     /// Search indexed files
     Search {
+        /// Query, e.g. `name:report ext:pdf,docx size:>10MB -path:archive (tag:work OR root:~/Projects)`
+        #[arg(allow_hyphen_values = true)]
         query: String,
         #[arg(long)]
         ext: Option<String>,
@@ -57,27 +78,88 @@ pub enum Commands {
         max_size: Option<u64>,
         #[arg(long)]
         root: Option<String>,
+        /// Only files carrying this tag (repeatable; all must match)
+        #[arg(long)]
+        tag: Vec<String>,
+        /// Result order: best match first, or most recently modified first
+        #[arg(long, value_enum, default_value = "score")]
+        sort: SortOrder,
+        /// Entries to search: present files, soft-deleted ones, or both
+        #[arg(long, value_enum, default_value = "active")]
+        status: StatusFilter,
         #[arg(long)]
         json: bool,
         /// Show more metadata
         #[arg(long, alias = "details")]
         long: bool,
     },
+    /// Interactively filter indexed files and print the chosen path(s)
+    Find {
+        /// Initial query (same syntax as `search`)
+        #[arg(default_value = "", allow_hyphen_values = true)]
+        query: String,
+        #[arg(long)]
+        root: Option<String>,
+        /// Only files carrying this tag (repeatable; all must match)
+        #[arg(long)]
+        tag: Vec<String>,
+    },
     /// List recently modified files
     Recent {
         #[arg(long)]
         days: Option<u32>,
         #[arg(long)]
         limit: Option<u32>,
+        /// Only files carrying this tag (repeatable; all must match)
+        #[arg(long)]
+        tag: Vec<String>,
+        #[arg(long)]
+        json: bool,
+        /// Show more metadata
+        #[arg(long, alias = "details")]
+        long: bool,
+    },
+    /// List files deleted since they were indexed, most recent first
+    Deleted {
+        /// Only files deleted within this window (e.g. 12h, 2d, 1w) or since
+        /// a date (YYYY-MM-DD)
+        #[arg(long, default_value = "7d")]
+        since: String,
+        #[arg(long)]
+        root: Option<String>,
         #[arg(long)]
         json: bool,
         /// Show more metadata
         #[arg(long, alias = "details")]
         long: bool,
     },
-    /// Watch for changes (polling)
+    /// Show what index runs created, modified, moved and deleted
+    Changes {
+        /// Only changes recorded within this window (e.g. 12h, 2d, 1w) or
+        /// since a date (YYYY-MM-DD); default 7d unless --run is given
+        #[arg(long)]
+        since: Option<String>,
+        /// Only changes from this run id
+        #[arg(long)]
+        run: Option<i64>,
+        #[arg(long, value_enum)]
+        kind: Option<ChangeKind>,
+        #[arg(long)]
+        json: bool,
+    },
+    /// Tag indexed files
+    Tag {
+        #[command(subcommand)]
+        command: TagCommands,
+    },
+    /// List tags with file counts
+    Tags {
+        #[arg(long)]
+        json: bool,
+    },
+    /// Watch for changes (filesystem notifications, polling fallback)
     Watch {
-        /// Poll interval in seconds
+        /// Poll interval in seconds (polling backend)
         #[arg(long)]
         interval: Option<u64>,
         /// Force full rescan each interval
@@ -86,6 +168,12 @@ pub enum Commands {
         /// Override one-filesystem for this run
         #[arg(long)]
         one_filesystem: bool,
+        /// Use the polling backend instead of filesystem notifications
+        #[arg(long)]
+        poll: bool,
+        /// Quiet period in milliseconds before a burst of events is applied
+        #[arg(long)]
+        debounce: Option<u64>,
     },
     /// Export store as JSON
     Export {
@@ -95,6 +183,26 @@ pub enum Commands {
     },
     /// Remove all stored index state
     Prune,
+    /// Drop soft-deleted entries from the index
+    Purge {
+        /// Only entries deleted before this long ago (e.g. 90d) or date
+        #[arg(long)]
+        older_than: Option<String>,
+        /// Only entries under this root
+        #[arg(long)]
+        root: Option<String>,
+    },
+    /// Check the store for damage and broken invariants
+    Fsck {
+        /// Fix what can be fixed, keeping a backup of the store
+        #[arg(long)]
+        repair: bool,
+    },
+    /// Store maintenance
+    Store {
+        #[command(subcommand)]
+        command: StoreCommands,
+    },
     /// Analyze disk usage
     Analyze {
         /// Path to analyze (defaults to configured roots)
@@ -117,6 +225,37 @@ pub enum Commands {
     },
 }
 
+#[derive(Subcommand)]
+pub enum TagCommands {
+    /// Add a tag to files (paths or ids)
+    Add {
+        tag: String,
+        #[arg(required = true)]
+        targets: Vec<String>,
+    },
+    /// Remove a tag from files (paths or ids)
+    Rm {
+        tag: String,
+        #[arg(required = true)]
+        targets: Vec<String>,
+    },
+    /// Show the tags of files (paths or ids)
+    Ls {
+        #[arg(required = true)]
+        targets: Vec<String>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum StoreCommands {
+    /// Upgrade a store written by an older catalog, keeping a backup
+    Migrate {
+        /// Report what would be upgraded without writing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
 #[derive(Clone, Debug, ValueEnum)]
 pub enum Preset {
     #[value(name = "macos-user-additions")]
@@ -125,6 +264,12 @@ pub enum Preset {
     MacosDeep,
     #[value(name = "macos-full")]
     MacosFull,
+    #[value(name = "linux-user-additions")]
+    LinuxUserAdditions,
+    #[value(name = "linux-deep")]
+    LinuxDeep,
+    #[value(name = "linux-full")]
+    LinuxFull,
 }
 
 impl Preset {
@@ -133,6 +278,18 @@ impl Preset {
             Preset::MacosUserAdditions => "macos-user-additions".to_string(),
             Preset::MacosDeep => "macos-deep".to_string(),
             Preset::MacosFull => "macos-full".to_string(),
+            Preset::LinuxUserAdditions => "linux-user-additions".to_string(),
+            Preset::LinuxDeep => "linux-deep".to_string(),
+            Preset::LinuxFull => "linux-full".to_string(),
+        }
+    }
+
+    /// The full-system preset for the platform `catalog` was built for.
+    pub fn platform_default() -> Self {
+        if cfg!(target_os = "macos") {
+            Preset::MacosFull
+        } else {
+            Preset::LinuxFull
         }
     }
 }
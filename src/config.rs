@@ -1,7 +1,8 @@
 use crate::cli::Preset;
+use crate::query;
 use crate::util::{expand_tilde, normalize_path, normalize_path_allow_missing, path_to_string};
 use anyhow::{Context, Result};
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::fs;
 use std::path::{Path, PathBuf};
 
@@ -18,8 +19,28 @@ pub struct Config {
     pub output: OutputMode,
     pub include_hidden: bool,
     pub one_filesystem: bool,
-    pub roots: Vec<String>,
+    #[serde(
+        deserialize_with = "deserialize_roots",
+        serialize_with = "serialize_roots"
+    )]
+    pub roots: Vec<RootConfig>,
     pub excludes: Vec<String>,
+    /// Honour `.gitignore`, `.ignore` and `.git/info/exclude` inside roots.
+    #[serde(default)]
+    pub vcs_ignore: bool,
+    /// Honour git's global excludes file (`core.excludesFile`).
+    #[serde(default)]
+    pub git_global_ignore: bool,
+    /// Honour `.catalogignore` files inside roots.
+    #[serde(default)]
+    pub catalog_ignore: bool,
+    /// Days a deleted entry stays in the index before it is purged; 0 keeps
+    /// deleted entries forever.
+    #[serde(default = "default_deleted_retention_days")]
+    pub deleted_retention_days: u32,
+    /// Days change journal records are kept; 0 keeps them forever.
+    #[serde(default = "default_change_retention_days")]
+    pub change_retention_days: u32,
 }
 
 impl Default for Config {
@@ -31,10 +52,209 @@ impl Default for Config {
             one_filesystem: true,
             roots: Vec::new(),
             excludes: default_excludes(),
+            vcs_ignore: false,
+            git_global_ignore: false,
+            catalog_ignore: false,
+            deleted_retention_days: default_deleted_retention_days(),
+            change_retention_days: default_change_retention_days(),
         }
     }
 }
 
+fn default_deleted_retention_days() -> u32 {
+    90
+}
+
+fn default_change_retention_days() -> u32 {
+    30
+}
+
+impl Config {
+    /// The configured root at `path`.
+    pub fn root(&self, path: &str) -> Option<&RootConfig> {
+        self.roots.iter().find(|r| r.path == path)
+    }
+
+    /// Walk settings of the root at `path`: the global ones with the root's
+    /// overrides applied.
+    pub fn root_options(&self, path: &str) -> RootOptions {
+        let root = self.root(path);
+        let mut excludes = self.excludes.clone();
+        if let Some(root) = root {
+            excludes.extend(root.excludes.iter().cloned());
+        }
+        RootOptions {
+            excludes,
+            include_hidden: root
+                .and_then(|r| r.include_hidden)
+                .unwrap_or(self.include_hidden),
+            one_filesystem: root
+                .and_then(|r| r.one_filesystem)
+                .unwrap_or(self.one_filesystem),
+            max_depth: root.and_then(|r| r.max_depth),
+            follow_symlinks: root.is_some_and(|r| r.follow_symlinks),
+            ignore_files: match root {
+                Some(root) => root.ignore_files(self.ignore_files()),
+                None => self.ignore_files(),
+            },
+        }
+    }
+
+    /// The ignore files honoured by roots that do not override them.
+    pub fn ignore_files(&self) -> IgnoreFiles {
+        IgnoreFiles {
+            vcs: self.vcs_ignore,
+            git_global: self.git_global_ignore,
+            catalog: self.catalog_ignore,
+        }
+    }
+}
+
+/// A configured root. Written as a plain path in `config.toml` unless it
+/// overrides something, then as a table with a `path` key.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RootConfig {
+    pub path: String,
+    /// Added to the global excludes for this root.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub excludes: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub include_hidden: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub one_filesystem: Option<bool>,
+    /// Deepest level below the root that is indexed; 1 keeps only its
+    /// direct children.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_depth: Option<usize>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub follow_symlinks: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vcs_ignore: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub git_global_ignore: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub catalog_ignore: Option<bool>,
+    /// Minimum time between index runs of this root, as an age like `6h`
+    /// or `1d`; `catalog index` skips it until then.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interval: Option<String>,
+}
+
+impl RootConfig {
+    /// No overrides: written as a plain path.
+    pub fn is_plain(&self) -> bool {
+        *self == RootConfig::from(self.path.clone())
+    }
+
+    /// The ignore files this root honours, given the global ones.
+    pub fn ignore_files(&self, global: IgnoreFiles) -> IgnoreFiles {
+        IgnoreFiles {
+            vcs: self.vcs_ignore.unwrap_or(global.vcs),
+            git_global: self.git_global_ignore.unwrap_or(global.git_global),
+            catalog: self.catalog_ignore.unwrap_or(global.catalog),
+        }
+    }
+
+    /// `interval` in seconds (validated when the config is loaded).
+    pub fn interval_secs(&self) -> Option<i64> {
+        self.interval.as_deref().and_then(query::age_seconds)
+    }
+}
+
+impl From<String> for RootConfig {
+    fn from(path: String) -> Self {
+        Self {
+            path,
+            ..Self::default()
+        }
+    }
+}
+
+/// The settings a root is walked with; see `Config::root_options`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RootOptions {
+    pub excludes: Vec<String>,
+    pub include_hidden: bool,
+    pub one_filesystem: bool,
+    pub max_depth: Option<usize>,
+    pub follow_symlinks: bool,
+    pub ignore_files: IgnoreFiles,
+}
+
+/// Which ignore files found while walking a root exclude entries.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct IgnoreFiles {
+    pub vcs: bool,
+    pub git_global: bool,
+    pub catalog: bool,
+}
+
+impl IgnoreFiles {
+    pub fn any(&self) -> bool {
+        self.vcs || self.git_global || self.catalog
+    }
+
+    /// For `catalog roots`, e.g. `.gitignore/.ignore + .catalogignore`.
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if self.vcs {
+            parts.push(".gitignore/.ignore");
+        }
+        if self.git_global {
+            parts.push("global git excludes");
+        }
+        if self.catalog {
+            parts.push(".catalogignore");
+        }
+        if parts.is_empty() {
+            "off".to_string()
+        } else {
+            parts.join(" + ")
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(
+    untagged,
+    expecting = "a path or a table with a path and root settings"
+)]
+enum RootSpec {
+    Path(String),
+    Table(RootConfig),
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum RootSpecRef<'a> {
+    Path(&'a str),
+    Table(&'a RootConfig),
+}
+
+fn deserialize_roots<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<RootConfig>, D::Error> {
+    let specs = Vec::<RootSpec>::deserialize(deserializer)?;
+    Ok(specs
+        .into_iter()
+        .map(|spec| match spec {
+            RootSpec::Path(path) => RootConfig::from(path),
+            RootSpec::Table(root) => root,
+        })
+        .collect())
+}
+
+fn serialize_roots<S: Serializer>(roots: &[RootConfig], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(roots.iter().map(|root| {
+        if root.is_plain() {
+            RootSpecRef::Path(&root.path)
+        } else {
+            RootSpecRef::Table(root)
+        }
+    }))
+}
+
 pub struct Paths {
     pub config_path: PathBuf,
     pub store_path: PathBuf,
@@ -74,7 +294,7 @@ pub fn init(paths: &Paths, preset: Option<Preset>) -> Result<()> {
     };
 
     let default_preset = if preset.is_none() && !paths.config_path.exists() {
-        Some(Preset::MacosFull)
+        Some(Preset::platform_default())
     } else {
         None
     };
@@ -90,7 +310,18 @@ pub fn init(paths: &Paths, preset: Option<Preset>) -> Result<()> {
 pub fn load(path: &Path) -> Result<Config> {
     let data = fs::read_to_string(path)
         .with_context(|| format!("failed to read config: {}", path.display()))?;
-    let cfg = toml::from_str(&data).context("failed to parse config")?;
+    let cfg: Config = toml::from_str(&data).context("failed to parse config")?;
+    for root in &cfg.roots {
+        if let Some(interval) = &root.interval
+            && query::age_seconds(interval).is_none()
+        {
+            anyhow::bail!(
+                "invalid interval '{}' for root {} (expected an age like 12h, 1d, 2w)",
+                interval,
+                root.path
+            );
+        }
+    }
     Ok(cfg)
 }
 
@@ -109,7 +340,13 @@ pub fn apply_preset(cfg: &mut Config, preset: Preset) -> Result<()> {
             r.extend(macos_deep_roots());
             r
         }
-        Preset::MacosFull => macos_full_roots(),
+        Preset::MacosFull | Preset::LinuxFull => full_roots(),
+        Preset::LinuxUserAdditions => linux_user_additions_roots(),
+        Preset::LinuxDeep => {
+            let mut r = linux_user_additions_roots();
+            r.extend(linux_deep_roots());
+            r
+        }
     };
     let mut normalized = Vec::new();
     for root in roots {
@@ -119,28 +356,83 @@ pub fn apply_preset(cfg: &mut Config, preset: Preset) -> Result<()> {
             normalized.push(path_to_string(&canonical));
         }
     }
-    cfg.roots = normalized;
+    cfg.roots = normalized.into_iter().map(RootConfig::from).collect();
     match preset {
         Preset::MacosFull => {
             cfg.excludes = Vec::new();
             cfg.include_hidden = true;
             cfg.one_filesystem = true;
         }
-        _ => {
-            cfg.excludes = default_excludes();
+        Preset::LinuxFull => {
+            cfg.excludes = linux_full_excludes();
+            cfg.include_hidden = true;
+            cfg.one_filesystem = true;
+        }
+        Preset::LinuxUserAdditions | Preset::LinuxDeep => {
+            cfg.excludes = linux_default_excludes();
+        }
+        Preset::MacosUserAdditions | Preset::MacosDeep => {
+            cfg.excludes = macos_default_excludes();
         }
     }
     Ok(())
 }
 
 pub fn default_config_path() -> Result<PathBuf> {
-    let home = std::env::var_os("HOME").context("HOME not set")?;
-    Ok(PathBuf::from(home).join("Library/Application Support/catalog/config.toml"))
+    Ok(config_dir()?.join("config.toml"))
 }
 
 pub fn default_store_path() -> Result<PathBuf> {
+    Ok(data_dir()?.join("catalog.bin"))
+}
+
+#[cfg(target_os = "macos")]
+fn config_dir() -> Result<PathBuf> {
+    let home = std::env::var_os("HOME").context("HOME not set")?;
+    Ok(PathBuf::from(home).join("Library/Application Support/catalog"))
+}
+
+#[cfg(target_os = "macos")]
+fn data_dir() -> Result<PathBuf> {
+    config_dir()
+}
+
+/// `$XDG_CONFIG_HOME/catalog`, defaulting to `~/.config/catalog`.
+#[cfg(not(target_os = "macos"))]
+fn config_dir() -> Result<PathBuf> {
     let home = std::env::var_os("HOME").context("HOME not set")?;
-    Ok(PathBuf::from(home).join("Library/Application Support/catalog/catalog.bin"))
+    let base = xdg_dir(std::env::var_os("XDG_CONFIG_HOME"), Path::new(&home), ".config");
+    Ok(legacy_fallback(base.join("catalog"), Path::new(&home), "config.toml"))
+}
+
+/// `$XDG_DATA_HOME/catalog`, defaulting to `~/.local/share/catalog`.
+#[cfg(not(target_os = "macos"))]
+fn data_dir() -> Result<PathBuf> {
+    let home = std::env::var_os("HOME").context("HOME not set")?;
+    let base = xdg_dir(std::env::var_os("XDG_DATA_HOME"), Path::new(&home), ".local/share");
+    Ok(legacy_fallback(base.join("catalog"), Path::new(&home), "catalog.bin"))
+}
+
+/// Releases before the XDG paths kept everything in
+/// `~/Library/Application Support/catalog` on every platform. That directory
+/// is used while it holds `file` and `dir` does not.
+#[cfg(not(target_os = "macos"))]
+fn legacy_fallback(dir: PathBuf, home: &Path, file: &str) -> PathBuf {
+    let legacy = home.join("Library/Application Support/catalog");
+    if !dir.join(file).exists() && legacy.join(file).exists() {
+        legacy
+    } else {
+        dir
+    }
+}
+
+/// The XDG base directory spec says relative values must be ignored.
+#[cfg(not(target_os = "macos"))]
+fn xdg_dir(value: Option<std::ffi::OsString>, home: &Path, fallback: &str) -> PathBuf {
+    match value.map(PathBuf::from) {
+        Some(dir) if dir.is_absolute() => dir,
+        _ => home.join(fallback),
+    }
 }
 
 fn ensure_parent_dir(path: &Path) -> Result<()> {
@@ -197,11 +489,87 @@ fn macos_deep_roots() -> Vec<String> {
     .collect()
 }
 
-fn macos_full_roots() -> Vec<String> {
+fn linux_user_additions_roots() -> Vec<String> {
+    let mut roots = vec![
+        "~/Downloads",
+        "~/Desktop",
+        "~/Documents",
+        "/opt",
+        "/usr/local",
+        "~/bin",
+        "~/.local/bin",
+        "~/.config",
+        "~/Applications",
+    ]
+    .into_iter()
+    .map(String::from)
+    .collect::<Vec<_>>();
+
+    let projects = ["~/Projects", "~/projects", "~/src", "~/code"]
+        .into_iter()
+        .map(expand_tilde)
+        .find(|p| p.exists());
+    if let Some(projects) = projects {
+        roots.push(path_to_string(&projects));
+    }
+
+    roots
+}
+
+fn linux_deep_roots() -> Vec<String> {
+    vec![
+        "/etc/systemd",
+        "/etc/xdg",
+        "/etc/profile.d",
+        "/etc/cron.d",
+        "/usr/share/applications",
+        "~/.local/share/applications",
+        "/usr/share/fonts",
+        "~/.local/share/fonts",
+    ]
+    .into_iter()
+    .map(String::from)
+    .collect()
+}
+
+fn full_roots() -> Vec<String> {
     vec!["/".to_string()]
 }
 
 fn default_excludes() -> Vec<String> {
+    if cfg!(target_os = "macos") {
+        macos_default_excludes()
+    } else {
+        linux_default_excludes()
+    }
+}
+
+/// Kernel and runtime pseudo-filesystems; none of it is worth indexing.
+fn linux_full_excludes() -> Vec<String> {
+    vec!["/proc", "/sys", "/run", "/dev", "~/.cache"]
+        .into_iter()
+        .map(String::from)
+        .collect()
+}
+
+fn linux_default_excludes() -> Vec<String> {
+    let mut excludes = linux_full_excludes();
+    excludes.extend(
+        [
+            "~/.local/share/Trash",
+            "**/.git/**",
+            "**/node_modules/**",
+            "**/target/**",
+            "**/dist/**",
+            "**/build/**",
+        ]
+        .into_iter()
+        .map(String::from),
+    );
+    excludes
+}
+
+fn macos_default_excludes() -> Vec<String> {
     vec![
         "~/Library/Caches",
         "~/Library/Containers",
@@ -244,12 +612,98 @@ mod tests {
             output: OutputMode::Json,
             include_hidden: true,
             one_filesystem: false,
-            roots: vec!["/tmp".to_string()],
+            roots: vec!["/tmp".to_string().into()],
             excludes: vec!["**/node_modules/**".to_string()],
+            vcs_ignore: false,
+            git_global_ignore: false,
+            catalog_ignore: false,
+            deleted_retention_days: 90,
+            change_retention_days: 30,
         };
 
         save(&path, &cfg).unwrap();
         let loaded = load(&path).unwrap();
         assert_eq!(cfg, loaded);
     }
+
+    #[test]
+    fn linux_full_preset_skips_pseudo_filesystems() {
+        let mut cfg = Config::default();
+        apply_preset(&mut cfg, Preset::LinuxFull).unwrap();
+        assert_eq!(cfg.roots, vec![RootConfig::from("/".to_string())]);
+        assert!(cfg.include_hidden);
+        for dir in ["/proc", "/sys", "/run", "~/.cache"] {
+            assert!(cfg.excludes.iter().any(|e| e == dir), "{}", dir);
+        }
+
+        #[cfg(not(target_os = "macos"))]
+        {
+            let home = Path::new("/home/alice");
+            assert_eq!(
+                xdg_dir(Some("/xdg/config".into()), home, ".config"),
+                PathBuf::from("/xdg/config")
+            );
+            assert_eq!(
+                xdg_dir(Some("relative".into()), home, ".config"),
+                home.join(".config")
+            );
+            assert_eq!(xdg_dir(None, home, ".local/share"), home.join(".local/share"));
+        }
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    #[test]
+    fn legacy_directory_is_used_until_the_xdg_one_exists() {
+        let home = temp_dir("config_legacy");
+        let xdg = home.join(".config/catalog");
+        let legacy = home.join("Library/Application Support/catalog");
+        assert_eq!(legacy_fallback(xdg.clone(), &home, "config.toml"), xdg);
+
+        fs::create_dir_all(&legacy).unwrap();
+        fs::write(legacy.join("config.toml"), "version = 1").unwrap();
+        assert_eq!(legacy_fallback(xdg.clone(), &home, "config.toml"), legacy);
+        assert_eq!(legacy_fallback(xdg.clone(), &home, "catalog.bin"), xdg);
+
+        fs::create_dir_all(&xdg).unwrap();
+        fs::write(xdg.join("config.toml"), "version = 1").unwrap();
+        assert_eq!(legacy_fallback(xdg.clone(), &home, "config.toml"), xdg);
+    }
+
+    #[test]
+    fn roots_accept_paths_and_tables() {
+        let dir = temp_dir("config_roots");
+        let path = dir.join("config.toml");
+        let data = r#"
+version = 1
+output = "plain"
+include_hidden = false
+one_filesystem = true
+excludes = ["**/target/**"]
+roots = [
+    "/home/alice/Projects",
+    { path = "/home/alice/.config", include_hidden = true, excludes = ["*.log"], max_depth = 4 },
+]
+"#;
+        fs::write(&path, data).unwrap();
+        let cfg = load(&path).unwrap();
+        assert_eq!(cfg.roots.len(), 2);
+        assert!(cfg.roots[0].is_plain());
+        assert_eq!(cfg.roots[1].max_depth, Some(4));
+
+        let config = cfg.root_options("/home/alice/.config");
+        assert!(config.include_hidden);
+        assert!(config.one_filesystem);
+        assert_eq!(config.excludes, vec!["**/target/**", "*.log"]);
+        let projects = cfg.root_options("/home/alice/Projects");
+        assert!(!projects.include_hidden);
+        assert_eq!(projects.excludes, vec!["**/target/**"]);
+
+        save(&path, &cfg).unwrap();
+        assert_eq!(load(&path).unwrap(), cfg);
+
+        let bad = data.replace("max_depth = 4", "interval = \"soon\"");
+        fs::write(&path, bad).unwrap();
+        let err = load(&path).unwrap_err();
+        assert!(format!("{:#}", err).contains("invalid interval 'soon'"));
+    }
 }
@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+
+/// Directories interned as a parent-pointer tree: each node is one path
+/// component below its parent, so a parent directory is stored once however
+/// many entries live under it. Parents always come before their children.
+///
+/// `"/Users/alice"` is three nodes: `""` (the filesystem root), `"Users"` and
+/// `"alice"`; a node's path is its parent's path, `/`, and its name.
+#[derive(Debug, Default, Clone)]
+pub struct DirTree {
+    nodes: Vec<DirNode>,
+    lookup: HashMap<(u32, String), u32>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DirNode {
+    pub parent: Option<u32>,
+    pub name: String,
+}
+
+impl DirTree {
+    pub fn from_nodes(nodes: Vec<DirNode>) -> Self {
+        let lookup = nodes
+            .iter()
+            .enumerate()
+            .map(|(id, node)| {
+                let parent = node.parent.unwrap_or(u32::MAX);
+                ((parent, node.name.clone()), id as u32)
+            })
+            .collect();
+        Self { nodes, lookup }
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn nodes(&self) -> &[DirNode] {
+        &self.nodes
+    }
+
+    pub fn parent(&self, node: u32) -> Option<u32> {
+        self.nodes[node as usize].parent
+    }
+
+    /// Node for directory `dir`, adding it and any missing ancestors.
+    pub fn intern(&mut self, dir: &str) -> u32 {
+        let mut parent: Option<u32> = None;
+        for name in dir.split('/') {
+            let key = (parent.unwrap_or(u32::MAX), name.to_string());
+            let id = match self.lookup.get(&key) {
+                Some(&id) => id,
+                None => {
+                    let id = self.nodes.len() as u32;
+                    self.nodes.push(DirNode {
+                        parent,
+                        name: name.to_string(),
+                    });
+                    self.lookup.insert(key, id);
+                    id
+                }
+            };
+            parent = Some(id);
+        }
+        parent.expect("split yields at least one component")
+    }
+
+    /// Node for directory `dir` if it was interned.
+    pub fn find(&self, dir: &str) -> Option<u32> {
+        let mut parent: Option<u32> = None;
+        for name in dir.split('/') {
+            let key = (parent.unwrap_or(u32::MAX), name.to_string());
+            parent = Some(*self.lookup.get(&key)?);
+        }
+        parent
+    }
+
+    pub fn path(&self, node: u32) -> String {
+        let mut names = Vec::new();
+        let mut current = Some(node);
+        while let Some(id) = current {
+            let node = &self.nodes[id as usize];
+            names.push(node.name.as_str());
+            current = node.parent;
+        }
+        names.reverse();
+        names.join("/")
+    }
+
+    /// Every node's path, built parents-first in one pass.
+    pub fn paths(&self) -> Vec<String> {
+        let mut out: Vec<String> = Vec::with_capacity(self.nodes.len());
+        for node in &self.nodes {
+            let path = match node.parent {
+                Some(parent) => format!("{}/{}", out[parent as usize], node.name),
+                None => node.name.clone(),
+            };
+            out.push(path);
+        }
+        out
+    }
+
+    /// Whether `node` is `ancestor` or lies below it.
+    pub fn is_within(&self, node: u32, ancestor: u32) -> bool {
+        let mut current = Some(node);
+        while let Some(id) = current {
+            if id == ancestor {
+                return true;
+            }
+            current = self.parent(id);
+        }
+        false
+    }
+}
+
+/// Splits an absolute path into its directory and base name.
+pub fn split(path: &str) -> Option<(&str, &str)> {
+    path.rsplit_once('/')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn intern_shares_parents_and_rebuilds_paths() {
+        let mut tree = DirTree::default();
+        let docs = tree.intern("/Users/alice/docs");
+        let src = tree.intern("/Users/alice/src");
+        let root = tree.intern("");
+        assert_eq!(tree.len(), 5);
+        assert_eq!(tree.path(docs), "/Users/alice/docs");
+        assert_eq!(tree.path(root), "");
+        assert_eq!(tree.parent(docs), tree.parent(src));
+        assert_eq!(tree.find("/Users/alice/src"), Some(src));
+        assert_eq!(tree.find("/Users/bob"), None);
+        assert!(tree.is_within(docs, tree.find("/Users").unwrap()));
+        assert!(!tree.is_within(docs, src));
+
+        let paths = tree.paths();
+        assert_eq!(paths[docs as usize], "/Users/alice/docs");
+        let rebuilt = DirTree::from_nodes(tree.nodes().to_vec());
+        assert_eq!(rebuilt.find("/Users/alice/docs"), Some(docs));
+    }
+}
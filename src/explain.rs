@@ -0,0 +1,361 @@
+use crate::config::{Config, RootConfig};
+use crate::indexer::{self, Exclusion};
+use crate::output::{format_time, human_size};
+use crate::roots::{self, RootState};
+use crate::store::{FileEntry, Store};
+use crate::util::{device_id, expand_tilde, normalize_path_allow_missing, path_to_string};
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// What `catalog explain` found out about a path.
+#[derive(Debug)]
+pub struct Explanation {
+    pub path: PathBuf,
+    /// The configured root the path is under (the innermost one).
+    pub root: Option<String>,
+    pub verdict: Verdict,
+    /// The store's entry for the path, preferring an active one.
+    pub entry: Option<FileEntry>,
+}
+
+/// Whether an index run reaches the path, and the first reason it does not.
+#[derive(Debug, PartialEq)]
+pub enum Verdict {
+    NoRoot,
+    /// The root's volume is not mounted. Carries the volume name.
+    RootOffline(String),
+    /// Left out by a rule; `source` says where the rule is configured.
+    Excluded {
+        rule: Exclusion,
+        source: String,
+    },
+    /// Below this symlinked directory, which is not followed.
+    UnderSymlink(PathBuf),
+    /// On another filesystem than the root, mounted here.
+    OtherFilesystem(PathBuf),
+    /// This directory (or the path itself) cannot be read.
+    PermissionDenied(PathBuf),
+    Missing,
+    Indexed,
+}
+
+/// Explains whether and why `input` is indexed, running the walker's own
+/// rules for the root it is under.
+pub fn explain(store: &Store, cfg: &Config, input: &str) -> Result<Explanation> {
+    // Symlinks along the path matter to the walk, so they are kept unless
+    // the path is only under a root once resolved (e.g. `/tmp` on macOS).
+    let lexical = absolute(input)?;
+    let (path, root) = match innermost_root(cfg, &lexical) {
+        Some(root) => (lexical, Some(root)),
+        None => {
+            let canonical = normalize_path_allow_missing(input)?;
+            let root = innermost_root(cfg, &canonical);
+            (canonical, root)
+        }
+    };
+    let entry = find_entry(store, &path_to_string(&path));
+    let meta = fs::symlink_metadata(&path);
+    let is_dir = match (&meta, &entry) {
+        (Ok(meta), _) => meta.is_dir(),
+        (Err(_), Some(entry)) => entry.is_dir,
+        (Err(_), None) => false,
+    };
+
+    let Some(root) = root else {
+        return Ok(Explanation {
+            path,
+            root: None,
+            verdict: Verdict::NoRoot,
+            entry,
+        });
+    };
+    let verdict = judge(store, cfg, root, &path, is_dir, meta.err())?;
+    Ok(Explanation {
+        path,
+        root: Some(root.path.clone()),
+        verdict,
+        entry,
+    })
+}
+
+fn judge(
+    store: &Store,
+    cfg: &Config,
+    root: &RootConfig,
+    path: &Path,
+    is_dir: bool,
+    meta_err: Option<std::io::Error>,
+) -> Result<Verdict> {
+    if let Some(entry) = store.data.roots.iter().find(|r| r.path == root.path)
+        && let Some(RootState::Offline(name)) =
+            roots::resolve_roots(std::slice::from_ref(entry)).remove(&entry.id)
+    {
+        return Ok(Verdict::RootOffline(name));
+    }
+
+    let options = cfg.root_options(&root.path);
+    if let Some(rule) = indexer::exclusion(&options, &root.path, path, is_dir)? {
+        let source = rule_source(root, &rule);
+        return Ok(Verdict::Excluded { rule, source });
+    }
+
+    // Directories between the root and the path, outermost first.
+    let root_path = Path::new(&root.path);
+    let mut dirs = path
+        .ancestors()
+        .skip(1)
+        .take_while(|dir| dir.starts_with(root_path) && *dir != root_path)
+        .collect::<Vec<_>>();
+    dirs.reverse();
+
+    if !options.follow_symlinks
+        && let Some(link) = dirs.iter().find(|dir| dir.is_symlink())
+    {
+        return Ok(Verdict::UnderSymlink(link.to_path_buf()));
+    }
+    if options.one_filesystem
+        && let Some(root_dev) = fs::metadata(root_path).ok().and_then(|m| device_id(&m))
+    {
+        let other = dirs
+            .iter()
+            .copied()
+            .chain(std::iter::once(path))
+            .find(|dir| {
+                fs::symlink_metadata(dir)
+                    .ok()
+                    .and_then(|m| device_id(&m))
+                    .is_some_and(|dev| dev != root_dev)
+            });
+        if let Some(mount) = other {
+            return Ok(Verdict::OtherFilesystem(mount.to_path_buf()));
+        }
+    }
+    let unreadable = std::iter::once(root_path)
+        .chain(dirs.iter().copied())
+        .find(|dir| {
+            fs::read_dir(dir).is_err_and(|err| err.kind() == std::io::ErrorKind::PermissionDenied)
+        });
+    if let Some(dir) = unreadable {
+        return Ok(Verdict::PermissionDenied(dir.to_path_buf()));
+    }
+    Ok(match meta_err {
+        Some(err) if err.kind() == std::io::ErrorKind::PermissionDenied => {
+            Verdict::PermissionDenied(path.to_path_buf())
+        }
+        Some(_) => Verdict::Missing,
+        None => Verdict::Indexed,
+    })
+}
+
+pub fn print(explanation: &Explanation) {
+    println!("Path:    {}", explanation.path.display());
+    println!("Root:    {}", explanation.root.as_deref().unwrap_or("none"));
+    let verdict = match &explanation.verdict {
+        Verdict::NoRoot => "not indexed: not under any configured root".to_string(),
+        Verdict::RootOffline(name) => format!(
+            "not indexed now: the root's volume {} is not mounted (entries are kept)",
+            name
+        ),
+        Verdict::Excluded { rule, source } => format!("excluded: {} ({})", rule, source),
+        Verdict::UnderSymlink(link) => format!(
+            "not indexed: {} is a symlink and follow_symlinks is off",
+            link.display()
+        ),
+        Verdict::OtherFilesystem(mount) => format!(
+            "not indexed: {} is on another filesystem and one_filesystem is on",
+            mount.display()
+        ),
+        Verdict::PermissionDenied(dir) => {
+            format!("skipped: permission denied reading {}", dir.display())
+        }
+        Verdict::Missing => "not indexed: the path does not exist".to_string(),
+        Verdict::Indexed => match &explanation.entry {
+            Some(entry) if entry.status == "active" => "indexed".to_string(),
+            _ => "included; indexed by the next `catalog index`".to_string(),
+        },
+    };
+    println!("Verdict: {}", verdict);
+    match &explanation.entry {
+        Some(entry) => {
+            let status = match entry.deleted_at {
+                Some(at) if entry.status == "deleted" => format!("deleted {}", format_time(at)),
+                _ => entry.status.clone(),
+            };
+            println!(
+                "Entry:   id {}, {}, {}{}, modified {}, last seen in run {}",
+                entry.id,
+                status,
+                if entry.is_dir { "dir, " } else { "" },
+                human_size(entry.size),
+                format_time(entry.mtime),
+                entry.last_seen_run
+            );
+        }
+        None => println!("Entry:   none"),
+    }
+}
+
+/// Where the setting behind `rule` is configured for `root`.
+pub fn rule_source(root: &RootConfig, rule: &Exclusion) -> String {
+    match rule {
+        Exclusion::Hidden => "include_hidden = false".to_string(),
+        Exclusion::TooDeep(_) => "the root's max_depth".to_string(),
+        Exclusion::AbsoluteExclude(ex) | Exclusion::Pattern(ex) => {
+            if root.excludes.contains(ex) {
+                "the root's excludes".to_string()
+            } else {
+                "excludes".to_string()
+            }
+        }
+        Exclusion::IgnoreFile(hit) => path_to_string(&hit.file),
+    }
+}
+
+/// The configured root that contains `path` most closely.
+fn innermost_root<'a>(cfg: &'a Config, path: &Path) -> Option<&'a RootConfig> {
+    cfg.roots
+        .iter()
+        .filter(|root| path.starts_with(expand_tilde(&root.path)))
+        .max_by_key(|root| root.path.len())
+}
+
+fn find_entry(store: &Store, path: &str) -> Option<FileEntry> {
+    let mut found = None;
+    for file in store.files().filter(|f| f.abs_path() == path) {
+        if file.status == "active" {
+            return Some(file.to_entry());
+        }
+        found = Some(file.to_entry());
+    }
+    found
+}
+
+/// `input` made absolute with `.` and `..` resolved, without following
+/// symlinks.
+fn absolute(input: &str) -> Result<PathBuf> {
+    let expanded = expand_tilde(input);
+    let joined = if expanded.is_absolute() {
+        expanded
+    } else {
+        std::env::current_dir()
+            .context("failed to read current dir")?
+            .join(expanded)
+    };
+    let mut out = PathBuf::new();
+    for component in joined.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                out.pop();
+            }
+            other => out.push(other),
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indexer;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn temp_dir(prefix: &str) -> PathBuf {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!(
+            "catalog_test_{}_{}_{}",
+            prefix,
+            std::process::id(),
+            nanos
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write_file(path: &Path, contents: &str) {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).unwrap();
+        }
+        fs::write(path, contents).unwrap();
+    }
+
+    #[test]
+    fn explain_names_the_deciding_rule() {
+        let dir = fs::canonicalize(temp_dir("explain")).unwrap();
+        let (root, nested) = (dir.join("root"), dir.join("root/nested"));
+        write_file(&root.join("kept.txt"), "kept");
+        write_file(&root.join("cache/blob"), "blob");
+        write_file(&root.join(".config/app.toml"), "app");
+        write_file(&nested.join("debug.log"), "log");
+        write_file(&root.join("debug.log"), "log");
+
+        let cfg = Config {
+            one_filesystem: false,
+            roots: vec![
+                path_to_string(&root).into(),
+                RootConfig {
+                    path: path_to_string(&nested),
+                    excludes: vec!["*.log".to_string()],
+                    ..Default::default()
+                },
+            ],
+            excludes: vec!["cache/".to_string()],
+            ..Config::default()
+        };
+        let mut store = Store::load(&dir.join("catalog.bin")).unwrap();
+        let path = |p: &Path| path_to_string(p);
+
+        let pending = explain(&store, &cfg, &path(&root.join("kept.txt"))).unwrap();
+        assert_eq!(pending.verdict, Verdict::Indexed);
+        assert!(pending.entry.is_none());
+
+        indexer::run(&mut store, &cfg, false, false).unwrap();
+        let kept = explain(&store, &cfg, &path(&root.join("sub/../kept.txt"))).unwrap();
+        assert_eq!(kept.path, root.join("kept.txt"));
+        assert_eq!(kept.root, Some(path(&root)));
+        assert_eq!(kept.verdict, Verdict::Indexed);
+        assert_eq!(kept.entry.map(|e| e.status), Some("active".to_string()));
+
+        let blob = explain(&store, &cfg, &path(&root.join("cache/blob"))).unwrap();
+        assert_eq!(
+            blob.verdict,
+            Verdict::Excluded {
+                rule: Exclusion::Pattern("cache/".to_string()),
+                source: "excludes".to_string(),
+            }
+        );
+        let hidden = explain(&store, &cfg, &path(&root.join(".config/app.toml"))).unwrap();
+        assert!(matches!(
+            hidden.verdict,
+            Verdict::Excluded {
+                rule: Exclusion::Hidden,
+                ..
+            }
+        ));
+
+        // The innermost root decides, with its own excludes.
+        let log = explain(&store, &cfg, &path(&nested.join("debug.log"))).unwrap();
+        assert_eq!(log.root, Some(path(&nested)));
+        assert_eq!(
+            log.verdict,
+            Verdict::Excluded {
+                rule: Exclusion::Pattern("*.log".to_string()),
+                source: "the root's excludes".to_string(),
+            }
+        );
+        let log = explain(&store, &cfg, &path(&root.join("debug.log"))).unwrap();
+        assert_eq!(log.verdict, Verdict::Indexed);
+
+        let missing = explain(&store, &cfg, &path(&root.join("gone.txt"))).unwrap();
+        assert_eq!(missing.verdict, Verdict::Missing);
+        let outside = explain(&store, &cfg, &path(&dir.join("catalog.bin"))).unwrap();
+        assert_eq!(outside.verdict, Verdict::NoRoot);
+        assert_eq!(outside.root, None);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,457 @@
+use crate::analyze::human_size;
+use crate::config::Config;
+use crate::query::{self, Flags};
+use crate::search::{self, SearchEntry, SortOrder, StatusFilter};
+use crate::store::Store;
+use anyhow::Result;
+use chrono::{Local, TimeZone};
+use crossterm::event::{
+    self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyModifiers,
+    MouseButton, MouseEventKind,
+};
+use crossterm::execute;
+use crossterm::terminal::{
+    EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode,
+};
+use ratatui::Terminal;
+use ratatui::backend::CrosstermBackend;
+use ratatui::layout::{Constraint, Direction, Layout, Rect};
+use ratatui::prelude::Frame;
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap};
+use std::io::{self, IsTerminal};
+use std::time::Duration;
+
+/// Results shown per query; typing more narrows further.
+const RESULT_LIMIT: usize = 500;
+
+/// Opens the finder and returns the chosen paths, or `None` when cancelled.
+/// The UI is drawn on stderr so stdout only carries the selection, which keeps
+/// `cd "$(catalog find)"` working.
+pub fn run_find_tui(
+    store: &Store,
+    cfg: &Config,
+    initial_query: &str,
+    flags: &Flags,
+) -> Result<Option<Vec<String>>> {
+    if !io::stderr().is_terminal() {
+        anyhow::bail!("`catalog find` needs an interactive terminal; use `catalog search` instead");
+    }
+
+    let mut app = FindApp::new(store, cfg, flags, initial_query);
+
+    enable_raw_mode()?;
+    let mut stderr = io::stderr();
+    execute!(stderr, EnterAlternateScreen, EnableMouseCapture)?;
+    let backend = CrosstermBackend::new(stderr);
+    let mut terminal = Terminal::new(backend)?;
+
+    let result = run_app(&mut terminal, &mut app);
+
+    disable_raw_mode()?;
+    execute!(
+        terminal.backend_mut(),
+        LeaveAlternateScreen,
+        DisableMouseCapture
+    )?;
+    terminal.show_cursor()?;
+
+    result
+}
+
+enum Outcome {
+    Continue,
+    Accept,
+    Cancel,
+}
+
+struct FindApp<'a> {
+    store: &'a Store,
+    cfg: &'a Config,
+    flags: &'a Flags,
+    query: String,
+    results: Vec<SearchEntry>,
+    error: Option<String>,
+    marked: Vec<String>,
+    state: ListState,
+    list_area: Rect,
+}
+
+impl<'a> FindApp<'a> {
+    fn new(store: &'a Store, cfg: &'a Config, flags: &'a Flags, query: &str) -> Self {
+        let mut app = Self {
+            store,
+            cfg,
+            flags,
+            query: query.to_string(),
+            results: Vec::new(),
+            error: None,
+            marked: Vec::new(),
+            state: ListState::default(),
+            list_area: Rect::default(),
+        };
+        app.refresh();
+        app
+    }
+
+    /// Re-runs the query. While the text does not parse (e.g. an open
+    /// parenthesis mid-typing) the previous results stay on screen.
+    fn refresh(&mut self) {
+        let results = query::build(&self.query, self.flags).and_then(|expr| {
+            search::search_top(
+                self.store,
+                self.cfg,
+                &expr,
+                SortOrder::Score,
+                StatusFilter::Active,
+                Some(RESULT_LIMIT),
+            )
+        });
+        match results {
+            Ok(results) => {
+                self.results = results;
+                self.error = None;
+                self.state.select(if self.results.is_empty() {
+                    None
+                } else {
+                    Some(0)
+                });
+            }
+            Err(err) => self.error = Some(format!("{:#}", err)),
+        }
+    }
+
+    fn push_char(&mut self, c: char) {
+        self.query.push(c);
+        self.refresh();
+    }
+
+    fn pop_char(&mut self) {
+        if self.query.pop().is_some() {
+            self.refresh();
+        }
+    }
+
+    fn pop_word(&mut self) {
+        let trimmed = self.query.trim_end();
+        let cut = trimmed.rfind(char::is_whitespace).map_or(0, |i| i + 1);
+        self.query.truncate(cut);
+        self.refresh();
+    }
+
+    fn clear(&mut self) {
+        self.query.clear();
+        self.refresh();
+    }
+
+    fn selected(&self) -> Option<&SearchEntry> {
+        self.state.selected().and_then(|idx| self.results.get(idx))
+    }
+
+    fn move_selection(&mut self, delta: isize) {
+        if self.results.is_empty() {
+            return;
+        }
+        let max = (self.results.len() - 1) as isize;
+        let current = self.state.selected().unwrap_or(0) as isize;
+        self.state
+            .select(Some((current + delta).clamp(0, max) as usize));
+    }
+
+    fn toggle_mark(&mut self) {
+        let Some(path) = self.selected().map(|e| e.path.clone()) else {
+            return;
+        };
+        match self.marked.iter().position(|p| *p == path) {
+            Some(pos) => {
+                self.marked.remove(pos);
+            }
+            None => self.marked.push(path),
+        }
+        self.move_selection(1);
+    }
+
+    /// Marked paths in the order they were marked, or the highlighted one.
+    fn chosen(&self) -> Vec<String> {
+        if !self.marked.is_empty() {
+            return self.marked.clone();
+        }
+        self.selected()
+            .map(|e| vec![e.path.clone()])
+            .unwrap_or_default()
+    }
+
+    fn select_at_mouse(&mut self, row: u16) {
+        if row < self.list_area.y || row >= self.list_area.y + self.list_area.height {
+            return;
+        }
+        let idx = (row - self.list_area.y) as usize + self.state.offset();
+        if idx < self.results.len() {
+            self.state.select(Some(idx));
+        }
+    }
+}
+
+fn run_app(
+    terminal: &mut Terminal<CrosstermBackend<io::Stderr>>,
+    app: &mut FindApp,
+) -> Result<Option<Vec<String>>> {
+    loop {
+        terminal.draw(|f| draw_ui(f, app))?;
+
+        if event::poll(Duration::from_millis(200))? {
+            match event::read()? {
+                Event::Key(key) => match handle_key(app, key) {
+                    Outcome::Continue => {}
+                    Outcome::Accept => {
+                        let chosen = app.chosen();
+                        if !chosen.is_empty() {
+                            return Ok(Some(chosen));
+                        }
+                    }
+                    Outcome::Cancel => return Ok(None),
+                },
+                Event::Mouse(mouse) => match mouse.kind {
+                    MouseEventKind::Down(MouseButton::Left)
+                    | MouseEventKind::Drag(MouseButton::Left) => app.select_at_mouse(mouse.row),
+                    MouseEventKind::ScrollUp => app.move_selection(-1),
+                    MouseEventKind::ScrollDown => app.move_selection(1),
+                    _ => {}
+                },
+                Event::Paste(text) => {
+                    app.query.push_str(&text.replace(['\n', '\r'], " "));
+                    app.refresh();
+                }
+                Event::Resize(_, _) | Event::FocusGained | Event::FocusLost => {}
+            }
+        }
+    }
+}
+
+fn handle_key(app: &mut FindApp, key: KeyEvent) -> Outcome {
+    let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+    match key.code {
+        KeyCode::Esc => return Outcome::Cancel,
+        KeyCode::Char('c') | KeyCode::Char('g') if ctrl => return Outcome::Cancel,
+        KeyCode::Enter => return Outcome::Accept,
+        KeyCode::Up => app.move_selection(-1),
+        KeyCode::Char('p') | KeyCode::Char('k') if ctrl => app.move_selection(-1),
+        KeyCode::Down => app.move_selection(1),
+        KeyCode::Char('n') | KeyCode::Char('j') if ctrl => app.move_selection(1),
+        KeyCode::PageUp => app.move_selection(-10),
+        KeyCode::PageDown => app.move_selection(10),
+        KeyCode::Tab => app.toggle_mark(),
+        KeyCode::Backspace => app.pop_char(),
+        KeyCode::Char('w') if ctrl => app.pop_word(),
+        KeyCode::Char('u') if ctrl => app.clear(),
+        KeyCode::Char(c) if !ctrl => app.push_char(c),
+        _ => {}
+    }
+    Outcome::Continue
+}
+
+fn draw_ui(frame: &mut Frame, app: &mut FindApp) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(2),
+            Constraint::Min(1),
+            Constraint::Length(1),
+        ])
+        .split(frame.size());
+
+    let status = match &app.error {
+        Some(err) => Span::styled(err.clone(), Style::default().fg(Color::Red)),
+        None => {
+            let count = if app.results.len() >= RESULT_LIMIT {
+                format!("{}+ matches", RESULT_LIMIT)
+            } else {
+                format!("{} matches", app.results.len())
+            };
+            let marked = if app.marked.is_empty() {
+                String::new()
+            } else {
+                format!("  {} marked", app.marked.len())
+            };
+            Span::raw(format!("{}{}", count, marked))
+        }
+    };
+    let header = Paragraph::new(vec![
+        Line::from(vec![
+            Span::styled("> ", Style::default().add_modifier(Modifier::BOLD)),
+            Span::raw(app.query.clone()),
+        ]),
+        Line::from(status),
+    ]);
+    frame.render_widget(header, chunks[0]);
+    let cursor_x = chunks[0].x + 2 + app.query.chars().count() as u16;
+    frame.set_cursor(
+        cursor_x.min(chunks[0].right().saturating_sub(1)),
+        chunks[0].y,
+    );
+
+    let body = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+        .split(chunks[1]);
+
+    let items: Vec<ListItem> = if app.results.is_empty() {
+        vec![ListItem::new("(no matches)")]
+    } else {
+        app.results
+            .iter()
+            .map(|entry| {
+                let mark = if app.marked.contains(&entry.path) {
+                    "* "
+                } else {
+                    "  "
+                };
+                let suffix = if entry.is_dir { "/" } else { "" };
+                ListItem::new(format!("{}{}{}", mark, entry.path, suffix))
+            })
+            .collect()
+    };
+    let list = List::new(items).highlight_style(Style::default().bg(Color::Blue).fg(Color::White));
+    app.list_area = body[0];
+    frame.render_stateful_widget(list, body[0], &mut app.state);
+
+    let preview = Paragraph::new(app.selected().map(preview_lines).unwrap_or_default())
+        .block(Block::default().borders(Borders::LEFT))
+        .wrap(Wrap { trim: false });
+    frame.render_widget(preview, body[1]);
+
+    let footer = Paragraph::new(Line::from(vec![
+        Span::styled("Enter", Style::default().add_modifier(Modifier::BOLD)),
+        Span::raw(": print  "),
+        Span::styled("Tab", Style::default().add_modifier(Modifier::BOLD)),
+        Span::raw(": mark  "),
+        Span::styled("Up/Down", Style::default().add_modifier(Modifier::BOLD)),
+        Span::raw(": move  "),
+        Span::styled("Ctrl+U", Style::default().add_modifier(Modifier::BOLD)),
+        Span::raw(": clear  "),
+        Span::styled("Esc", Style::default().add_modifier(Modifier::BOLD)),
+        Span::raw(": cancel"),
+    ]));
+    frame.render_widget(footer, chunks[2]);
+}
+
+/// The same metadata `--long` prints, one field per line.
+fn preview_lines(entry: &SearchEntry) -> Vec<Line<'static>> {
+    let mtime = Local
+        .timestamp_opt(entry.mtime, 0)
+        .single()
+        .map(|d| d.format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_else(|| "-".to_string());
+    let kind = if entry.is_dir {
+        "dir"
+    } else if entry.is_symlink {
+        "symlink"
+    } else {
+        "file"
+    };
+    let tags = if entry.tags.is_empty() {
+        "-".to_string()
+    } else {
+        entry.tags.join(",")
+    };
+    let field = |label: &str, value: String| {
+        Line::from(vec![
+            Span::styled(
+                format!("{}: ", label),
+                Style::default().add_modifier(Modifier::BOLD),
+            ),
+            Span::raw(value),
+        ])
+    };
+    vec![
+        field("Path", entry.path.clone()),
+        field(
+            "Size",
+            format!(
+                "{} ({} bytes)",
+                human_size(entry.size.max(0) as u64),
+                entry.size
+            ),
+        ),
+        field("Modified", mtime),
+        field("Kind", kind.to_string()),
+        field("Ext", entry.ext.clone().unwrap_or_else(|| "-".to_string())),
+        field("Status", entry.status.clone()),
+        field("Tags", tags),
+        field("Root", entry.root.clone()),
+        field("Id", entry.id.to_string()),
+        field(
+            "Score",
+            entry
+                .score
+                .map_or_else(|| "-".to_string(), |s| s.to_string()),
+        ),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::{FileEntry, StoreData};
+    use std::path::PathBuf;
+
+    fn file(id: i64, abs_path: &str) -> FileEntry {
+        FileEntry {
+            id,
+            root_id: 1,
+            rel_path: abs_path.trim_start_matches('/').to_string(),
+            abs_path: abs_path.to_string(),
+            is_dir: false,
+            is_symlink: false,
+            size: 1,
+            mtime: id,
+            ext: None,
+            status: "active".to_string(),
+            last_seen_run: 1,
+            dev: None,
+            ino: None,
+            prev_path: None,
+            deleted_at: None,
+        }
+    }
+
+    #[test]
+    fn typing_narrows_and_marks_choose_paths() {
+        let mut data = StoreData::new();
+        data.files.push(file(1, "/work/report.pdf"));
+        data.files.push(file(2, "/work/notes.txt"));
+        data.files.push(file(3, "/home/report-old.pdf"));
+        let store = Store::from_data(PathBuf::from("/nonexistent/catalog_find.bin"), data);
+        let cfg = Config {
+            excludes: Vec::new(),
+            ..Config::default()
+        };
+        let flags = Flags::default();
+
+        let mut app = FindApp::new(&store, &cfg, &flags, "");
+        assert_eq!(app.results.len(), 3);
+        for c in "report".chars() {
+            app.push_char(c);
+        }
+        assert_eq!(app.chosen(), vec!["/work/report.pdf".to_string()]);
+        assert_eq!(app.results.len(), 2);
+
+        app.push_char('(');
+        assert!(app.error.is_some());
+        assert_eq!(app.results.len(), 2);
+        app.pop_char();
+
+        app.toggle_mark();
+        app.toggle_mark();
+        assert_eq!(
+            app.chosen(),
+            vec![
+                "/work/report.pdf".to_string(),
+                "/home/report-old.pdf".to_string()
+            ]
+        );
+        app.clear();
+        assert_eq!(app.results.len(), 3);
+    }
+}
@@ -0,0 +1,544 @@
+use crate::snapshot::{self, Snapshot};
+use crate::store::{Store, StoreData};
+use crate::util::path_to_string;
+use crate::wal;
+use anyhow::{Context, Result};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug)]
+pub struct Issue {
+    pub message: String,
+    /// Whether `--repair` fixed it (or would, without `--repair`).
+    pub fixable: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct Report {
+    pub issues: Vec<Issue>,
+    pub files: usize,
+    /// Whether the snapshot carries a checksum that was verified.
+    pub checksum: bool,
+    /// Copy of the store taken before repairing it.
+    pub backup: Option<PathBuf>,
+}
+
+impl Report {
+    fn problem(&mut self, fixable: bool, message: String) {
+        self.issues.push(Issue { message, fixable });
+    }
+
+    /// Problems `--repair` cannot fix.
+    pub fn unresolved(&self) -> usize {
+        self.issues.iter().filter(|issue| !issue.fixable).count()
+    }
+
+    pub fn summary(&self, repair: bool) -> String {
+        let mut out = String::new();
+        for issue in &self.issues {
+            let label = match (issue.fixable, repair) {
+                (true, true) => "fixed",
+                (true, false) => "fixable",
+                (false, _) => "problem",
+            };
+            out.push_str(&format!("{}: {}\n", label, issue.message));
+        }
+        if !self.checksum {
+            out.push_str("note: this store layout has no checksum; the next rewrite adds one\n");
+        }
+        let fixable = self.issues.len() - self.unresolved();
+        if self.issues.is_empty() {
+            out.push_str(&format!("Store is consistent ({} entries).", self.files));
+        } else if repair {
+            out.push_str(&format!(
+                "Found {} problem(s), fixed {}",
+                self.issues.len(),
+                fixable
+            ));
+            if let Some(backup) = &self.backup {
+                out.push_str(&format!("; backup at {}", backup.display()));
+            }
+            out.push('.');
+        } else if fixable > 0 {
+            out.push_str(&format!(
+                "Found {} problem(s); run `catalog fsck --repair` to fix {} of them.",
+                self.issues.len(),
+                fixable
+            ));
+        } else {
+            out.push_str(&format!("Found {} problem(s).", self.issues.len()));
+        }
+        out
+    }
+}
+
+/// Where `--repair` keeps the store as it was before repairing it.
+pub fn backup_path(store_path: &Path) -> PathBuf {
+    store_path.with_extension("fsck.bak")
+}
+
+/// `catalog fsck`: checks the snapshot checksum, the log, and the store's
+/// invariants. With `repair`, fixes what can be fixed (keeping a backup) and
+/// rewrites the store.
+pub fn run(path: &Path, repair: bool) -> Result<Report> {
+    if !path.exists() {
+        anyhow::bail!("store not found: {}; run `catalog init`", path.display());
+    }
+    let mut report = Report::default();
+    let mut rewrite = check_file(path, &mut report)?;
+
+    let mut store = Store::inspect(path)
+        .with_context(|| format!("store cannot be decoded: {}", path.display()))?;
+    let log = wal::wal_path(path);
+    if let Ok(meta) = fs::metadata(&log) {
+        let intact = store.log_len().unwrap_or(0);
+        if meta.len() > intact {
+            report.problem(
+                true,
+                format!(
+                    "{}: {} byte(s) after the last intact record are ignored",
+                    log.display(),
+                    meta.len() - intact
+                ),
+            );
+            rewrite = true;
+        }
+    }
+
+    let before = report.issues.len();
+    check_data(&mut store.data, &mut report);
+    rewrite |= report.issues[before..].iter().any(|issue| issue.fixable);
+    report.files = store.data.files.len();
+
+    if repair && rewrite {
+        report.backup = Some(backup(path)?);
+        store.compact()?;
+    }
+    Ok(report)
+}
+
+/// Checks the snapshot file itself. Returns whether it has to be rewritten.
+fn check_file(path: &Path, report: &mut Report) -> Result<bool> {
+    let mut file = fs::File::open(path)
+        .with_context(|| format!("failed to read store: {}", path.display()))?;
+    let mut magic = [0u8; 8];
+    if file.read_exact(&mut magic).is_err() || !snapshot::is_snapshot(&magic) {
+        // Bincode snapshot from before version 7: no checksum to check.
+        return Ok(false);
+    }
+    let (snapshot, _) = Snapshot::open(path)
+        .with_context(|| format!("store cannot be decoded: {}", path.display()))?;
+    report.checksum = snapshot.has_checksum();
+    if let Err(err) = snapshot.verify() {
+        let message = format!("{:#}", err);
+        let message = message.split(';').next().unwrap_or(&message).to_string();
+        report.problem(
+            true,
+            format!("{}; entries are rewritten as they decode", message),
+        );
+        return Ok(true);
+    }
+    Ok(false)
+}
+
+/// Checks the decoded store and fixes what it can in place.
+fn check_data(data: &mut StoreData, report: &mut Report) {
+    check_counters(data, report);
+    check_ids(data, report);
+    check_roots(data, report);
+    check_tags(data, report);
+    check_paths(data, report);
+    check_dir_sizes(data, report);
+}
+
+fn check_counters(data: &mut StoreData, report: &mut Report) {
+    let counters = [
+        (
+            "root",
+            data.next_root_id,
+            data.roots.iter().map(|r| r.id).max(),
+        ),
+        (
+            "file",
+            data.next_file_id,
+            data.files.iter().map(|f| f.id).max(),
+        ),
+        (
+            "tag",
+            data.next_tag_id,
+            data.tags.iter().map(|t| t.id).max(),
+        ),
+    ];
+    for (name, next, max) in counters {
+        if let Some(max) = max.filter(|&max| next <= max) {
+            report.problem(
+                true,
+                format!(
+                    "next {} id is {} but id {} is already used",
+                    name, next, max
+                ),
+            );
+        }
+    }
+    data.ensure_counters();
+
+    let last_run = data
+        .files
+        .iter()
+        .map(|f| f.last_seen_run)
+        .chain(data.changes.iter().map(|c| c.run_id))
+        .max()
+        .unwrap_or(0);
+    if last_run > data.last_run_id {
+        report.problem(
+            true,
+            format!(
+                "last run id is {} but run {} is already recorded",
+                data.last_run_id, last_run
+            ),
+        );
+        data.last_run_id = last_run;
+    }
+}
+
+fn check_ids(data: &mut StoreData, report: &mut Report) {
+    let mut seen = HashSet::new();
+    let mut reassigned = 0;
+    for idx in 0..data.files.len() {
+        if !seen.insert(data.files[idx].id) {
+            let id = data.next_file_id();
+            report.problem(
+                true,
+                format!(
+                    "file id {} is used twice; {} gets id {}",
+                    data.files[idx].id, data.files[idx].abs_path, id
+                ),
+            );
+            data.files[idx].id = id;
+            reassigned += 1;
+        }
+    }
+    if reassigned > 0 {
+        data.ensure_sorted();
+    }
+
+    let mut seen = HashSet::new();
+    for root in &data.roots {
+        if !seen.insert(root.id) {
+            report.problem(
+                false,
+                format!("root id {} is used twice ({})", root.id, root.path),
+            );
+        }
+    }
+    let mut ids = HashSet::new();
+    let mut names = HashSet::new();
+    for tag in &data.tags {
+        if !ids.insert(tag.id) {
+            report.problem(
+                false,
+                format!("tag id {} is used twice ({})", tag.id, tag.name),
+            );
+        }
+        if !names.insert(tag.name.as_str()) {
+            report.problem(false, format!("tag name {} is used twice", tag.name));
+        }
+    }
+}
+
+fn check_roots(data: &mut StoreData, report: &mut Report) {
+    let roots: HashSet<i64> = data.roots.iter().map(|r| r.id).collect();
+    let mut orphans: HashMap<i64, usize> = HashMap::new();
+    for file in &data.files {
+        if !roots.contains(&file.root_id) {
+            *orphans.entry(file.root_id).or_default() += 1;
+        }
+    }
+    if orphans.is_empty() {
+        return;
+    }
+    let mut orphans: Vec<_> = orphans.into_iter().collect();
+    orphans.sort();
+    for (root_id, count) in orphans {
+        report.problem(
+            true,
+            format!(
+                "{} entries belong to missing root {}; dropped",
+                count, root_id
+            ),
+        );
+    }
+    data.files.retain(|f| roots.contains(&f.root_id));
+}
+
+fn check_tags(data: &mut StoreData, report: &mut Report) {
+    let tags: HashSet<i64> = data.tags.iter().map(|t| t.id).collect();
+    let mut seen = HashSet::new();
+    let mut problems = Vec::new();
+    let files = &data.files;
+    data.file_tags.retain(|link| {
+        let problem = if files.binary_search_by_key(&link.file_id, |f| f.id).is_err() {
+            "a missing file"
+        } else if !tags.contains(&link.tag_id) {
+            "a missing tag"
+        } else if !seen.insert((link.file_id, link.tag_id)) {
+            "a duplicate link"
+        } else {
+            return true;
+        };
+        problems.push(format!(
+            "tag link (file {}, tag {}) points at {}; dropped",
+            link.file_id, link.tag_id, problem
+        ));
+        false
+    });
+    for message in problems {
+        report.problem(true, message);
+    }
+}
+
+fn check_paths(data: &mut StoreData, report: &mut Report) {
+    let roots: HashMap<i64, _> = data.roots.iter().map(|r| (r.id, r)).collect();
+    for file in &mut data.files {
+        let Some(root) = roots.get(&file.root_id) else {
+            continue;
+        };
+        if file.status != "active" {
+            continue;
+        }
+        if root.volume.is_some() {
+            // The volume may have been mounted elsewhere since; only the
+            // relative part is known for sure.
+            if !file.abs_path.ends_with(&file.rel_path) {
+                report.problem(
+                    false,
+                    format!(
+                        "{} does not end with its relative path {}",
+                        file.abs_path, file.rel_path
+                    ),
+                );
+            }
+            continue;
+        }
+        let expected = if file.rel_path.is_empty() {
+            root.path.clone()
+        } else {
+            path_to_string(&Path::new(&root.path).join(&file.rel_path))
+        };
+        if file.abs_path != expected {
+            report.problem(
+                true,
+                format!(
+                    "{} does not match its root and relative path; now {}",
+                    file.abs_path, expected
+                ),
+            );
+            file.abs_path = expected;
+        }
+    }
+}
+
+fn check_dir_sizes(data: &mut StoreData, report: &mut Report) {
+    if data.dir_sizes_run_id > data.last_run_id {
+        report.problem(
+            true,
+            format!(
+                "directory sizes are from run {}, after the last run {}; cleared",
+                data.dir_sizes_run_id, data.last_run_id
+            ),
+        );
+        data.dir_sizes.clear();
+        data.dir_sizes_run_id = 0;
+    }
+}
+
+/// Copies the store (and its log) to `backup_path`, replacing an older copy.
+fn backup(path: &Path) -> Result<PathBuf> {
+    let backup = backup_path(path);
+    let log = wal::wal_path(path);
+    let log_backup = wal::wal_path(&backup);
+    if log.exists() {
+        fs::copy(&log, &log_backup)
+            .with_context(|| format!("failed to back up store: {}", log_backup.display()))?;
+    } else if log_backup.exists() {
+        fs::remove_file(&log_backup)?;
+    }
+    fs::copy(path, &backup)
+        .with_context(|| format!("failed to back up store: {}", backup.display()))?;
+    Ok(backup)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::{FileEntry, FileTagEntry, RootEntry, TagEntry};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn temp_dir(prefix: &str) -> PathBuf {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!(
+            "catalog_test_{}_{}_{}",
+            prefix,
+            std::process::id(),
+            nanos
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn file(id: i64, root_id: i64, rel_path: &str, abs_path: &str) -> FileEntry {
+        FileEntry {
+            id,
+            root_id,
+            rel_path: rel_path.to_string(),
+            abs_path: abs_path.to_string(),
+            is_dir: false,
+            is_symlink: false,
+            size: 10,
+            mtime: 100,
+            ext: None,
+            status: "active".to_string(),
+            last_seen_run: 2,
+            dev: None,
+            ino: None,
+            prev_path: None,
+            deleted_at: None,
+        }
+    }
+
+    fn sample() -> StoreData {
+        let mut data = StoreData::new();
+        data.last_run_id = 2;
+        data.next_root_id = 2;
+        data.next_file_id = 3;
+        data.next_tag_id = 2;
+        data.roots.push(RootEntry {
+            id: 1,
+            path: "/data".to_string(),
+            added_at: "now".to_string(),
+            preset_name: None,
+            last_indexed_at: None,
+            one_filesystem: false,
+            volume: None,
+            scan: None,
+        });
+        data.files.push(file(1, 1, "a.txt", "/data/a.txt"));
+        data.files.push(file(2, 1, "b/c.txt", "/data/b/c.txt"));
+        data.tags.push(TagEntry {
+            id: 1,
+            name: "work".to_string(),
+        });
+        data.file_tags.push(FileTagEntry {
+            file_id: 2,
+            tag_id: 1,
+        });
+        data
+    }
+
+    fn write(data: StoreData) -> PathBuf {
+        let path = temp_dir("fsck").join("catalog.bin");
+        Store::from_data(path.clone(), data).compact().unwrap();
+        path
+    }
+
+    #[test]
+    fn clean_store_passes() {
+        let path = write(sample());
+        let report = run(&path, false).unwrap();
+        assert!(report.issues.is_empty(), "{:?}", report.issues);
+        assert!(report.checksum);
+        assert_eq!(report.files, 2);
+    }
+
+    #[test]
+    fn repair_fixes_broken_invariants() {
+        let mut data = sample();
+        data.next_file_id = 2;
+        data.files.push(file(2, 1, "d.txt", "/data/d.txt"));
+        data.files.push(file(9, 7, "x.txt", "/elsewhere/x.txt"));
+        data.files[0].abs_path = "/data/old/a.txt".to_string();
+        data.file_tags.push(FileTagEntry {
+            file_id: 9,
+            tag_id: 1,
+        });
+        data.file_tags.push(FileTagEntry {
+            file_id: 1,
+            tag_id: 5,
+        });
+        data.file_tags.push(FileTagEntry {
+            file_id: 2,
+            tag_id: 1,
+        });
+        data.dir_sizes_run_id = 8;
+        data.tags.push(TagEntry {
+            id: 2,
+            name: "work".to_string(),
+        });
+        let path = write(data);
+
+        let report = run(&path, false).unwrap();
+        assert_eq!(report.unresolved(), 1, "{}", report.summary(false));
+        assert!(report.issues.len() >= 7, "{}", report.summary(false));
+        assert!(report.backup.is_none());
+        assert_eq!(run(&path, false).unwrap().issues.len(), report.issues.len());
+
+        let report = run(&path, true).unwrap();
+        assert_eq!(report.backup, Some(backup_path(&path)));
+        assert!(backup_path(&path).exists());
+
+        let store = Store::open(&path).unwrap();
+        let data = &store.data;
+        let ids: Vec<i64> = data.files.iter().map(|f| f.id).collect();
+        assert_eq!(ids, vec![1, 2, 10]);
+        assert_eq!(data.files[0].abs_path, "/data/a.txt");
+        assert_eq!(data.next_file_id, 11);
+        assert_eq!(data.file_tags.len(), 1);
+        assert_eq!(data.dir_sizes_run_id, 0);
+
+        let report = run(&path, false).unwrap();
+        assert_eq!(report.issues.len(), 1, "{}", report.summary(false));
+        assert!(report.issues[0].message.contains("tag name work"));
+    }
+
+    #[test]
+    fn detects_and_repairs_a_damaged_file() {
+        let path = write(sample());
+        let mut raw = fs::read(&path).unwrap();
+        // Flip a byte of the first record's size; the records area's offset
+        // is the third header pair.
+        let records = u64::from_le_bytes(raw[48..56].try_into().unwrap()) as usize;
+        raw[records + 16] ^= 0xff;
+        fs::write(&path, &raw).unwrap();
+
+        assert!(Store::open(&path).is_err());
+        let report = run(&path, false).unwrap();
+        assert!(report.issues[0].message.contains("checksum mismatch"));
+
+        run(&path, true).unwrap();
+        assert!(run(&path, false).unwrap().issues.is_empty());
+        assert!(Store::open(&path).is_ok());
+    }
+
+    #[test]
+    fn torn_log_tail_is_reported_and_dropped() {
+        let path = write(sample());
+        let mut store = Store::open(&path).unwrap();
+        store.data.files[0].size = 99;
+        store.save().unwrap();
+        let log = wal::wal_path(&path);
+        let mut raw = fs::read(&log).unwrap();
+        raw.extend_from_slice(&[1, 2, 3]);
+        fs::write(&log, &raw).unwrap();
+
+        let report = run(&path, true).unwrap();
+        assert_eq!(report.issues.len(), 1);
+        assert!(report.issues[0].message.contains("3 byte(s)"));
+        assert!(!log.exists());
+        assert_eq!(Store::open(&path).unwrap().data.files[0].size, 99);
+    }
+}
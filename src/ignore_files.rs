@@ -0,0 +1,281 @@
+use crate::config::IgnoreFiles;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::{Match, WalkBuilder};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Per-directory ignore file honoured when `catalog_ignore` is on.
+pub const CATALOG_IGNORE: &str = ".catalogignore";
+
+/// An ignore-file line that excludes a path.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct IgnoreHit {
+    pub file: PathBuf,
+    pub pattern: String,
+}
+
+/// Makes `builder` honour the enabled ignore files. Those in the parent
+/// directories of each start path are read too, so a walk that starts below
+/// the root still sees the root's own files. `.gitignore`, `info/exclude`
+/// and the global excludes only apply inside a git repository, as in git.
+pub fn configure(builder: &mut WalkBuilder, files: IgnoreFiles) {
+    builder
+        .standard_filters(false)
+        .parents(files.any())
+        .ignore(files.vcs)
+        .git_ignore(files.vcs)
+        .git_exclude(files.vcs)
+        .git_global(files.git_global);
+    if files.catalog {
+        builder.add_custom_ignore_filename(CATALOG_IGNORE);
+    }
+}
+
+/// The ignore-file line that excludes `path`, for checks of single paths
+/// outside a walk. Files are consulted in the walker's order: every
+/// `.catalogignore`, then `.ignore`, then `.gitignore` (closest first), then
+/// the repository's `info/exclude` and git's global excludes. A negated
+/// (`!`) line that matches first keeps the path.
+pub fn excluded_by(path: &Path, is_dir: bool, files: IgnoreFiles) -> Option<IgnoreHit> {
+    IgnoreCache::default().excluded_by(path, is_dir, files)
+}
+
+/// Ignore files parsed by `excluded_by`, kept for checking many paths under
+/// the same directories. Shared between walker threads.
+#[derive(Debug, Default)]
+pub struct IgnoreCache {
+    /// By ignore file path; `None` when there is no such file.
+    parsed: Mutex<HashMap<PathBuf, Option<Arc<Gitignore>>>>,
+    /// Git's global excludes, by the repository they are matched under.
+    global: Mutex<HashMap<PathBuf, Arc<Gitignore>>>,
+    /// Whether a directory holds `.git`.
+    repos: Mutex<HashMap<PathBuf, bool>>,
+}
+
+impl IgnoreCache {
+    /// As the free `excluded_by`, reading each ignore file once.
+    pub fn excluded_by(&self, path: &Path, is_dir: bool, files: IgnoreFiles) -> Option<IgnoreHit> {
+        if !files.any() {
+            return None;
+        }
+        let repo = path.ancestors().skip(1).find(|dir| self.is_repo(dir));
+        let in_repo = |dir: &Path| repo.is_some_and(|repo| dir.starts_with(repo));
+
+        let mut names = Vec::new();
+        if files.catalog {
+            names.push(CATALOG_IGNORE);
+        }
+        if files.vcs {
+            names.extend([".ignore", ".gitignore"]);
+        }
+        for name in names {
+            for dir in path.ancestors().skip(1) {
+                if name == ".gitignore" && !in_repo(dir) {
+                    break;
+                }
+                match self.check(dir, Some(&dir.join(name)), path, is_dir) {
+                    Match::None => continue,
+                    Match::Ignore(hit) => return Some(hit),
+                    Match::Whitelist(_) => return None,
+                }
+            }
+        }
+
+        let repo = repo?;
+        if files.vcs {
+            match self.check(repo, Some(&repo.join(".git/info/exclude")), path, is_dir) {
+                Match::None => {}
+                Match::Ignore(hit) => return Some(hit),
+                Match::Whitelist(_) => return None,
+            }
+        }
+        if files.git_global
+            && let Match::Ignore(hit) = self.check(repo, None, path, is_dir)
+        {
+            return Some(hit);
+        }
+        None
+    }
+
+    /// Drops what was read for `path` (an ignore file, or a `.git` directory
+    /// or anything in it), so the next check reads it again.
+    pub fn forget(&self, path: &Path) {
+        self.parsed.lock().unwrap().remove(path);
+        if let Some(git) = path
+            .ancestors()
+            .find(|dir| dir.file_name().is_some_and(|name| name == ".git"))
+            && let Some(repo) = git.parent()
+        {
+            self.repos.lock().unwrap().remove(repo);
+            self.parsed
+                .lock()
+                .unwrap()
+                .remove(&repo.join(".git/info/exclude"));
+        }
+    }
+
+    fn is_repo(&self, dir: &Path) -> bool {
+        if let Some(&found) = self.repos.lock().unwrap().get(dir) {
+            return found;
+        }
+        let found = dir.join(".git").exists();
+        self.repos.lock().unwrap().insert(dir.to_path_buf(), found);
+        found
+    }
+
+    /// Matches `path` against one ignore file rooted at `dir`, or against
+    /// git's global excludes when `file` is `None`.
+    fn check(
+        &self,
+        dir: &Path,
+        file: Option<&Path>,
+        path: &Path,
+        is_dir: bool,
+    ) -> Match<IgnoreHit> {
+        let gitignore = match file {
+            Some(file) => {
+                let cached = self.parsed.lock().unwrap().get(file).cloned();
+                let parsed = cached.unwrap_or_else(|| {
+                    let parsed = parse(dir, file).map(Arc::new);
+                    self.parsed
+                        .lock()
+                        .unwrap()
+                        .insert(file.to_path_buf(), parsed.clone());
+                    parsed
+                });
+                match parsed {
+                    Some(gitignore) => gitignore,
+                    None => return Match::None,
+                }
+            }
+            None => self
+                .global
+                .lock()
+                .unwrap()
+                .entry(dir.to_path_buf())
+                .or_insert_with(|| Arc::new(GitignoreBuilder::new(dir).build_global().0))
+                .clone(),
+        };
+        let hit = |glob: &ignore::gitignore::Glob| IgnoreHit {
+            file: glob.from().map(Path::to_path_buf).unwrap_or_default(),
+            pattern: glob.original().to_string(),
+        };
+        match gitignore.matched_path_or_any_parents(path, is_dir) {
+            Match::None => Match::None,
+            Match::Ignore(glob) => Match::Ignore(hit(glob)),
+            Match::Whitelist(glob) => Match::Whitelist(hit(glob)),
+        }
+    }
+}
+
+/// Reads the ignore file `file` rooted at `dir`; `None` when there is none.
+fn parse(dir: &Path, file: &Path) -> Option<Gitignore> {
+    if !file.is_file() {
+        return None;
+    }
+    let mut builder = GitignoreBuilder::new(dir);
+    if let Some(err) = builder.add(file) {
+        tracing::debug!("ignore file error: {} ({})", file.display(), err);
+    }
+    builder.build().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn temp_dir(prefix: &str) -> PathBuf {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!(
+            "catalog_test_{}_{}_{}",
+            prefix,
+            std::process::id(),
+            nanos
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn excluded_by_follows_ignore_file_precedence() {
+        let root = temp_dir("ignore_files");
+        fs::create_dir_all(root.join("repo/.git/info")).unwrap();
+        fs::create_dir_all(root.join("repo/sub")).unwrap();
+        fs::write(root.join("repo/.gitignore"), "*.log\nbuild/\n").unwrap();
+        fs::write(root.join("repo/sub/.gitignore"), "!keep.log\n").unwrap();
+        fs::write(root.join("repo/.git/info/exclude"), "scratch.txt\n").unwrap();
+        fs::write(root.join("repo/sub/.catalogignore"), "keep.log\n").unwrap();
+        fs::write(root.join(".gitignore"), "*.md\n").unwrap();
+
+        let vcs = IgnoreFiles {
+            vcs: true,
+            ..Default::default()
+        };
+        let hit = excluded_by(&root.join("repo/build/out/app"), false, vcs).unwrap();
+        assert_eq!(hit.file, root.join("repo/.gitignore"));
+        assert_eq!(hit.pattern, "build/");
+        assert!(excluded_by(&root.join("repo/sub/debug.log"), false, vcs).is_some());
+        assert!(excluded_by(&root.join("repo/sub/keep.log"), false, vcs).is_none());
+        assert_eq!(
+            excluded_by(&root.join("repo/scratch.txt"), false, vcs).map(|h| h.pattern),
+            Some("scratch.txt".to_string())
+        );
+        // `.gitignore` outside a repository does not apply.
+        assert!(excluded_by(&root.join("notes.md"), false, vcs).is_none());
+        assert!(
+            excluded_by(
+                &root.join("repo/sub/keep.log"),
+                false,
+                IgnoreFiles::default()
+            )
+            .is_none()
+        );
+
+        // `.catalogignore` outranks the whitelist in `.gitignore`.
+        let both = IgnoreFiles {
+            catalog: true,
+            ..vcs
+        };
+        let hit = excluded_by(&root.join("repo/sub/keep.log"), false, both).unwrap();
+        assert_eq!(hit.file, root.join("repo/sub/.catalogignore"));
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn cache_rereads_forgotten_ignore_files() {
+        let root = temp_dir("ignore_cache");
+        fs::create_dir_all(root.join("repo/.git")).unwrap();
+        fs::write(root.join("repo/.gitignore"), "*.log\n").unwrap();
+        let vcs = IgnoreFiles {
+            vcs: true,
+            ..Default::default()
+        };
+        let cache = IgnoreCache::default();
+        let log = root.join("repo/debug.log");
+        assert!(cache.excluded_by(&log, false, vcs).is_some());
+
+        fs::write(root.join("repo/.gitignore"), "*.tmp\n").unwrap();
+        assert!(
+            cache.excluded_by(&log, false, vcs).is_some(),
+            "still cached"
+        );
+        cache.forget(&root.join("repo/.gitignore"));
+        assert!(cache.excluded_by(&log, false, vcs).is_none());
+
+        // Without a repository `.gitignore` does not apply.
+        fs::write(root.join("repo/.gitignore"), "*.log\n").unwrap();
+        fs::remove_dir(root.join("repo/.git")).unwrap();
+        cache.forget(&root.join("repo/.gitignore"));
+        cache.forget(&root.join("repo/.git"));
+        assert!(cache.excluded_by(&log, false, vcs).is_none());
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
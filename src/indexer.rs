@@ -1,25 +1,118 @@
-use crate::config::Config;
-use crate::roots;
-use crate::store::{DirSizeEntry, FileEntry, Store, StoreData};
-use crate::util::{normalize_path_allow_missing, path_to_string};
+use crate::changes;
+use crate::checkpoint::{self, Checkpoint, PartialRoot};
+use crate::config::{Config, IgnoreFiles, RootOptions};
+use crate::ignore_files::{self, IgnoreCache, IgnoreHit};
+use crate::output;
+use crate::purge;
+use crate::roots::{self, RootState};
+use crate::store::{
+    ChangeEntry, DirSizeEntry, FileEntry, RootEntry, ScanRecord, Store, StoreData,
+};
+use crate::util::{device_id, file_identity, normalize_path_allow_missing, path_to_string};
 use anyhow::Result;
 use chrono::Local;
 use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
 use ignore::gitignore::{Gitignore, GitignoreBuilder};
 use ignore::{WalkBuilder, WalkState};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::hash::{DefaultHasher, Hash, Hasher};
 use std::path::{Path, PathBuf};
 use std::marker::PhantomData;
 use std::sync::mpsc;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::thread;
 use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
+/// Incremental runs list every directory again at least this often, to pick
+/// up files modified in place (which leaves their directory's mtime alone).
+pub const VERIFY_INTERVAL_DAYS: i64 = 7;
+
+/// How often a long run saves the store and a checkpoint to resume from.
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(120);
+
+/// Levels below a root listed one at a time before the subtrees under them
+/// are walked whole; an interrupted scan resumes from the subtrees it had
+/// not finished.
+const SPLIT_DEPTH: usize = 3;
+
+/// Subtrees walked together between checkpoints.
+const WALK_BATCH: usize = 64;
+
+#[derive(Debug, Default, Clone, Copy)]
 pub struct IndexStats {
     pub seen: usize,
-    pub updated: usize,
+    /// Entries not in the store before.
+    pub new: usize,
+    /// Entries whose size, mtime, type or inode changed.
+    pub changed: usize,
+    pub unchanged: usize,
+    /// Soft-deleted entries that showed up again.
+    pub resurrected: usize,
     pub deleted: usize,
+    pub moved: usize,
     pub skipped: usize,
+    /// The run stopped early; its progress is saved for the next run.
+    pub interrupted: bool,
+}
+
+impl IndexStats {
+    /// Entries written by the run: new, changed and resurrected.
+    pub fn updated(&self) -> usize {
+        self.new + self.changed + self.resurrected
+    }
+
+    pub fn add(&mut self, other: IndexStats) {
+        self.seen += other.seen;
+        self.new += other.new;
+        self.changed += other.changed;
+        self.unchanged += other.unchanged;
+        self.resurrected += other.resurrected;
+        self.deleted += other.deleted;
+        self.moved += other.moved;
+        self.skipped += other.skipped;
+    }
+
+    fn count(&mut self, merged: Merged) {
+        self.seen += 1;
+        match merged {
+            Merged::New => self.new += 1,
+            Merged::Changed => self.changed += 1,
+            Merged::Unchanged => self.unchanged += 1,
+            Merged::Resurrected => self.resurrected += 1,
+        }
+    }
+
+    pub fn summary(&self) -> String {
+        format!(
+            "Indexed {} files ({} new, {} changed, {} unchanged, {} resurrected, {} deleted, \
+             {} moved, {} skipped).",
+            self.seen,
+            self.new,
+            self.changed,
+            self.unchanged,
+            self.resurrected,
+            self.deleted,
+            self.moved,
+            self.skipped
+        )
+    }
+
+    fn progress(&self) -> String {
+        format!(
+            "files {} (new {}, changed {}, deleted {}, skipped {})",
+            self.seen, self.new, self.changed, self.deleted, self.skipped
+        )
+    }
+}
+
+/// How `RootMerge::apply` classified a scanned entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Merged {
+    New,
+    Changed,
+    Unchanged,
+    Resurrected,
 }
 
 #[derive(Debug, Clone)]
@@ -31,12 +124,45 @@ pub struct ScannedFile {
     pub size: i64,
     pub mtime: i64,
     pub ext: Option<String>,
+    pub dev: Option<u64>,
+    pub ino: Option<u64>,
+}
+
+impl From<&FileEntry> for ScannedFile {
+    fn from(file: &FileEntry) -> Self {
+        Self {
+            rel_path: file.rel_path.clone(),
+            abs_path: file.abs_path.clone(),
+            is_dir: file.is_dir,
+            is_symlink: file.is_symlink,
+            size: file.size,
+            mtime: file.mtime,
+            ext: file.ext.clone(),
+            dev: file.dev,
+            ino: file.ino,
+        }
+    }
+}
+
+/// One root to scan in a run; see `scan_root`.
+struct RootScan<'a> {
+    /// Where the root is now; differs from its configured path when its
+    /// volume was remounted elsewhere.
+    root: &'a str,
+    root_id: i64,
+    run_id: i64,
+    full: bool,
+    /// What an interrupted run left of this root.
+    resume: Option<PartialRoot>,
+    progress: ProgressBar,
 }
 
 struct RootScanResult {
     stats: IndexStats,
     duration: Duration,
     root_missing: bool,
+    /// Set when the scan stopped early: what is left of it.
+    interrupted: Option<PartialRoot>,
 }
 
 pub trait ScanObserver {
@@ -61,12 +187,22 @@ impl<'a> ObserverPtr<'a> {
 
 struct IgnoreMatcher {
     gitignore: Gitignore,
-    abs_excludes: Vec<PathBuf>,
+    /// Absolute excludes, normalized and as configured.
+    abs_excludes: Vec<(PathBuf, String)>,
     include_hidden: bool,
+    max_depth: Option<usize>,
+    ignore_files: IgnoreFiles,
+    /// Ignore files read for checks outside a walk.
+    ignore_cache: IgnoreCache,
 }
 
 enum ScanEvent {
     File(ScannedFile),
+    /// A directory whose mtime matches the store; its children were not
+    /// listed.
+    TrustedDir(ScannedFile),
+    /// A directory below the depth a listing round stops at.
+    Unlisted(ScannedFile),
     WalkError(String),
     MetadataError {
         path: String,
@@ -81,42 +217,105 @@ struct RootMerge {
     run_id: i64,
     file_index: HashMap<String, usize>,
     indices: Vec<usize>,
+    /// Entries inserted during the current run.
+    created: Vec<usize>,
+    /// Entries that went from active to deleted during the current run.
+    removed: Vec<usize>,
+    /// Deleted entries that reappeared.
+    restored: Vec<usize>,
+    /// Entries whose size, mtime or kind changed, with the old size and mtime.
+    modified: Vec<(usize, i64, i64)>,
+    /// Surviving entries of detected moves, with their size and mtime from
+    /// before the move.
+    moved: Vec<(usize, i64, i64)>,
+    /// Whether changes are journaled; a root's first index is not.
+    journal: bool,
+    /// Ids of the entries changed, when kept (by `LiveRoot`, whose saves
+    /// only compare those).
+    touched: Option<HashSet<i64>>,
 }
 
 impl RootMerge {
-    fn new(store: &mut StoreData, root_id: i64, run_id: i64, full: bool) -> Self {
+    fn new(store: &StoreData, root_id: i64, run_id: i64) -> Self {
         let mut file_index = HashMap::new();
         let mut indices = Vec::new();
-        for (idx, file) in store.files.iter_mut().enumerate() {
+        for (idx, file) in store.files.iter().enumerate() {
             if file.root_id == root_id {
-                if full {
-                    file.status = "deleted".to_string();
-                }
                 file_index.insert(file.rel_path.clone(), idx);
                 indices.push(idx);
             }
         }
+        let journal = store
+            .roots
+            .iter()
+            .any(|r| r.id == root_id && r.last_indexed_at.is_some());
         Self {
             root_id,
             run_id,
             file_index,
             indices,
+            created: Vec::new(),
+            removed: Vec::new(),
+            restored: Vec::new(),
+            modified: Vec::new(),
+            moved: Vec::new(),
+            journal,
+            touched: None,
         }
     }
 
-    fn apply(&mut self, store: &mut StoreData, scanned: ScannedFile) {
+    fn touch(&mut self, id: i64) {
+        if let Some(touched) = &mut self.touched {
+            touched.insert(id);
+        }
+    }
+
+    /// Upserts a scanned entry. Unchanged entries are only marked as seen;
+    /// an inode is compared only when both sides have one.
+    fn apply(&mut self, store: &mut StoreData, scanned: ScannedFile) -> Merged {
         if let Some(&idx) = self.file_index.get(&scanned.rel_path) {
+            self.touch(store.files[idx].id);
             let file = &mut store.files[idx];
+            let was_active = file.status == "active";
+            let same = file.is_dir == scanned.is_dir
+                && file.is_symlink == scanned.is_symlink
+                && file.size == scanned.size
+                && file.mtime == scanned.mtime
+                && (file.ino.is_none() || scanned.ino.is_none() || file.ino == scanned.ino);
+            file.last_seen_run = self.run_id;
+            if was_active && same {
+                // The root may have been found under a new mount point.
+                if file.abs_path != scanned.abs_path {
+                    file.abs_path = scanned.abs_path;
+                    file.dev = scanned.dev;
+                }
+                return Merged::Unchanged;
+            }
+            if self.journal {
+                if was_active {
+                    self.modified.push((idx, file.size, file.mtime));
+                } else {
+                    self.restored.push(idx);
+                }
+            }
             file.abs_path = scanned.abs_path;
             file.is_dir = scanned.is_dir;
             file.is_symlink = scanned.is_symlink;
             file.size = scanned.size;
             file.mtime = scanned.mtime;
             file.ext = scanned.ext;
+            file.dev = scanned.dev;
+            file.ino = scanned.ino;
             file.status = "active".to_string();
-            file.last_seen_run = self.run_id;
+            file.deleted_at = None;
+            if was_active {
+                Merged::Changed
+            } else {
+                Merged::Resurrected
+            }
         } else {
             let id = store.next_file_id();
+            self.touch(id);
             let rel_key = scanned.rel_path.clone();
             let idx = store.files.len();
             store.files.push(FileEntry {
@@ -131,29 +330,628 @@ impl RootMerge {
                 ext: scanned.ext,
                 status: "active".to_string(),
                 last_seen_run: self.run_id,
+                dev: scanned.dev,
+                ino: scanned.ino,
+                prev_path: None,
+                deleted_at: None,
             });
             self.file_index.insert(rel_key, idx);
             self.indices.push(idx);
+            self.created.push(idx);
+            Merged::New
+        }
+    }
+
+    /// Picks up a run saved by a checkpoint: the entries it created (ids from
+    /// `first_file_id` on) may still turn out to be moves.
+    fn resume(&mut self, store: &StoreData, first_file_id: i64) {
+        for &idx in &self.indices {
+            let file = &store.files[idx];
+            if file.id >= first_file_id && file.last_seen_run == self.run_id {
+                self.created.push(idx);
+            }
+        }
+    }
+
+    fn is_active_dir(&self, store: &StoreData, rel_path: &str) -> bool {
+        self.file_index
+            .get(rel_path)
+            .map(|&idx| store.files[idx].is_dir && store.files[idx].status == "active")
+            .unwrap_or(false)
+    }
+
+    fn mark_missing(&mut self, store: &mut StoreData, rel_path: &str) -> usize {
+        let Some(&idx) = self.file_index.get(rel_path) else {
+            return 0;
+        };
+        let before = self.removed.len();
+        let now = Local::now().timestamp();
+        let file = &mut store.files[idx];
+        let is_dir = file.is_dir;
+        let id = file.id;
+        if file.status != "deleted" {
+            mark_deleted(file, now);
+            self.removed.push(idx);
+            self.touch(id);
+        }
+        if is_dir {
+            let prefix = format!("{}/", rel_path);
+            for &idx in &self.indices {
+                let file = &mut store.files[idx];
+                if file.status != "deleted" && file.rel_path.starts_with(&prefix) {
+                    mark_deleted(file, now);
+                    self.removed.push(idx);
+                    if let Some(touched) = &mut self.touched {
+                        touched.insert(file.id);
+                    }
+                }
+            }
+        }
+        self.removed.len() - before
+    }
+
+    /// Pairs entries that disappeared during this run with entries created in
+    /// the same run that share their device/inode identity. The surviving
+    /// entry keeps its id (and therefore its tags) and records where it used
+    /// to live; the duplicate created entries are returned for removal.
+    fn reconcile_moves(&mut self, store: &mut StoreData) -> Vec<usize> {
+        let mut drop = Vec::new();
+        if self.created.is_empty() || self.removed.is_empty() {
+            return drop;
+        }
+
+        let mut disappeared: HashMap<(u64, u64), usize> = HashMap::new();
+        for &idx in &self.removed {
+            let file = &store.files[idx];
+            if file.status != "deleted" {
+                continue;
+            }
+            if let (Some(dev), Some(ino)) = (file.dev, file.ino) {
+                disappeared.entry((dev, ino)).or_insert(idx);
+            }
+        }
+
+        // Names and identities of the entries that disappeared and of those
+        // created, by the relative path of their parent directory.
+        let children = |indices: &[usize]| {
+            let mut out: HashMap<&str, HashSet<(&str, u64, u64)>> = HashMap::new();
+            for &idx in indices {
+                let file = &store.files[idx];
+                if let (Some(dev), Some(ino)) = (file.dev, file.ino) {
+                    let (parent, name) = file
+                        .rel_path
+                        .rsplit_once('/')
+                        .unwrap_or(("", &file.rel_path));
+                    out.entry(parent).or_default().insert((name, dev, ino));
+                }
+            }
+            out
+        };
+        let (old_children, new_children) = (children(&self.removed), children(&self.created));
+
+        let mut pairs = Vec::new();
+        for &new_idx in &self.created {
+            let new = &store.files[new_idx];
+            let (Some(dev), Some(ino)) = (new.dev, new.ino) else {
+                continue;
+            };
+            let Some(&old_idx) = disappeared.get(&(dev, ino)) else {
+                continue;
+            };
+            // Inode numbers get reused after a delete. A rename keeps a
+            // file's size and mtime; a directory must keep its name or a
+            // child (same name and inode) to count as the same one.
+            let old = &store.files[old_idx];
+            let same = if old.is_dir != new.is_dir {
+                false
+            } else if new.is_dir {
+                file_name(&old.rel_path) == file_name(&new.rel_path)
+                    || match (
+                        old_children.get(old.rel_path.as_str()),
+                        new_children.get(new.rel_path.as_str()),
+                    ) {
+                        (Some(old), Some(new)) => !old.is_disjoint(new),
+                        _ => false,
+                    }
+            } else {
+                old.size == new.size && old.mtime == new.mtime
+            };
+            if same {
+                disappeared.remove(&(dev, ino));
+                pairs.push((old_idx, new_idx));
+            }
+        }
+
+        for (old_idx, new_idx) in pairs {
+            self.touch(store.files[old_idx].id);
+            self.touch(store.files[new_idx].id);
+            let new = store.files[new_idx].clone();
+            let old = &mut store.files[old_idx];
+            tracing::debug!("moved: {} -> {}", old.abs_path, new.abs_path);
+            if self.file_index.get(&old.rel_path) == Some(&old_idx) {
+                self.file_index.remove(&old.rel_path);
+            }
+            self.moved.push((old_idx, old.size, old.mtime));
+            old.prev_path = Some(std::mem::take(&mut old.abs_path));
+            old.rel_path = new.rel_path.clone();
+            old.abs_path = new.abs_path;
+            old.is_dir = new.is_dir;
+            old.is_symlink = new.is_symlink;
+            old.size = new.size;
+            old.mtime = new.mtime;
+            old.ext = new.ext;
+            old.status = new.status;
+            old.deleted_at = None;
+            old.last_seen_run = new.last_seen_run;
+            self.file_index.insert(new.rel_path, old_idx);
+            drop.push(new_idx);
+        }
+        drop
+    }
+
+    /// Turns the bookkeeping since the last call into journal records and
+    /// resets it. Call after `reconcile_moves`, before `drop` is removed.
+    fn take_changes(&mut self, store: &StoreData, drop: &[usize]) -> Vec<ChangeEntry> {
+        let mut out = Vec::new();
+        if self.journal {
+            let at = Local::now().timestamp();
+            let change = |idx, kind, old| self.change(store, at, idx, kind, old);
+            let active = |idx: &usize| store.files[*idx].status == "active";
+            let dropped: HashSet<usize> = drop.iter().copied().collect();
+            let created: HashSet<usize> = self.created.iter().copied().collect();
+            let moved: HashSet<usize> = self.moved.iter().map(|&(idx, _, _)| idx).collect();
+
+            for &idx in self.created.iter().chain(&self.restored) {
+                if !dropped.contains(&idx) && active(&idx) {
+                    out.push(change(idx, "created", None));
+                }
+            }
+            for &(idx, size, mtime) in &self.modified {
+                if active(&idx) && !moved.contains(&idx) {
+                    out.push(change(idx, "modified", Some((size, mtime))));
+                }
+            }
+            for &(idx, size, mtime) in &self.moved {
+                out.push(change(idx, "moved", Some((size, mtime))));
+            }
+            let mut seen = HashSet::new();
+            for &idx in &self.removed {
+                // Entries created and removed again within one batch never
+                // existed as far as the journal is concerned.
+                if active(&idx) || moved.contains(&idx) || created.contains(&idx) {
+                    continue;
+                }
+                if seen.insert(idx) {
+                    let file = &store.files[idx];
+                    out.push(change(idx, "deleted", Some((file.size, file.mtime))));
+                }
+            }
+        }
+        self.created.clear();
+        self.removed.clear();
+        self.restored.clear();
+        self.modified.clear();
+        self.moved.clear();
+        out
+    }
+
+    /// Journal records for the entries seen changed or back so far, for a
+    /// checkpoint. Created entries wait for `take_changes`, since they may
+    /// still pair up with entries that turn out missing as moves.
+    fn take_seen_changes(&mut self, store: &StoreData) -> Vec<ChangeEntry> {
+        let at = Local::now().timestamp();
+        let mut out = Vec::new();
+        if self.journal {
+            for &idx in &self.restored {
+                out.push(self.change(store, at, idx, "created", None));
+            }
+            for &(idx, size, mtime) in &self.modified {
+                out.push(self.change(store, at, idx, "modified", Some((size, mtime))));
+            }
+        }
+        self.restored.clear();
+        self.modified.clear();
+        out
+    }
+
+    fn change(
+        &self,
+        store: &StoreData,
+        at: i64,
+        idx: usize,
+        kind: &str,
+        old: Option<(i64, i64)>,
+    ) -> ChangeEntry {
+        let file = &store.files[idx];
+        let new = (file.status == "active").then_some((file.size, file.mtime));
+        ChangeEntry {
+            run_id: self.run_id,
+            at,
+            file_id: file.id,
+            root_id: file.root_id,
+            kind: kind.to_string(),
+            path: file.abs_path.clone(),
+            prev_path: if kind == "moved" { file.prev_path.clone() } else { None },
+            is_dir: file.is_dir,
+            old_size: old.map(|o| o.0),
+            new_size: new.map(|n| n.0),
+            old_mtime: old.map(|o| o.1),
+            new_mtime: new.map(|n| n.1),
         }
     }
 
-    fn finalize(self, store: &mut StoreData) -> usize {
-        let mut deleted = 0;
-        for idx in self.indices {
+    fn finalize(mut self, store: &mut StoreData) -> (usize, usize) {
+        let now = Local::now().timestamp();
+        for &idx in &self.indices {
             let file = &mut store.files[idx];
             if file.last_seen_run != self.run_id && file.status != "deleted" {
-                file.status = "deleted".to_string();
-                deleted += 1;
+                mark_deleted(file, now);
+                self.removed.push(idx);
             }
         }
+        let removed = self
+            .removed
+            .iter()
+            .filter(|&&idx| store.files[idx].status == "deleted")
+            .count();
+        let drop = self.reconcile_moves(store);
+        let moved = drop.len();
+        let journal = self.take_changes(store, &drop);
+        remove_entries(store, &drop);
+        store.changes.extend(journal);
 
         let now = Local::now().to_rfc3339();
         if let Some(root_entry) = store.roots.iter_mut().find(|r| r.id == self.root_id) {
             root_entry.last_indexed_at = Some(now);
         }
 
-        deleted
+        (removed - moved, moved)
+    }
+}
+
+fn file_name(rel_path: &str) -> &str {
+    rel_path.rsplit_once('/').map_or(rel_path, |(_, name)| name)
+}
+
+/// Relative path -> (mtime, inode) of directories an incremental scan may skip.
+type TrustedDirs = HashMap<String, (i64, Option<u64>)>;
+
+/// Lets an incremental scan skip listing directories whose mtime (and inode)
+/// still match the store. Creating, removing or renaming a child bumps the
+/// directory's mtime, so the children the store knows about are still all
+/// there; they are carried forward as seen and subdirectories are walked on
+/// their own, since their changes do not show up in the parent's mtime.
+struct DirShortcut {
+    /// Directories that may be skipped, with the mtime and inode they must
+    /// still have.
+    trusted: Arc<TrustedDirs>,
+    /// Active entries by the relative path of their parent directory.
+    children: HashMap<String, Vec<usize>>,
+    /// Device of the root when the walk stays on one filesystem.
+    root_dev: Option<u64>,
+}
+
+impl DirShortcut {
+    fn new(store: &StoreData, root_id: i64, last_started_at: i64, root_dev: Option<u64>) -> Self {
+        let mut trusted = HashMap::new();
+        let mut children: HashMap<String, Vec<usize>> = HashMap::new();
+        for (idx, file) in store.files.iter().enumerate() {
+            if file.root_id != root_id || file.status != "active" {
+                continue;
+            }
+            // A directory changed within the second it was last listed in
+            // keeps the mtime the store saw, so only older ones are trusted.
+            if file.is_dir && !file.is_symlink && file.mtime < last_started_at {
+                trusted.insert(file.rel_path.clone(), (file.mtime, file.ino));
+            }
+            let parent = Path::new(&file.rel_path)
+                .parent()
+                .map(path_to_string)
+                .unwrap_or_default();
+            children.entry(parent).or_default().push(idx);
+        }
+        Self {
+            trusted: Arc::new(trusted),
+            children,
+            root_dev,
+        }
+    }
+
+    /// The known children of a skipped directory to carry forward. Its
+    /// subdirectories are pushed onto `walk` instead, except mount points the
+    /// walk would not descend into.
+    fn expand(
+        &self,
+        store: &StoreData,
+        root_path: &Path,
+        rel_path: &str,
+        walk: &mut Vec<PathBuf>,
+    ) -> Vec<ScannedFile> {
+        let mut carried = Vec::new();
+        for &idx in self.children.get(rel_path).into_iter().flatten() {
+            let file = &store.files[idx];
+            let other_fs =
+                self.root_dev.is_some() && file.dev.is_some() && file.dev != self.root_dev;
+            if file.is_dir && !file.is_symlink && !other_fs {
+                walk.push(root_path.join(&file.rel_path));
+            } else {
+                carried.push(ScannedFile::from(file));
+            }
+        }
+        carried
+    }
+}
+
+/// Fingerprint of the settings that decide which entries a walk yields; the
+/// shortcut is off for a scan whose settings differ from the last one.
+/// `DefaultHasher` may change between toolchains, which only costs one full
+/// listing.
+fn scan_settings(options: &RootOptions) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    options.excludes.hash(&mut hasher);
+    options.include_hidden.hash(&mut hasher);
+    options.one_filesystem.hash(&mut hasher);
+    // Only mixed in when set, so roots without them keep the fingerprint
+    // they had before these settings existed.
+    if options.max_depth.is_some() || options.follow_symlinks || options.ignore_files.any() {
+        options.max_depth.hash(&mut hasher);
+        options.follow_symlinks.hash(&mut hasher);
+        options.ignore_files.hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+/// Soft-deletes `file`; `deleted_at` is cleared again if it comes back.
+fn mark_deleted(file: &mut FileEntry, now: i64) {
+    file.status = "deleted".to_string();
+    file.deleted_at = Some(now);
+}
+
+fn remove_entries(store: &mut StoreData, indices: &[usize]) {
+    if indices.is_empty() {
+        return;
+    }
+    let drop: HashSet<usize> = indices.iter().copied().collect();
+    let mut idx = 0;
+    store.files.retain(|_| {
+        let keep = !drop.contains(&idx);
+        idx += 1;
+        keep
+    });
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct LiveStats {
+    pub updated: usize,
+    pub deleted: usize,
+    pub moved: usize,
+    pub skipped: usize,
+}
+
+impl LiveStats {
+    pub fn changed(&self) -> bool {
+        self.updated > 0 || self.deleted > 0 || self.moved > 0
+    }
+
+    pub fn add(&mut self, other: LiveStats) {
+        self.updated += other.updated;
+        self.deleted += other.deleted;
+        self.moved += other.moved;
+        self.skipped += other.skipped;
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct PathRefresh {
+    pub stats: LiveStats,
+    /// Everything below the path was handled as well (deleted or re-walked).
+    pub subtree: bool,
+}
+
+/// Merge state for one root kept alive between filesystem notification
+/// batches, so changed paths can be re-checked without walking the root.
+pub struct LiveRoot {
+    root_id: i64,
+    root: String,
+    root_path: PathBuf,
+    root_dev: Option<u64>,
+    one_filesystem: bool,
+    follow_symlinks: bool,
+    matcher: Arc<IgnoreMatcher>,
+    merge: RootMerge,
+}
+
+impl LiveRoot {
+    pub fn new(
+        store: &mut StoreData,
+        cfg: &Config,
+        root: &RootEntry,
+        one_filesystem_override: bool,
+    ) -> Result<Self> {
+        let root_path = normalize_path_allow_missing(&root.path)?;
+        let root_dev = std::fs::metadata(&root_path)
+            .ok()
+            .and_then(|meta| device_id(&meta));
+        let options = cfg.root_options(&root.path);
+        let matcher = Arc::new(build_matcher(&options, &root.path)?);
+        let run_id = store.last_run_id;
+        let mut merge = RootMerge::new(store, root.id, run_id);
+        merge.touched = Some(HashSet::new());
+        Ok(Self {
+            root_id: root.id,
+            root: root.path.clone(),
+            root_path,
+            root_dev,
+            one_filesystem: one_filesystem_override || options.one_filesystem,
+            follow_symlinks: options.follow_symlinks,
+            matcher,
+            merge,
+        })
+    }
+
+    pub fn root_path(&self) -> &Path {
+        &self.root_path
+    }
+
+    fn reload(&mut self, store: &mut StoreData) {
+        let run_id = self.merge.run_id;
+        let touched = self.merge.touched.take();
+        self.merge = RootMerge::new(store, self.root_id, run_id);
+        self.merge.touched = touched;
+    }
+
+    /// Ids of the entries changed since the last call, for
+    /// `Store::save_changed`.
+    pub fn take_changed(&mut self) -> HashSet<i64> {
+        self.merge
+            .touched
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    pub fn contains(&self, path: &Path) -> bool {
+        path != self.root_path && path.starts_with(&self.root_path)
+    }
+
+    /// Rereads an ignore file (or repository) at `path` on its next use.
+    /// Call for every path of a batch before refreshing any of them.
+    pub fn invalidate(&self, path: &Path) {
+        self.matcher.ignore_cache.forget(path);
+    }
+
+    /// Re-checks a single path reported by the watcher. Existing entries are
+    /// upserted, and directories not yet in the store are walked so moved-in
+    /// trees get indexed. Missing entries are soft deleted along with
+    /// everything below them.
+    pub fn refresh(
+        &mut self,
+        store: &mut StoreData,
+        path: &Path,
+        run_id: i64,
+    ) -> Result<PathRefresh> {
+        self.merge.run_id = run_id;
+        let mut result = PathRefresh::default();
+        let rel = match path.strip_prefix(&self.root_path) {
+            Ok(rel) if !rel.as_os_str().is_empty() => rel,
+            _ => return Ok(result),
+        };
+        let rel_path = path_to_string(rel);
+
+        let meta = match metadata(path, self.follow_symlinks) {
+            Ok(meta) => meta,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                result.stats.deleted += self.merge.mark_missing(store, &rel_path);
+                result.subtree = true;
+                return Ok(result);
+            }
+            Err(err) => {
+                tracing::debug!("metadata error: {} ({})", path.display(), err);
+                result.stats.skipped += 1;
+                return Ok(result);
+            }
+        };
+
+        let is_dir = meta.is_dir();
+        if should_skip(path, is_dir, &self.root_path, &self.matcher)
+            || self
+                .matcher
+                .ignore_cache
+                .excluded_by(path, is_dir, self.matcher.ignore_files)
+                .is_some()
+            || (self.one_filesystem && self.root_dev.is_some() && device_id(&meta) != self.root_dev)
+        {
+            return Ok(result);
+        }
+
+        let known_dir = self.merge.is_active_dir(store, &rel_path);
+        let is_symlink =
+            meta.file_type().is_symlink() || (self.follow_symlinks && path.is_symlink());
+        let scanned = scanned_file(path, rel, &meta, is_dir, is_symlink);
+        if self.merge.apply(store, scanned) != Merged::Unchanged {
+            result.stats.updated += 1;
+        }
+        if is_dir && !known_dir && !at_max_depth(path, &self.root_path, &self.matcher) {
+            result.stats.add(self.refresh_subtree(store, path)?);
+            result.subtree = true;
+        }
+        Ok(result)
+    }
+
+    fn refresh_subtree(&mut self, store: &mut StoreData, dir: &Path) -> Result<LiveStats> {
+        let mut stats = LiveStats::default();
+        let mut builder = WalkBuilder::new(dir);
+        ignore_files::configure(&mut builder, self.matcher.ignore_files);
+        builder
+            .follow_links(self.follow_symlinks)
+            .same_file_system(self.one_filesystem);
+        let matcher = self.matcher.clone();
+        let root_path = self.root_path.clone();
+        builder.filter_entry(move |entry| {
+            let is_dir = entry.file_type().map(|ft| ft.is_dir()).unwrap_or(false);
+            !should_skip(entry.path(), is_dir, &root_path, &matcher)
+        });
+
+        for entry in builder.build() {
+            let entry = match entry {
+                Ok(e) => e,
+                Err(err) => {
+                    tracing::debug!("walk error: {}", err);
+                    stats.skipped += 1;
+                    continue;
+                }
+            };
+            let path = entry.path();
+            if path == dir {
+                continue;
+            }
+            let meta = match metadata(path, self.follow_symlinks) {
+                Ok(m) => m,
+                Err(_) => {
+                    stats.skipped += 1;
+                    continue;
+                }
+            };
+            let Ok(rel) = path.strip_prefix(&self.root_path) else {
+                stats.skipped += 1;
+                continue;
+            };
+            let scanned = scanned_file(
+                path,
+                rel,
+                &meta,
+                meta.is_dir(),
+                entry.path_is_symlink(),
+            );
+            if self.merge.apply(store, scanned) != Merged::Unchanged {
+                stats.updated += 1;
+            }
+        }
+        tracing::debug!("refreshed subtree {} under {}", dir.display(), self.root);
+        Ok(stats)
+    }
+}
+
+/// Folds the delete + create pairs collected by `LiveRoot::refresh` into
+/// moves (see `RootMerge::reconcile_moves`). Removing the redundant entries
+/// shifts indices, so every live root is reloaded afterwards.
+pub fn reconcile_live_moves(store: &mut StoreData, live: &mut [LiveRoot]) -> usize {
+    let mut drop = Vec::new();
+    let mut journal = Vec::new();
+    for root in live.iter_mut() {
+        let dropped = root.merge.reconcile_moves(store);
+        journal.extend(root.merge.take_changes(store, &dropped));
+        drop.extend(dropped);
+    }
+    store.changes.extend(journal);
+    if drop.is_empty() {
+        return 0;
+    }
+    remove_entries(store, &drop);
+    for root in live.iter_mut() {
+        root.reload(store);
     }
+    drop.len()
 }
 
 pub fn run(
@@ -162,7 +960,7 @@ pub fn run(
     full: bool,
     one_filesystem_override: bool,
 ) -> Result<IndexStats> {
-    run_internal(store, cfg, full, one_filesystem_override, None)
+    run_internal(store, cfg, full, one_filesystem_override, None, None)
 }
 
 pub fn run_with_observer(
@@ -178,23 +976,101 @@ pub fn run_with_observer(
         full,
         one_filesystem_override,
         Some(observer),
+        None,
     )
 }
 
+/// Like `run`, but stops once `stop` is set (see `interrupt::install`): the
+/// store is saved with what was indexed so far and `IndexStats::interrupted`
+/// is set. The next run resumes where this one stopped.
+pub fn run_interruptible(
+    store: &mut Store,
+    cfg: &Config,
+    full: bool,
+    one_filesystem_override: bool,
+    stop: Arc<AtomicBool>,
+) -> Result<IndexStats> {
+    run_internal(store, cfg, full, one_filesystem_override, None, Some(stop))
+}
+
+/// The `catalog index` flags a run was started with, for messages.
+fn run_flags(full: bool, one_filesystem: bool) -> String {
+    let flags: Vec<&str> = [(full, "--full"), (one_filesystem, "--one-filesystem")]
+        .into_iter()
+        .filter_map(|(set, flag)| set.then_some(flag))
+        .collect();
+    if flags.is_empty() {
+        "none".to_string()
+    } else {
+        flags.join(" ")
+    }
+}
+
+/// The state of the current run that a checkpoint saves, and when to save it.
+struct RunProgress {
+    checkpoint: Checkpoint,
+    stop: Arc<AtomicBool>,
+    last_saved: Instant,
+}
+
+impl RunProgress {
+    fn stopped(&self) -> bool {
+        self.stop.load(Ordering::Relaxed)
+    }
+
+    fn due(&self) -> bool {
+        self.last_saved.elapsed() >= CHECKPOINT_INTERVAL
+    }
+
+    /// Saves the store, then the checkpoint describing it.
+    fn save(&mut self, store: &Store, partial: Option<PartialRoot>) -> Result<()> {
+        self.checkpoint.partial = partial;
+        store.save()?;
+        checkpoint::save(&store.path, &self.checkpoint)?;
+        self.last_saved = Instant::now();
+        Ok(())
+    }
+}
+
 fn run_internal(
     store: &mut Store,
     cfg: &Config,
     full: bool,
     one_filesystem_override: bool,
     observer: Option<&mut dyn ScanObserver>,
+    stop: Option<Arc<AtomicBool>>,
 ) -> Result<IndexStats> {
+    store.make_writable();
     roots::sync_roots(&mut store.data, cfg, None)?;
-    let run_id = store.data.next_run_id();
+    // Only `run_interruptible` (i.e. `catalog index`) picks up a checkpoint:
+    // other callers, like `analyze`, need every root walked.
+    let resumed = stop
+        .is_some()
+        .then(|| checkpoint::load(&store.path))
+        .flatten()
+        .filter(|checkpoint| checkpoint.run_id == store.data.last_run_id);
+    let checkpoint = match resumed.clone() {
+        Some(checkpoint) => checkpoint,
+        None => Checkpoint {
+            run_id: store.data.next_run_id(),
+            first_file_id: store.data.next_file_id,
+            full,
+            one_filesystem: one_filesystem_override,
+            done: Vec::new(),
+            partial: None,
+        },
+    };
+    let run_id = checkpoint.run_id;
+    let requested = run_flags(full, one_filesystem_override);
+    let full = checkpoint.full;
+    let one_filesystem_override = checkpoint.one_filesystem;
+    let mut run = RunProgress {
+        checkpoint,
+        stop: stop.unwrap_or_default(),
+        last_saved: Instant::now(),
+    };
 
-    let mut total_seen = 0;
-    let mut total_updated = 0;
-    let mut total_deleted = 0;
-    let mut total_skipped = 0;
+    let mut total = IndexStats::default();
     let mut dir_sizes: HashMap<PathBuf, u64> = HashMap::new();
 
     let mut roots = store.data.roots.clone();
@@ -206,52 +1082,152 @@ fn run_internal(
         ProgressStyle::with_template("{bar:40.cyan/blue} {pos}/{len} | {msg}")
             .unwrap_or_else(|_| ProgressStyle::default_bar());
     overall.set_style(overall_style);
-    overall.set_message("files 0 (updated 0, deleted 0, skipped 0)");
+    overall.set_message(total.progress());
+    if resumed.is_some() {
+        let _ = multi.println(format!(
+            "Resuming interrupted index run {} ({} of {} roots done)",
+            run_id,
+            run.checkpoint.done.len(),
+            roots.len()
+        ));
+        // A resumed root keeps what the walk so far was based on, so the
+        // run finishes with the flags it started with.
+        let started = run_flags(full, one_filesystem_override);
+        if started != requested {
+            let _ = multi.println(format!(
+                "Warning: resuming run {} with the flags it was started with ({}), not the ones given now ({})",
+                run_id, started, requested
+            ));
+        }
+    }
 
     let observer_ptr = observer.map(ObserverPtr::new);
 
+    let states = roots::resolve_roots(&roots);
     for root in roots {
+        if run.checkpoint.done.contains(&root.id) {
+            overall.inc(1);
+            continue;
+        }
+        if run.stopped() {
+            run.save(store, None)?;
+            total.interrupted = true;
+            overall.abandon_with_message(total.progress());
+            return Ok(total);
+        }
         let pb = multi.add(ProgressBar::new_spinner());
-        let one_fs = one_filesystem_override || root.one_filesystem;
-        let result = scan_root(
-            &mut store.data,
-            cfg,
-            &root.path,
-            root.id,
+        let scan_path = match states.get(&root.id) {
+            Some(RootState::Offline(name)) => {
+                // Entries stay as they are until the volume comes back; keep
+                // their directory sizes too.
+                tracing::info!("root offline: {} (volume {})", root.path, name);
+                for entry in &store.data.dir_sizes {
+                    if Path::new(&entry.path).starts_with(&root.path) {
+                        dir_sizes.insert(PathBuf::from(&entry.path), entry.size);
+                    }
+                }
+                overall.inc(1);
+                pb.finish_with_message(format!("offline ({})", name));
+                continue;
+            }
+            Some(RootState::Missing) => {
+                tracing::warn!("root missing: {}", root.path);
+                overall.inc(1);
+                pb.finish_with_message("missing");
+                dir_sizes.entry(PathBuf::from(&root.path)).or_insert(0);
+                continue;
+            }
+            Some(RootState::Remounted(path)) => {
+                tracing::info!("root {} found at {}", root.path, path.display());
+                path_to_string(path)
+            }
+            _ => root.path.clone(),
+        };
+        let resume = run
+            .checkpoint
+            .partial
+            .take()
+            .filter(|partial| partial.root_id == root.id);
+        // Roots with an `interval` wait for it to pass since their last
+        // scan, unless the run is `--full`.
+        let interval = cfg.root(&root.path).and_then(|r| r.interval_secs());
+        let due_at = match (interval, root.scan) {
+            (Some(interval), Some(scan)) if !full && resume.is_none() => {
+                Some(scan.started_at + interval)
+            }
+            _ => None,
+        };
+        if let Some(due_at) = due_at.filter(|&at| at > Local::now().timestamp()) {
+            tracing::info!("root not due: {}", root.path);
+            for entry in &store.data.dir_sizes {
+                if Path::new(&entry.path).starts_with(&root.path) {
+                    dir_sizes.insert(PathBuf::from(&entry.path), entry.size);
+                }
+            }
+            run.checkpoint.done.push(root.id);
+            overall.inc(1);
+            pb.finish_with_message(format!("not due until {}", output::format_time(due_at)));
+            continue;
+        }
+        let mut options = cfg.root_options(&root.path);
+        options.one_filesystem |= one_filesystem_override;
+        let scan = RootScan {
+            root: &scan_path,
+            root_id: root.id,
             run_id,
             full,
-            one_fs,
-            pb.clone(),
+            resume,
+            progress: pb.clone(),
+        };
+        let result = scan_root(
+            store,
+            &options,
+            scan,
             Some(&mut dir_sizes),
             observer_ptr,
+            &mut run,
         )?;
 
-        total_seen += result.stats.seen;
-        total_updated += result.stats.updated;
-        total_deleted += result.stats.deleted;
-        total_skipped += result.stats.skipped;
+        total.add(result.stats);
+        if let Some(partial) = result.interrupted {
+            run.save(store, Some(partial))?;
+            total.interrupted = true;
+            pb.abandon_with_message("interrupted");
+            overall.abandon_with_message(total.progress());
+            return Ok(total);
+        }
+        run.checkpoint.done.push(root.id);
         overall.inc(1);
-        overall.set_message(format!(
-            "files {} (updated {}, deleted {}, skipped {})",
-            total_seen, total_updated, total_deleted, total_skipped
-        ));
+        overall.set_message(total.progress());
 
         if result.root_missing {
             pb.finish_with_message("missing");
         } else {
             pb.finish_with_message(format!("{:.2}s", result.duration.as_secs_f64()));
+            let volume = roots::root_volume(Path::new(&scan_path));
+            if let Some(entry) = store.data.roots.iter_mut().find(|r| r.id == root.id) {
+                entry.volume = volume;
+            }
         }
 
-        let root_path = normalize_path_allow_missing(&root.path)?;
+        let root_path = normalize_path_allow_missing(&scan_path)?;
         dir_sizes.entry(root_path).or_insert(0);
     }
 
-    overall.finish_with_message(format!(
-        "files {} (updated {}, deleted {}, skipped {})",
-        total_seen, total_updated, total_deleted, total_skipped
-    ));
+    let now = Local::now().timestamp();
+    changes::prune(&mut store.data, cfg, now);
+    let purged = purge::apply_retention(&mut store.data, cfg, now);
+    if purged > 0 {
+        tracing::debug!("purged {} entries past deleted retention", purged);
+    }
+
+    overall.finish_with_message(total.progress());
 
-    if !dir_sizes.is_empty() {
+    // Roots finished before a resume were not walked this time, so the
+    // totals would be partial; leave the cache stale instead.
+    if resumed.is_some() {
+        dir_sizes.clear();
+    } else if !dir_sizes.is_empty() {
         let mut entries = dir_sizes
             .into_iter()
             .map(|(path, size)| DirSizeEntry {
@@ -266,27 +1242,27 @@ fn run_internal(
         store.data.dir_sizes.clear();
         store.data.dir_sizes_run_id = run_id;
     }
+    checkpoint::clear(&store.path)?;
 
-    Ok(IndexStats {
-        seen: total_seen,
-        updated: total_updated,
-        deleted: total_deleted,
-        skipped: total_skipped,
-    })
+    Ok(total)
 }
 
 fn scan_root(
-    store: &mut StoreData,
-    cfg: &Config,
-    root: &str,
-    root_id: i64,
-    run_id: i64,
-    full: bool,
-    one_filesystem: bool,
-    progress: ProgressBar,
+    store: &mut Store,
+    options: &RootOptions,
+    scan: RootScan<'_>,
     mut dir_sizes: Option<&mut HashMap<PathBuf, u64>>,
     observer: Option<ObserverPtr<'_>>,
+    run: &mut RunProgress,
 ) -> Result<RootScanResult> {
+    let RootScan {
+        root,
+        root_id,
+        run_id,
+        full,
+        resume,
+        progress,
+    } = scan;
     let root_path = normalize_path_allow_missing(root)?;
     let started = Instant::now();
 
@@ -305,122 +1281,155 @@ fn scan_root(
         progress.set_message(format!("Root missing: {}", root));
         progress.disable_steady_tick();
         return Ok(RootScanResult {
-            stats: IndexStats {
-                seen: 0,
-                updated: 0,
-                deleted: 0,
-                skipped: 0,
-            },
+            stats: IndexStats::default(),
             duration: started.elapsed(),
             root_missing: true,
+            interrupted: None,
         });
     }
 
-    let matcher = Arc::new(build_matcher(cfg, root)?);
-    let mut merger = RootMerge::new(store, root_id, run_id, full);
-
-    let (tx, rx) = mpsc::channel();
-    let worker_root = root_path.clone();
-    let worker_matcher = matcher.clone();
-    let handle = thread::spawn(move || {
-        let mut builder = WalkBuilder::new(&worker_root);
-        builder
-            .follow_links(false)
-            .same_file_system(one_filesystem)
-            .standard_filters(false);
-        let walker = builder.build_parallel();
-        walker.run(move || {
-            let tx = tx.clone();
-            let matcher = worker_matcher.clone();
-            let root_path = worker_root.clone();
-            Box::new(move |entry| {
-                let entry = match entry {
-                    Ok(e) => e,
-                    Err(err) => {
-                        let _ = tx.send(ScanEvent::WalkError(err.to_string()));
-                        return WalkState::Continue;
+    let matcher = Arc::new(build_matcher(options, root)?);
+    let one_filesystem = options.one_filesystem;
+    let mut merger = RootMerge::new(&store.data, root_id, run_id);
+    let root_dev = std::fs::metadata(&root_path)
+        .ok()
+        .and_then(|m| device_id(&m));
+
+    // Directories still to walk, and how many levels below the root have
+    // been listed so far. The first `SPLIT_DEPTH` levels are listed one at a
+    // time; the subtrees below them are then walked in batches, so a
+    // checkpoint can name the ones not walked yet.
+    let (started_at, mut pending, mut level) = match &resume {
+        Some(partial) => {
+            merger.resume(&store.data, run.checkpoint.first_file_id);
+            let pending = partial
+                .pending
+                .iter()
+                .map(|rel| {
+                    if rel.is_empty() {
+                        root_path.clone()
+                    } else {
+                        root_path.join(rel)
                     }
-                };
+                })
+                .collect::<Vec<_>>();
+            (partial.started_at, pending, SPLIT_DEPTH)
+        }
+        None => (Local::now().timestamp(), vec![root_path.clone()], 0),
+    };
 
-                let path = entry.path();
-                if path == root_path.as_path() {
-                    return WalkState::Continue;
-                }
-
-                let is_dir = entry
-                    .file_type()
-                    .map(|ft| ft.is_dir())
-                    .unwrap_or(false);
-                if should_skip(path, is_dir, &root_path, &matcher) {
-                    return if is_dir {
-                        WalkState::Skip
-                    } else {
-                        WalkState::Continue
-                    };
-                }
-
-                let meta = match std::fs::symlink_metadata(path) {
-                    Ok(m) => m,
-                    Err(err) => {
-                        let _ = tx.send(ScanEvent::MetadataError {
-                            path: path_to_string(path),
-                            error: err.to_string(),
-                            permission_denied: err.kind()
-                                == std::io::ErrorKind::PermissionDenied,
-                        });
-                        return WalkState::Continue;
-                    }
-                };
-
-                let rel = match path.strip_prefix(&root_path) {
-                    Ok(p) => p,
-                    Err(_) => {
-                        let _ = tx.send(ScanEvent::RelPathError);
-                        return WalkState::Continue;
-                    }
-                };
-
-                let is_symlink = entry.path_is_symlink();
-                let size = if is_dir { 0 } else { meta.len() as i64 };
-                let mtime = meta
-                    .modified()
-                    .unwrap_or(SystemTime::UNIX_EPOCH)
-                    .duration_since(UNIX_EPOCH)
-                    .unwrap_or_default()
-                    .as_secs() as i64;
-                let ext = rel
-                    .extension()
-                    .and_then(|s| s.to_str())
-                    .map(|s| s.to_lowercase());
-
-                let abs_path = path_to_string(path);
-                let rel_path = path_to_string(rel);
-
-                let _ = tx.send(ScanEvent::File(ScannedFile {
-                    rel_path,
-                    abs_path,
-                    is_dir,
-                    is_symlink,
-                    size,
-                    mtime,
-                    ext,
-                }));
-
-                WalkState::Continue
-            })
-        });
-    });
+    let settings = scan_settings(options);
+    let record = store
+        .data
+        .roots
+        .iter()
+        .find(|r| r.id == root_id && r.path == root)
+        .and_then(|r| r.scan);
+    // Editing an ignore file changes what a directory yields without
+    // touching its mtime, so roots that honour them are always listed.
+    let ignore_files = options.ignore_files.any();
+    let shortcut = match record {
+        Some(record)
+            if !full
+                && !ignore_files
+                && record.settings == settings
+                && started_at - record.verified_at < VERIFY_INTERVAL_DAYS * 86400 =>
+        {
+            let root_dev = if one_filesystem { root_dev } else { None };
+            Some(DirShortcut::new(
+                &store.data,
+                root_id,
+                record.started_at,
+                root_dev,
+            ))
+        }
+        _ => None,
+    };
+    if shortcut.is_none() && !full && !ignore_files && record.is_some() {
+        tracing::info!("listing every directory under {}", root);
+    }
 
-    let mut seen = 0;
-    let mut updated = 0;
-    let mut skipped = 0;
+    let mut stats = IndexStats::default();
     let mut permission_skips = 0;
     let mut walk_errors = 0;
     let mut first_walk_error: Option<String> = None;
 
-    for event in rx {
-        match event {
-            ScanEvent::File(file) => {
+    while !pending.is_empty() {
+        let listing = level < SPLIT_DEPTH;
+        let starts = if listing {
+            level += 1;
+            std::mem::take(&mut pending)
+        } else {
+            let rest = pending.split_off(pending.len().min(WALK_BATCH));
+            std::mem::replace(&mut pending, rest)
+        };
+        // Subdirectories of skipped directories, and (while listing) the
+        // directories of the level below, are walked in later rounds.
+        let mut walk = Vec::new();
+        let (tx, rx) = mpsc::channel();
+        let handle = spawn_walker(
+            starts.clone(),
+            Walk {
+                root_path: root_path.clone(),
+                matcher: matcher.clone(),
+                one_filesystem,
+                follow_links: options.follow_symlinks,
+                listing,
+                trusted: shortcut.as_ref().map(|s| s.trusted.clone()),
+                stop: run.stop.clone(),
+            },
+            tx,
+        );
+
+        for event in rx {
+            let (file, carried) = match event {
+                ScanEvent::File(file) => (file, Vec::new()),
+                ScanEvent::TrustedDir(dir) => {
+                    let carried = match &shortcut {
+                        Some(shortcut) => {
+                            shortcut.expand(&store.data, &root_path, &dir.rel_path, &mut walk)
+                        }
+                        None => Vec::new(),
+                    };
+                    (dir, carried)
+                }
+                ScanEvent::Unlisted(dir) => {
+                    // Reported when it is walked, unless the walk would not
+                    // descend into another filesystem.
+                    if !(one_filesystem && dir.dev.is_some() && dir.dev != root_dev) {
+                        walk.push(PathBuf::from(&dir.abs_path));
+                        continue;
+                    }
+                    (dir, Vec::new())
+                }
+                ScanEvent::WalkError(err) => {
+                    walk_errors += 1;
+                    stats.skipped += 1;
+                    if first_walk_error.is_none() {
+                        first_walk_error = Some(err.clone());
+                    }
+                    tracing::debug!("walk error: {}", err);
+                    continue;
+                }
+                ScanEvent::MetadataError {
+                    path,
+                    error,
+                    permission_denied,
+                } => {
+                    stats.skipped += 1;
+                    if permission_denied {
+                        permission_skips += 1;
+                    } else {
+                        tracing::warn!("metadata error: {} ({})", path, error);
+                    }
+                    continue;
+                }
+                ScanEvent::RelPathError => {
+                    stats.skipped += 1;
+                    continue;
+                }
+            };
+            for file in std::iter::once(file).chain(carried) {
                 if let Some(obs) = observer {
                     unsafe {
                         (&mut *obs.ptr).on_file_scanned(root, &file);
@@ -444,46 +1453,46 @@ fn scan_root(
                         }
                     }
                 }
-                merger.apply(store, file);
-                seen += 1;
-                updated += 1;
-                if seen % 5000 == 0 {
-                    progress.set_message(format!(
-                        "{} {}k (u{} s{})",
-                        root_label,
-                        seen / 1000,
-                        updated / 1000,
-                        skipped
-                    ));
+                stats.count(merger.apply(&mut store.data, file));
+                if stats.seen % 5000 == 0 {
+                    progress.set_message(root_progress(root_label, &stats));
                 }
             }
-            ScanEvent::WalkError(err) => {
-                walk_errors += 1;
-                skipped += 1;
-                if first_walk_error.is_none() {
-                    first_walk_error = Some(err.clone());
-                }
-                tracing::debug!("walk error: {}", err);
-            }
-            ScanEvent::MetadataError {
-                path,
-                error,
-                permission_denied,
-            } => {
-                skipped += 1;
-                if permission_denied {
-                    permission_skips += 1;
-                } else {
-                    tracing::warn!("metadata error: {} ({})", path, error);
-                }
-            }
-            ScanEvent::RelPathError => {
-                skipped += 1;
-            }
         }
-    }
 
-    handle.join().expect("indexer worker panicked");
+        handle.join().expect("indexer worker panicked");
+
+        if run.stopped() {
+            // The interrupted round is walked again in full on resume.
+            pending.splice(0..0, starts);
+            let partial = checkpoint_root(
+                store,
+                &mut merger,
+                root_id,
+                started_at,
+                &root_path,
+                &pending,
+            );
+            return Ok(RootScanResult {
+                stats,
+                duration: started.elapsed(),
+                root_missing: false,
+                interrupted: Some(partial),
+            });
+        }
+        pending.extend(walk);
+        if run.due() && !pending.is_empty() {
+            let partial = checkpoint_root(
+                store,
+                &mut merger,
+                root_id,
+                started_at,
+                &root_path,
+                &pending,
+            );
+            run.save(store, Some(partial))?;
+        }
+    }
 
     if walk_errors > 0 {
         if let Some(sample) = &first_walk_error {
@@ -502,16 +1511,25 @@ fn scan_root(
         ));
     }
 
-    progress.set_message(format!(
-        "{} {}k (u{} s{})",
-        root_label,
-        seen / 1000,
-        updated / 1000,
-        skipped
-    ));
+    progress.set_message(root_progress(root_label, &stats));
     progress.disable_steady_tick();
 
-    let deleted = merger.finalize(store);
+    let (deleted, moved) = merger.finalize(&mut store.data);
+    // A moved entry was first seen as new at its new path.
+    stats.new = stats.new.saturating_sub(moved);
+    stats.deleted = deleted;
+    stats.moved = moved;
+    let verified_at = match (&shortcut, record) {
+        (Some(_), Some(record)) => record.verified_at,
+        _ => started_at,
+    };
+    if let Some(entry) = store.data.roots.iter_mut().find(|r| r.id == root_id) {
+        entry.scan = Some(ScanRecord {
+            started_at,
+            verified_at,
+            settings,
+        });
+    }
     if let Some(obs) = observer {
         unsafe {
             (&mut *obs.ptr).on_root_finished(root);
@@ -519,25 +1537,404 @@ fn scan_root(
     }
 
     Ok(RootScanResult {
-        stats: IndexStats {
-            seen,
-            updated,
-            deleted,
-            skipped,
-        },
+        stats,
         duration: started.elapsed(),
         root_missing: false,
+        interrupted: None,
+    })
+}
+
+/// Journals what the merge has settled so far and describes the rest of the
+/// root's scan for a checkpoint. Nothing is marked deleted: entries not seen
+/// yet may still be under `pending`.
+fn checkpoint_root(
+    store: &mut Store,
+    merger: &mut RootMerge,
+    root_id: i64,
+    started_at: i64,
+    root_path: &Path,
+    pending: &[PathBuf],
+) -> PartialRoot {
+    let journal = merger.take_seen_changes(&store.data);
+    store.data.changes.extend(journal);
+    let pending = pending
+        .iter()
+        .map(|dir| path_to_string(dir.strip_prefix(root_path).unwrap_or(dir)))
+        .collect();
+    PartialRoot {
+        root_id,
+        started_at,
+        pending,
+    }
+}
+
+/// How `spawn_walker` walks a round of a root's directories.
+struct Walk {
+    root_path: PathBuf,
+    matcher: Arc<IgnoreMatcher>,
+    one_filesystem: bool,
+    follow_links: bool,
+    /// Only visit the starts' direct children, reporting their
+    /// subdirectories as `Unlisted`.
+    listing: bool,
+    /// Directories reported as `TrustedDir` and not descended into when
+    /// their mtime and inode match.
+    trusted: Option<Arc<TrustedDirs>>,
+    /// Stops the walk early once set.
+    stop: Arc<AtomicBool>,
+}
+
+/// Walks `starts` on a worker thread as `walk` describes, sending what it
+/// finds to `tx`.
+fn spawn_walker(
+    starts: Vec<PathBuf>,
+    walk: Walk,
+    tx: mpsc::Sender<ScanEvent>,
+) -> thread::JoinHandle<()> {
+    let Walk {
+        root_path,
+        matcher,
+        one_filesystem,
+        follow_links,
+        listing,
+        trusted,
+        stop,
+    } = walk;
+    thread::spawn(move || {
+        let mut builder = WalkBuilder::new(&starts[0]);
+        for start in &starts[1..] {
+            builder.add(start);
+        }
+        ignore_files::configure(&mut builder, matcher.ignore_files);
+        builder
+            .follow_links(follow_links)
+            .same_file_system(one_filesystem)
+            .max_depth(listing.then_some(1));
+        let walker = builder.build_parallel();
+        walker.run(move || {
+            let tx = tx.clone();
+            let matcher = matcher.clone();
+            let root_path = root_path.clone();
+            let trusted = trusted.clone();
+            let stop = stop.clone();
+            Box::new(move |entry| {
+                if stop.load(Ordering::Relaxed) {
+                    return WalkState::Quit;
+                }
+                let entry = match entry {
+                    Ok(e) => e,
+                    Err(err) => {
+                        let _ = tx.send(ScanEvent::WalkError(err.to_string()));
+                        return WalkState::Continue;
+                    }
+                };
+
+                let path = entry.path();
+                if path == root_path.as_path() {
+                    return WalkState::Continue;
+                }
+
+                let is_dir = entry
+                    .file_type()
+                    .map(|ft| ft.is_dir())
+                    .unwrap_or(false);
+                if should_skip(path, is_dir, &root_path, &matcher) {
+                    return if is_dir {
+                        WalkState::Skip
+                    } else {
+                        WalkState::Continue
+                    };
+                }
+
+                let meta = match metadata(path, follow_links) {
+                    Ok(m) => m,
+                    Err(err) => {
+                        let _ = tx.send(ScanEvent::MetadataError {
+                            path: path_to_string(path),
+                            error: err.to_string(),
+                            permission_denied: err.kind()
+                                == std::io::ErrorKind::PermissionDenied,
+                        });
+                        return WalkState::Continue;
+                    }
+                };
+
+                let rel = match path.strip_prefix(&root_path) {
+                    Ok(p) => p,
+                    Err(_) => {
+                        let _ = tx.send(ScanEvent::RelPathError);
+                        return WalkState::Continue;
+                    }
+                };
+
+                let is_symlink = entry.path_is_symlink();
+                let file = scanned_file(path, rel, &meta, is_dir, is_symlink);
+                if is_dir
+                    && let Some(trusted) = &trusted
+                    && let Some(&(mtime, ino)) = trusted.get(&file.rel_path)
+                    && mtime == file.mtime
+                    && (ino.is_none() || file.ino.is_none() || ino == file.ino)
+                {
+                    let _ = tx.send(ScanEvent::TrustedDir(file));
+                    return WalkState::Skip;
+                }
+                if is_dir && at_max_depth(path, &root_path, &matcher) {
+                    let _ = tx.send(ScanEvent::File(file));
+                    return WalkState::Skip;
+                }
+                if listing && is_dir && entry.depth() > 0 {
+                    let _ = tx.send(ScanEvent::Unlisted(file));
+                    return WalkState::Skip;
+                }
+                let _ = tx.send(ScanEvent::File(file));
+
+                WalkState::Continue
+            })
+        });
     })
 }
 
-fn build_matcher(cfg: &Config, root: &str) -> Result<IgnoreMatcher> {
+/// What indexing a root would pick up; see `preview_root`.
+#[derive(Debug, Default)]
+pub struct RootPreview {
+    pub indexed: Tally,
+    /// What would be indexed below each top-level entry of the root, most
+    /// entries first.
+    pub subtrees: Vec<(String, Tally)>,
+    /// What each rule leaves out, including everything below an excluded
+    /// directory; most entries first.
+    pub excluded: Vec<(Exclusion, Tally)>,
+    /// Entries whose metadata could not be read.
+    pub skipped: u64,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Tally {
+    pub entries: u64,
+    /// Sizes of the files among the entries.
+    pub bytes: u64,
+}
+
+impl Tally {
+    fn add(&mut self, other: Tally) {
+        self.entries += other.entries;
+        self.bytes += other.bytes;
+    }
+}
+
+enum PreviewEvent {
+    Indexed { top: String, size: u64 },
+    Excluded { rule: Exclusion, tally: Tally },
+    Skipped,
+}
+
+/// Walks `root` the way an index run with `options` would and counts what it
+/// would index and what each rule leaves out, without touching a store.
+/// Every directory is listed, as in a `--full` run.
+pub fn preview_root(
+    options: &RootOptions,
+    root: &str,
+    progress: ProgressBar,
+) -> Result<RootPreview> {
+    let root_path = normalize_path_allow_missing(root)?;
+    let matcher = Arc::new(build_matcher(options, root)?);
+    let follow_links = options.follow_symlinks;
+    let one_filesystem = options.one_filesystem;
+    let root_label = root_path
+        .file_name()
+        .and_then(|s| s.to_str())
+        .unwrap_or(root)
+        .to_string();
+
+    let style = ProgressStyle::with_template("{spinner:.green} {msg}")
+        .unwrap_or_else(|_| ProgressStyle::default_spinner());
+    progress.set_style(style);
+    progress.set_message(format!("Walking {}", root_label));
+    progress.enable_steady_tick(Duration::from_millis(120));
+
+    let (tx, rx) = mpsc::channel();
+    let walk_root = root_path.clone();
+    let handle = thread::spawn(move || {
+        let mut builder = WalkBuilder::new(&walk_root);
+        // Ignore files are matched per entry instead, so that what they
+        // leave out can be counted.
+        ignore_files::configure(&mut builder, IgnoreFiles::default());
+        builder
+            .follow_links(follow_links)
+            .same_file_system(one_filesystem);
+        builder.build_parallel().run(move || {
+            let tx = tx.clone();
+            let matcher = matcher.clone();
+            let root_path = walk_root.clone();
+            Box::new(move |entry| {
+                let Ok(entry) = entry else {
+                    let _ = tx.send(PreviewEvent::Skipped);
+                    return WalkState::Continue;
+                };
+                let path = entry.path();
+                if path == root_path.as_path() {
+                    return WalkState::Continue;
+                }
+                let is_dir = entry.file_type().map(|ft| ft.is_dir()).unwrap_or(false);
+                let size = |meta: &std::fs::Metadata| if is_dir { 0 } else { meta.len() };
+
+                if let Some(rule) = exclusion_with(path, is_dir, &root_path, &matcher) {
+                    let mut tally = Tally {
+                        entries: 1,
+                        bytes: metadata(path, follow_links).map_or(0, |m| size(&m)),
+                    };
+                    if is_dir {
+                        tally.add(subtree_tally(path, one_filesystem, follow_links));
+                    }
+                    let _ = tx.send(PreviewEvent::Excluded { rule, tally });
+                    return if is_dir {
+                        WalkState::Skip
+                    } else {
+                        WalkState::Continue
+                    };
+                }
+
+                let Ok(meta) = metadata(path, follow_links) else {
+                    let _ = tx.send(PreviewEvent::Skipped);
+                    return WalkState::Continue;
+                };
+                let top = path
+                    .strip_prefix(&root_path)
+                    .ok()
+                    .and_then(|rel| rel.components().next())
+                    .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                let _ = tx.send(PreviewEvent::Indexed {
+                    top,
+                    size: size(&meta),
+                });
+                if is_dir && at_max_depth(path, &root_path, &matcher) {
+                    let tally = subtree_tally(path, one_filesystem, follow_links);
+                    if let Some(max) = matcher.max_depth
+                        && tally.entries > 0
+                    {
+                        let rule = Exclusion::TooDeep(max);
+                        let _ = tx.send(PreviewEvent::Excluded { rule, tally });
+                    }
+                    return WalkState::Skip;
+                }
+                WalkState::Continue
+            })
+        });
+    });
+
+    let mut preview = RootPreview::default();
+    let mut subtrees: HashMap<String, Tally> = HashMap::new();
+    let mut excluded: HashMap<Exclusion, Tally> = HashMap::new();
+    for event in rx {
+        match event {
+            PreviewEvent::Indexed { top, size } => {
+                let tally = Tally {
+                    entries: 1,
+                    bytes: size,
+                };
+                preview.indexed.add(tally);
+                subtrees.entry(top).or_default().add(tally);
+                if preview.indexed.entries % 5000 == 0 {
+                    progress.set_message(format!(
+                        "Walking {} {}k",
+                        root_label,
+                        preview.indexed.entries / 1000
+                    ));
+                }
+            }
+            PreviewEvent::Excluded { rule, tally } => excluded.entry(rule).or_default().add(tally),
+            PreviewEvent::Skipped => preview.skipped += 1,
+        }
+    }
+    handle.join().expect("preview worker panicked");
+    progress.disable_steady_tick();
+
+    preview.subtrees = subtrees.into_iter().collect();
+    preview
+        .subtrees
+        .sort_by(|a, b| b.1.entries.cmp(&a.1.entries).then_with(|| a.0.cmp(&b.0)));
+    preview.excluded = excluded.into_iter().collect();
+    preview.excluded.sort_by(|a, b| {
+        b.1.entries
+            .cmp(&a.1.entries)
+            .then_with(|| a.0.to_string().cmp(&b.0.to_string()))
+    });
+    Ok(preview)
+}
+
+/// Entries below `dir` and the sizes of its files, on `dir`'s filesystem.
+fn subtree_tally(dir: &Path, one_filesystem: bool, follow_links: bool) -> Tally {
+    let mut builder = WalkBuilder::new(dir);
+    builder
+        .standard_filters(false)
+        .same_file_system(one_filesystem)
+        .follow_links(follow_links);
+    let mut tally = Tally::default();
+    for entry in builder.build().flatten().filter(|e| e.depth() > 0) {
+        tally.entries += 1;
+        if entry.file_type().is_some_and(|ft| ft.is_file()) {
+            tally.bytes += entry.metadata().map_or(0, |m| m.len());
+        }
+    }
+    tally
+}
+
+fn root_progress(label: &str, stats: &IndexStats) -> String {
+    format!(
+        "{} {}k (n{} c{} s{})",
+        label,
+        stats.seen / 1000,
+        stats.new,
+        stats.changed,
+        stats.skipped
+    )
+}
+
+fn scanned_file(
+    path: &Path,
+    rel: &Path,
+    meta: &std::fs::Metadata,
+    is_dir: bool,
+    is_symlink: bool,
+) -> ScannedFile {
+    let size = if is_dir { 0 } else { meta.len() as i64 };
+    let mtime = meta
+        .modified()
+        .unwrap_or(SystemTime::UNIX_EPOCH)
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64;
+    let ext = rel
+        .extension()
+        .and_then(|s| s.to_str())
+        .map(|s| s.to_lowercase());
+    let (dev, ino) = match file_identity(meta) {
+        Some((dev, ino)) => (Some(dev), Some(ino)),
+        None => (None, None),
+    };
+
+    ScannedFile {
+        rel_path: path_to_string(rel),
+        abs_path: path_to_string(path),
+        is_dir,
+        is_symlink,
+        size,
+        mtime,
+        ext,
+        dev,
+        ino,
+    }
+}
+
+fn build_matcher(options: &RootOptions, root: &str) -> Result<IgnoreMatcher> {
     let mut builder = GitignoreBuilder::new(root);
     let mut abs_excludes = Vec::new();
 
-    for ex in &cfg.excludes {
+    for ex in &options.excludes {
         if ex.starts_with("~/") || ex.starts_with('/') {
             let abs = normalize_path_allow_missing(ex)?;
-            abs_excludes.push(abs);
+            abs_excludes.push((abs, ex.clone()));
         } else {
             builder.add_line(None, ex)?;
         }
@@ -547,18 +1944,101 @@ fn build_matcher(cfg: &Config, root: &str) -> Result<IgnoreMatcher> {
     Ok(IgnoreMatcher {
         gitignore,
         abs_excludes,
-        include_hidden: cfg.include_hidden,
+        include_hidden: options.include_hidden,
+        max_depth: options.max_depth,
+        ignore_files: options.ignore_files,
+        ignore_cache: IgnoreCache::default(),
     })
 }
 
+/// Why a walk leaves a path out; see `exclusion`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Exclusion {
+    /// A path component starts with a dot and `include_hidden` is off.
+    Hidden,
+    /// Deeper below the root than its `max_depth`.
+    TooDeep(usize),
+    /// An absolute entry of `excludes`, as configured.
+    AbsoluteExclude(String),
+    /// A gitignore-style pattern of `excludes`.
+    Pattern(String),
+    IgnoreFile(IgnoreHit),
+}
+
+/// The rule that makes a walk of `root` with `options` leave out `path`, if
+/// any, decided by the same matcher and ignore files the walker uses.
+pub fn exclusion(
+    options: &RootOptions,
+    root: &str,
+    path: &Path,
+    is_dir: bool,
+) -> Result<Option<Exclusion>> {
+    let matcher = build_matcher(options, root)?;
+    let root_path = normalize_path_allow_missing(root)?;
+    Ok(exclusion_with(path, is_dir, &root_path, &matcher))
+}
+
+fn exclusion_with(
+    path: &Path,
+    is_dir: bool,
+    root: &Path,
+    matcher: &IgnoreMatcher,
+) -> Option<Exclusion> {
+    if let Some(rule) = skip_rule(path, is_dir, root, matcher) {
+        return Some(match rule {
+            SkipRule::Hidden => Exclusion::Hidden,
+            SkipRule::TooDeep(max) => Exclusion::TooDeep(max),
+            SkipRule::AbsoluteExclude(ex) => Exclusion::AbsoluteExclude(ex.to_string()),
+            SkipRule::Pattern(pattern) => Exclusion::Pattern(pattern.to_string()),
+        });
+    }
+    matcher
+        .ignore_cache
+        .excluded_by(path, is_dir, matcher.ignore_files)
+        .map(Exclusion::IgnoreFile)
+}
+
+impl std::fmt::Display for Exclusion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Exclusion::Hidden => write!(f, "hidden path"),
+            Exclusion::TooDeep(max) => write!(f, "deeper than max_depth = {}", max),
+            Exclusion::AbsoluteExclude(ex) => write!(f, "exclude `{}`", ex),
+            Exclusion::Pattern(pattern) => write!(f, "exclude pattern `{}`", pattern),
+            Exclusion::IgnoreFile(hit) => write!(f, "ignore pattern `{}`", hit.pattern),
+        }
+    }
+}
+
+enum SkipRule<'a> {
+    Hidden,
+    TooDeep(usize),
+    AbsoluteExclude(&'a str),
+    Pattern(&'a str),
+}
+
 fn should_skip(path: &Path, is_dir: bool, root: &Path, matcher: &IgnoreMatcher) -> bool {
+    skip_rule(path, is_dir, root, matcher).is_some()
+}
+
+fn skip_rule<'a>(
+    path: &Path,
+    is_dir: bool,
+    root: &Path,
+    matcher: &'a IgnoreMatcher,
+) -> Option<SkipRule<'a>> {
     if !matcher.include_hidden && is_hidden(path, root) {
-        return true;
+        return Some(SkipRule::Hidden);
+    }
+    if let Some(max) = matcher.max_depth
+        && depth(path, root) > max
+    {
+        return Some(SkipRule::TooDeep(max));
     }
 
-    for abs in &matcher.abs_excludes {
+    for (abs, ex) in &matcher.abs_excludes {
         if path == abs || path.starts_with(abs) {
-            return true;
+            return Some(SkipRule::AbsoluteExclude(ex));
         }
     }
 
@@ -566,15 +2046,30 @@ fn should_skip(path: &Path, is_dir: bool, root: &Path, matcher: &IgnoreMatcher)
         Ok(p) => p,
         Err(_) => path,
     };
-    if matcher
-        .gitignore
-        .matched_path_or_any_parents(rel, is_dir)
-        .is_ignore()
-    {
-        return true;
+    match matcher.gitignore.matched_path_or_any_parents(rel, is_dir) {
+        ignore::Match::Ignore(glob) => Some(SkipRule::Pattern(glob.original())),
+        _ => None,
     }
+}
+
+/// Directories at `max_depth` are indexed but not descended into.
+fn at_max_depth(path: &Path, root: &Path, matcher: &IgnoreMatcher) -> bool {
+    matcher
+        .max_depth
+        .is_some_and(|max| depth(path, root) >= max)
+}
 
-    false
+fn depth(path: &Path, root: &Path) -> usize {
+    path.strip_prefix(root).unwrap_or(path).components().count()
+}
+
+/// Metadata of `path`, or of its target when symlinks are followed.
+fn metadata(path: &Path, follow_links: bool) -> std::io::Result<std::fs::Metadata> {
+    if follow_links {
+        std::fs::metadata(path)
+    } else {
+        std::fs::symlink_metadata(path)
+    }
 }
 
 fn is_hidden(path: &Path, root: &Path) -> bool {
@@ -588,7 +2083,7 @@ fn is_hidden(path: &Path, root: &Path) -> bool {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::{Config, OutputMode};
+    use crate::config::Config;
     use crate::store;
     use std::fs;
     use std::time::{SystemTime, UNIX_EPOCH};
@@ -634,12 +2129,9 @@ mod tests {
         let hidden_canon = root_canon.join(".hidden/secret.txt");
 
         let cfg = Config {
-            version: 1,
-            output: OutputMode::Plain,
-            include_hidden: false,
-            one_filesystem: true,
-            roots: vec![path_to_string(&root_canon)],
+            roots: vec![path_to_string(&root_canon).into()],
             excludes: vec!["**/node_modules/**".to_string()],
+            ..Config::default()
         };
 
         let store_path = dir.join("catalog.bin");
@@ -673,4 +2165,596 @@ mod tests {
             .unwrap();
         assert_eq!(status, "deleted");
     }
+
+    #[test]
+    fn indexer_preserves_ids_across_moves() {
+        let dir = temp_dir("indexer_moves");
+        let root = fs::canonicalize(&dir).unwrap().join("root");
+        write_file(&root.join("a/report.pdf"), "report");
+        write_file(&root.join("docs/notes/todo.txt"), "todo");
+
+        let cfg = Config {
+            roots: vec![path_to_string(&root).into()],
+            excludes: vec![],
+            ..Config::default()
+        };
+        let mut store = store::Store::load(&dir.join("catalog.bin")).unwrap();
+        run(&mut store, &cfg, false, false).unwrap();
+
+        let id_of = |store: &store::Store, path: &Path| {
+            let path = path_to_string(path);
+            store
+                .data
+                .files
+                .iter()
+                .find(|f| f.abs_path == path)
+                .map(|f| f.id)
+        };
+        let report_id = id_of(&store, &root.join("a/report.pdf")).unwrap();
+        let todo_id = id_of(&store, &root.join("docs/notes/todo.txt")).unwrap();
+        let total = store.data.files.len();
+
+        let docs = store.data.files.iter().find(|f| f.rel_path == "docs");
+        let docs_mtime = docs.unwrap().mtime;
+
+        fs::rename(root.join("a/report.pdf"), root.join("report-final.pdf")).unwrap();
+        fs::rename(root.join("docs"), root.join("archive")).unwrap();
+        let touched = UNIX_EPOCH + Duration::from_secs(1_000_000);
+        fs::File::open(root.join("archive"))
+            .unwrap()
+            .set_modified(touched)
+            .unwrap();
+        let stats = run(&mut store, &cfg, true, false).unwrap();
+
+        assert_eq!(stats.moved, 4);
+        assert_eq!(stats.deleted, 0);
+        assert_eq!(store.data.files.len(), total);
+        assert_eq!(id_of(&store, &root.join("report-final.pdf")), Some(report_id));
+        assert_eq!(id_of(&store, &root.join("archive/notes/todo.txt")), Some(todo_id));
+        let moved = store.data.files.iter().find(|f| f.id == report_id).unwrap();
+        assert_eq!(moved.status, "active");
+        assert_eq!(
+            moved.prev_path.as_deref(),
+            Some(path_to_string(&root.join("a/report.pdf")).as_str())
+        );
+
+        // The journal keeps what the directory looked like before the move.
+        let archive = path_to_string(&root.join("archive"));
+        let change = store.data.changes.iter().find(|c| c.path == archive);
+        let change = change.unwrap();
+        assert_eq!(change.kind, "moved");
+        assert_eq!(change.old_mtime, Some(docs_mtime));
+        assert_eq!(change.new_mtime, Some(1_000_000));
+    }
+
+    #[test]
+    fn reused_directory_inodes_are_not_moves() {
+        let mut store = StoreData::new();
+        store.roots.push(RootEntry {
+            id: 1,
+            path: "/r".to_string(),
+            added_at: String::new(),
+            preset_name: None,
+            last_indexed_at: None,
+            one_filesystem: false,
+            volume: None,
+            scan: None,
+        });
+        let scanned = |rel: &str, is_dir: bool, ino: u64| ScannedFile {
+            rel_path: rel.to_string(),
+            abs_path: format!("/r/{}", rel),
+            is_dir,
+            is_symlink: false,
+            size: if is_dir { 0 } else { 1 },
+            mtime: 1,
+            ext: None,
+            dev: Some(1),
+            ino: Some(ino),
+        };
+        let index = |store: &mut StoreData, run_id, files: Vec<ScannedFile>| {
+            let mut merger = RootMerge::new(store, 1, run_id);
+            for file in files {
+                merger.apply(store, file);
+            }
+            merger.finalize(store)
+        };
+        index(
+            &mut store,
+            1,
+            vec![
+                scanned("old", true, 10),
+                scanned("old/inner.txt", false, 11),
+            ],
+        );
+        let id_of = |store: &StoreData, rel: &str| {
+            store
+                .files
+                .iter()
+                .find(|f| f.rel_path == rel && f.status == "active")
+                .map(|f| f.id)
+        };
+        let old_id = id_of(&store, "old").unwrap();
+
+        // A new directory that got the deleted one's inode.
+        let (deleted, moved) = index(
+            &mut store,
+            2,
+            vec![scanned("new", true, 10), scanned("new/file.rs", false, 12)],
+        );
+        assert_eq!((deleted, moved), (2, 0));
+        assert_ne!(id_of(&store, "new"), Some(old_id));
+        assert!(!store.changes.iter().any(|c| c.kind == "moved"));
+
+        // A renamed directory keeps its children.
+        let new_id = id_of(&store, "new").unwrap();
+        let (deleted, moved) = index(
+            &mut store,
+            3,
+            vec![scanned("src", true, 10), scanned("src/file.rs", false, 12)],
+        );
+        assert_eq!((deleted, moved), (0, 2));
+        assert_eq!(id_of(&store, "src"), Some(new_id));
+    }
+
+    #[test]
+    fn indexer_journals_changes_between_runs() {
+        let dir = temp_dir("indexer_journal");
+        let root = fs::canonicalize(&dir).unwrap().join("root");
+        write_file(&root.join("keep.txt"), "keep");
+        write_file(&root.join("edit.txt"), "edit");
+        write_file(&root.join("sub/gone.txt"), "gone");
+
+        let cfg = Config {
+            roots: vec![path_to_string(&root).into()],
+            excludes: vec![],
+            ..Config::default()
+        };
+        let mut store = store::Store::load(&dir.join("catalog.bin")).unwrap();
+        run(&mut store, &cfg, false, false).unwrap();
+        assert!(store.data.changes.is_empty(), "first index is not journaled");
+
+        write_file(&root.join("edit.txt"), "edited, and longer");
+        fs::remove_file(root.join("sub/gone.txt")).unwrap();
+        write_file(&root.join("sub/new.txt"), "a brand new file");
+        run(&mut store, &cfg, false, false).unwrap();
+
+        let prefix = path_to_string(&root);
+        let files = |store: &store::Store| {
+            let mut out: Vec<(String, String)> = store
+                .data
+                .changes
+                .iter()
+                .filter(|c| !c.is_dir)
+                .map(|c| (c.kind.clone(), c.path.trim_start_matches(&prefix).to_string()))
+                .collect();
+            out.sort();
+            out
+        };
+        assert_eq!(
+            files(&store),
+            vec![
+                ("created".to_string(), "/sub/new.txt".to_string()),
+                ("deleted".to_string(), "/sub/gone.txt".to_string()),
+                ("modified".to_string(), "/edit.txt".to_string()),
+            ]
+        );
+        let edit = store.data.changes.iter().find(|c| c.kind == "modified").unwrap();
+        assert_eq!((edit.old_size, edit.new_size), (Some(4), Some(18)));
+
+        // A full rescan of an unchanged tree journals nothing new.
+        let before = store.data.changes.len();
+        run(&mut store, &cfg, true, false).unwrap();
+        assert_eq!(store.data.changes.len(), before);
+    }
+
+    #[test]
+    fn indexer_counts_unchanged_and_resurrected_entries() {
+        let dir = temp_dir("indexer_stats");
+        let root = fs::canonicalize(&dir).unwrap().join("root");
+        write_file(&root.join("a.txt"), "a");
+        write_file(&root.join("sub/b.txt"), "b");
+
+        let cfg = Config {
+            roots: vec![path_to_string(&root).into()],
+            excludes: vec![],
+            ..Config::default()
+        };
+        let mut store = store::Store::load(&dir.join("catalog.bin")).unwrap();
+        let first = run(&mut store, &cfg, false, false).unwrap();
+        assert_eq!((first.seen, first.new, first.unchanged), (3, 3, 0));
+
+        for full in [false, true] {
+            let again = run(&mut store, &cfg, full, false).unwrap();
+            assert_eq!(again.unchanged, 3, "full={}", full);
+            assert_eq!(again.updated() + again.deleted, 0, "full={}", full);
+        }
+
+        let deleted_at = |store: &store::Store| {
+            let file = store.data.files.iter().find(|f| f.rel_path == "sub/b.txt");
+            file.unwrap().deleted_at
+        };
+        let kept = fs::read(root.join("sub/b.txt")).unwrap();
+        fs::remove_file(root.join("sub/b.txt")).unwrap();
+        let before = Local::now().timestamp();
+        let gone = run(&mut store, &cfg, false, false).unwrap();
+        assert_eq!(gone.deleted, 1);
+        assert!(deleted_at(&store).is_some_and(|at| at >= before));
+
+        fs::write(root.join("sub/b.txt"), kept).unwrap();
+        let back = run(&mut store, &cfg, false, false).unwrap();
+        assert_eq!(back.resurrected, 1);
+        assert_eq!(back.new, 0);
+        assert_eq!(deleted_at(&store), None);
+    }
+
+    #[test]
+    fn incremental_index_skips_directories_with_unchanged_mtime() {
+        let dir = temp_dir("indexer_shortcut");
+        let root = fs::canonicalize(&dir).unwrap().join("root");
+        write_file(&root.join("docs/notes.txt"), "draft");
+        write_file(&root.join("docs/deep/x.txt"), "x");
+        let set_dir_mtime = |path: &Path, secs_ago: u64| {
+            let when = SystemTime::now() - Duration::from_secs(secs_ago);
+            fs::File::open(path).unwrap().set_modified(when).unwrap();
+        };
+        set_dir_mtime(&root.join("docs"), 3600);
+        set_dir_mtime(&root.join("docs/deep"), 3600);
+
+        let cfg = Config {
+            roots: vec![path_to_string(&root).into()],
+            excludes: vec![],
+            ..Config::default()
+        };
+        let mut store = store::Store::load(&dir.join("catalog.bin")).unwrap();
+        run(&mut store, &cfg, false, false).unwrap();
+
+        // An in-place edit leaves `docs` alone; a new file bumps `docs/deep`.
+        fs::write(root.join("docs/notes.txt"), "final version").unwrap();
+        set_dir_mtime(&root.join("docs"), 3600);
+        write_file(&root.join("docs/deep/y.txt"), "y");
+        let quick = run(&mut store, &cfg, false, false).unwrap();
+        // Only `docs/deep` itself counts as changed.
+        assert_eq!((quick.seen, quick.new, quick.changed), (5, 1, 1));
+        let notes = store
+            .data
+            .files
+            .iter()
+            .find(|f| f.rel_path == "docs/notes.txt")
+            .unwrap();
+        assert_eq!(notes.size, 5);
+        assert_eq!(notes.status, "active");
+        assert_eq!(notes.last_seen_run, store.data.last_run_id);
+
+        let full = run(&mut store, &cfg, true, false).unwrap();
+        assert_eq!((full.new, full.changed), (0, 1));
+    }
+
+    /// Stops the run at the first file below the levels listed up front.
+    struct StopBelowSplit(Arc<AtomicBool>);
+
+    impl ScanObserver for StopBelowSplit {
+        fn on_file_scanned(&mut self, _root_path: &str, file: &ScannedFile) {
+            if file.rel_path.matches('/').count() >= SPLIT_DEPTH {
+                self.0.store(true, Ordering::Relaxed);
+            }
+        }
+    }
+
+    #[test]
+    fn interrupted_run_resumes_without_deleting_unseen_entries() {
+        let dir = temp_dir("indexer_resume");
+        let base = fs::canonicalize(&dir).unwrap();
+        let (first, second) = (base.join("a"), base.join("b"));
+        write_file(&first.join("top.txt"), "top");
+        write_file(&first.join("x/y/z/deep.txt"), "deep");
+        write_file(&first.join("x/y/z/gone.txt"), "gone");
+        write_file(&second.join("other.txt"), "other");
+
+        let cfg = Config {
+            roots: vec![
+                path_to_string(&first).into(),
+                path_to_string(&second).into(),
+            ],
+            excludes: vec![],
+            ..Config::default()
+        };
+        let store_path = dir.join("catalog.bin");
+        let mut store = store::Store::open(&store_path).unwrap();
+        run(&mut store, &cfg, false, false).unwrap();
+        store.save().unwrap();
+        let active = |store: &store::Store| store.files().filter(|f| f.status == "active").count();
+        let indexed = active(&store);
+
+        fs::remove_file(first.join("x/y/z/gone.txt")).unwrap();
+        let stop = Arc::new(AtomicBool::new(false));
+        let mut observer = StopBelowSplit(stop.clone());
+        let stats = run_internal(
+            &mut store,
+            &cfg,
+            true,
+            false,
+            Some(&mut observer),
+            Some(stop),
+        )
+        .unwrap();
+        assert!(stats.interrupted);
+        assert_eq!(stats.deleted, 0);
+
+        // Saved as it stood: nothing half-scanned is marked deleted.
+        let saved = checkpoint::load(&store_path).unwrap();
+        let partial = saved.partial.clone().unwrap();
+        assert!(saved.full);
+        assert!(saved.done.is_empty());
+        assert_eq!(partial.pending, vec!["x/y/z".to_string()]);
+        let store = store::Store::load(&store_path).unwrap();
+        assert_eq!(store.data.last_run_id, saved.run_id);
+        assert_eq!(active(&store), indexed);
+
+        let mut store = store::Store::open(&store_path).unwrap();
+        let stop = Arc::new(AtomicBool::new(false));
+        let stats = run_interruptible(&mut store, &cfg, false, false, stop).unwrap();
+        assert!(!stats.interrupted);
+        assert_eq!(stats.deleted, 1);
+        assert_eq!(store.data.last_run_id, saved.run_id);
+        assert_eq!(active(&store), indexed - 1);
+        assert!(checkpoint::load(&store_path).is_none());
+        let other = store.files().find(|f| f.rel_path() == "other.txt").unwrap();
+        assert_eq!(other.last_seen_run, saved.run_id);
+    }
+
+    struct FinishedRoots(Vec<String>);
+
+    impl ScanObserver for FinishedRoots {
+        fn on_file_scanned(&mut self, _root_path: &str, _file: &ScannedFile) {}
+
+        fn on_root_finished(&mut self, root_path: &str) {
+            self.0.push(root_path.to_string());
+        }
+    }
+
+    #[test]
+    fn observed_runs_ignore_checkpoints() {
+        let dir = temp_dir("indexer_observed");
+        let base = fs::canonicalize(&dir).unwrap();
+        let (first, second) = (base.join("a"), base.join("b"));
+        write_file(&first.join("one.txt"), "one");
+        write_file(&second.join("two.txt"), "two");
+
+        let cfg = Config {
+            roots: vec![
+                path_to_string(&first).into(),
+                path_to_string(&second).into(),
+            ],
+            excludes: vec![],
+            ..Config::default()
+        };
+        let store_path = dir.join("catalog.bin");
+        let mut store = store::Store::open(&store_path).unwrap();
+        run(&mut store, &cfg, false, false).unwrap();
+        store.save().unwrap();
+        let run_id = store.data.last_run_id;
+        let first = path_to_string(&first);
+        let first_id = store
+            .data
+            .roots
+            .iter()
+            .find(|r| r.path == first)
+            .unwrap()
+            .id;
+        checkpoint::save(
+            &store_path,
+            &Checkpoint {
+                run_id,
+                first_file_id: store.data.next_file_id,
+                full: false,
+                one_filesystem: false,
+                done: vec![first_id],
+                partial: None,
+            },
+        )
+        .unwrap();
+
+        // An observer (e.g. `analyze`) sees every root, in a run of its own.
+        let mut observer = FinishedRoots(Vec::new());
+        run_with_observer(&mut store, &cfg, false, false, &mut observer).unwrap();
+        assert_eq!(observer.0.len(), 2);
+        assert!(store.data.last_run_id > run_id);
+    }
+
+    #[test]
+    fn root_settings_override_global_ones() {
+        let dir = temp_dir("indexer_root_settings");
+        let base = fs::canonicalize(&dir).unwrap();
+        let (dotfiles, projects) = (base.join("dotfiles"), base.join("projects"));
+        write_file(&dotfiles.join(".nvim/init.lua"), "nvim");
+        write_file(&dotfiles.join("a/b/too-deep.txt"), "deep");
+        write_file(&dotfiles.join("debug.log"), "log");
+        write_file(&projects.join(".git/HEAD"), "ref");
+        write_file(&projects.join("debug.log"), "log");
+        write_file(&base.join("shared/linked.txt"), "linked");
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(base.join("shared"), projects.join("shared")).unwrap();
+
+        let cfg = Config {
+            roots: vec![
+                crate::config::RootConfig {
+                    path: path_to_string(&dotfiles),
+                    excludes: vec!["*.log".to_string()],
+                    include_hidden: Some(true),
+                    max_depth: Some(2),
+                    ..Default::default()
+                },
+                crate::config::RootConfig {
+                    path: path_to_string(&projects),
+                    follow_symlinks: true,
+                    interval: Some("1d".to_string()),
+                    ..Default::default()
+                },
+            ],
+            excludes: vec![],
+            ..Config::default()
+        };
+        let mut store = store::Store::load(&dir.join("catalog.bin")).unwrap();
+        run(&mut store, &cfg, false, false).unwrap();
+        let prefix = path_to_string(&base);
+        let active = |store: &store::Store| {
+            let mut paths = store
+                .data
+                .files
+                .iter()
+                .filter(|f| f.status == "active")
+                .map(|f| f.abs_path.trim_start_matches(&prefix).to_string())
+                .collect::<Vec<_>>();
+            paths.sort();
+            paths
+        };
+        let mut expected = vec![
+            "/dotfiles/.nvim",
+            "/dotfiles/.nvim/init.lua",
+            "/dotfiles/a",
+            "/dotfiles/a/b",
+            "/projects/debug.log",
+        ];
+        if cfg!(unix) {
+            expected.extend(["/projects/shared", "/projects/shared/linked.txt"]);
+        }
+        assert_eq!(active(&store), expected);
+
+        // Within its interval the root is left alone, unless the run is full.
+        write_file(&projects.join("new.txt"), "new");
+        run(&mut store, &cfg, false, false).unwrap();
+        assert!(!active(&store).contains(&"/projects/new.txt".to_string()));
+        run(&mut store, &cfg, true, false).unwrap();
+        assert!(active(&store).contains(&"/projects/new.txt".to_string()));
+    }
+
+    #[test]
+    fn ignore_files_are_honoured_when_enabled() {
+        let dir = temp_dir("indexer_ignore_files");
+        let root = fs::canonicalize(&dir).unwrap().join("project");
+        fs::create_dir_all(root.join(".git")).unwrap();
+        write_file(&root.join(".gitignore"), "target/\n*.tmp\n");
+        write_file(&root.join("src/main.rs"), "fn main() {}");
+        write_file(&root.join("notes.tmp"), "tmp");
+        write_file(&root.join("target/debug/app"), "bin");
+        // Below the split depth, so walked from a start under the root.
+        write_file(&root.join("a/b/c/d/target/out.o"), "obj");
+        write_file(&root.join("a/b/c/d/lib.rs"), "lib");
+        write_file(&root.join("docs/.catalogignore"), "draft.md\n");
+        write_file(&root.join("docs/draft.md"), "draft");
+        write_file(&root.join("docs/final.md"), "final");
+
+        let mut cfg = Config {
+            roots: vec![path_to_string(&root).into()],
+            excludes: vec![],
+            ..Config::default()
+        };
+        let mut store = store::Store::load(&dir.join("catalog.bin")).unwrap();
+        let files = |store: &store::Store| {
+            let mut paths = store
+                .data
+                .files
+                .iter()
+                .filter(|f| f.status == "active" && !f.is_dir)
+                .map(|f| f.rel_path.clone())
+                .collect::<Vec<_>>();
+            paths.sort();
+            paths
+        };
+        run(&mut store, &cfg, false, false).unwrap();
+        assert_eq!(files(&store).len(), 7);
+
+        cfg.vcs_ignore = true;
+        cfg.roots[0].catalog_ignore = Some(true);
+        // Settings changed, so every directory is listed again.
+        let stats = run(&mut store, &cfg, false, false).unwrap();
+        assert_eq!(stats.deleted, 4 + 3, "files and the target directories");
+        assert_eq!(
+            files(&store),
+            vec!["a/b/c/d/lib.rs", "docs/final.md", "src/main.rs"]
+        );
+
+        // An edited ignore file leaves the directory's mtime alone.
+        let docs_mtime = SystemTime::now() - Duration::from_secs(3600);
+        fs::File::open(root.join("docs"))
+            .unwrap()
+            .set_modified(docs_mtime)
+            .unwrap();
+        run(&mut store, &cfg, false, false).unwrap();
+        fs::write(root.join("docs/.catalogignore"), "final.md\n").unwrap();
+        fs::File::open(root.join("docs"))
+            .unwrap()
+            .set_modified(docs_mtime)
+            .unwrap();
+        run(&mut store, &cfg, false, false).unwrap();
+        assert_eq!(
+            files(&store),
+            vec!["a/b/c/d/lib.rs", "docs/draft.md", "src/main.rs"]
+        );
+    }
+
+    #[test]
+    fn preview_counts_what_index_would_pick_up() {
+        let dir = temp_dir("indexer_preview");
+        let root = fs::canonicalize(&dir).unwrap().join("root");
+        write_file(&root.join("src/main.rs"), "fn main() {}");
+        write_file(&root.join("src/lib.rs"), "pub fn lib() {}");
+        write_file(&root.join("node_modules/pkg/index.js"), "module");
+        write_file(&root.join("node_modules/pkg/readme.md"), "readme");
+        write_file(&root.join("debug.log"), "log");
+        write_file(&root.join(".env"), "KEY=1");
+        write_file(&root.join(".catalogignore"), "*.log\n");
+        write_file(&root.join("deep/a/b/file.txt"), "deep");
+
+        let cfg = Config {
+            one_filesystem: false,
+            roots: vec![crate::config::RootConfig {
+                path: path_to_string(&root),
+                excludes: vec!["node_modules/".to_string()],
+                max_depth: Some(2),
+                catalog_ignore: Some(true),
+                ..Default::default()
+            }],
+            excludes: vec![],
+            ..Config::default()
+        };
+        let root_str = path_to_string(&root);
+        let options = cfg.root_options(&root_str);
+        let preview = preview_root(&options, &root_str, ProgressBar::hidden()).unwrap();
+        let tally = |entries, bytes| Tally { entries, bytes };
+        assert_eq!(preview.indexed, tally(5, 27));
+        assert_eq!(
+            preview.subtrees,
+            vec![
+                ("src".to_string(), tally(3, 27)),
+                ("deep".to_string(), tally(2, 0)),
+            ]
+        );
+        assert_eq!(
+            preview.excluded,
+            vec![
+                (
+                    Exclusion::Pattern("node_modules/".to_string()),
+                    tally(4, 12)
+                ),
+                (Exclusion::TooDeep(2), tally(2, 4)),
+                (Exclusion::Hidden, tally(2, 11)),
+                (
+                    Exclusion::IgnoreFile(IgnoreHit {
+                        file: root.join(".catalogignore"),
+                        pattern: "*.log".to_string(),
+                    }),
+                    tally(1, 3)
+                ),
+            ]
+        );
+
+        // The preview wrote nothing, and agrees with a real run.
+        let store_path = dir.join("catalog.bin");
+        assert!(!store_path.exists());
+        let mut store = store::Store::load(&store_path).unwrap();
+        run(&mut store, &cfg, false, false).unwrap();
+        let active = store.data.files.iter().filter(|f| f.status == "active");
+        assert_eq!(active.count(), 5);
+    }
 }
@@ -0,0 +1,21 @@
+use anyhow::{Context, Result};
+use signal_hook::consts::{SIGINT, SIGTERM};
+use signal_hook::flag;
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+
+/// Exit status after stopping on Ctrl+C (128 + SIGINT).
+pub const EXIT_CODE: i32 = 130;
+
+/// Turns the first Ctrl+C (or SIGTERM) into a flag that long-running work
+/// polls so it can save what it has and stop. A second signal exits at once.
+pub fn install() -> Result<Arc<AtomicBool>> {
+    let stop = Arc::new(AtomicBool::new(false));
+    for signal in [SIGINT, SIGTERM] {
+        // Registered first, so it only fires once the flag below is set.
+        flag::register_conditional_shutdown(signal, EXIT_CODE, stop.clone())
+            .context("failed to install signal handler")?;
+        flag::register(signal, stop.clone()).context("failed to install signal handler")?;
+    }
+    Ok(stop)
+}
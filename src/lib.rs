@@ -1,10 +1,30 @@
 pub mod analyze;
 pub mod analyze_tui;
+pub mod changes;
+pub mod checkpoint;
 pub mod cli;
 pub mod config;
+pub mod dir_tree;
+pub mod explain;
+pub mod find_tui;
+pub mod fsck;
+pub mod ignore_files;
 pub mod indexer;
+pub mod interrupt;
+pub mod lock;
+pub mod migrate;
 pub mod output;
+pub mod path_index;
+pub mod preview;
+pub mod purge;
+pub mod query;
+pub mod rank;
 pub mod roots;
 pub mod search;
+pub mod snapshot;
 pub mod store;
+pub mod tags;
 pub mod util;
+pub mod volume;
+pub mod wal;
+pub mod watch;
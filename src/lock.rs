@@ -0,0 +1,275 @@
+use anyhow::{Context, Result};
+use std::fmt;
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// File next to the store that commands which modify it lock exclusively for
+/// as long as they run. It holds the holder's pid and what it is doing.
+pub fn lock_path(store_path: &Path) -> PathBuf {
+    store_path.with_extension("lock")
+}
+
+/// File locked while the snapshot and log change hands: shared by readers
+/// while they map the snapshot and replay the log, exclusive while a writer
+/// appends to the log or replaces the snapshot.
+pub fn commit_lock_path(store_path: &Path) -> PathBuf {
+    store_path.with_extension("commit.lock")
+}
+
+/// The process holding the write lock, as it recorded itself.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Holder {
+    pub pid: u32,
+    pub activity: String,
+}
+
+/// Returned (inside `anyhow::Error`) when another process holds the write
+/// lock and the caller did not ask to wait.
+#[derive(Debug)]
+pub struct Busy {
+    /// `None` when the lock record names no live process.
+    pub holder: Option<Holder>,
+}
+
+impl Busy {
+    fn who(&self) -> String {
+        match &self.holder {
+            Some(holder) => format!(
+                "another catalog process is {} (pid {})",
+                holder.activity, holder.pid
+            ),
+            None => "another catalog process is using the store".to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Busy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}; rerun with --wait to wait for it", self.who())
+    }
+}
+
+impl std::error::Error for Busy {}
+
+/// Exclusive lock for a command that modifies the store; released (and its
+/// record cleared) on drop. The OS drops the lock of a process that dies, so
+/// a lock is never stale; a record left behind by one is ignored.
+#[derive(Debug)]
+pub struct WriteLock {
+    file: Option<File>,
+}
+
+impl WriteLock {
+    /// Takes the write lock, recording `activity` (e.g. "indexing") for
+    /// whoever finds it taken. With `wait`, blocks until it is released;
+    /// otherwise fails with `Busy`.
+    pub fn acquire(store_path: &Path, activity: &str, wait: bool) -> Result<Self> {
+        let path = lock_path(store_path);
+        let Some(mut file) = open(&path)? else {
+            return Ok(Self { file: None });
+        };
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                let busy = Busy {
+                    holder: read_holder(&mut file).filter(|h| is_alive(h.pid)),
+                };
+                if !wait {
+                    return Err(busy.into());
+                }
+                eprintln!("Waiting for the store: {}...", busy.who());
+                file.lock()
+                    .with_context(|| format!("failed to lock store: {}", path.display()))?;
+            }
+            Err(TryLockError::Error(err)) if err.kind() == io::ErrorKind::Unsupported => {
+                tracing::warn!("store locking unsupported here: {}", path.display());
+                return Ok(Self { file: None });
+            }
+            Err(TryLockError::Error(err)) => {
+                return Err(err)
+                    .with_context(|| format!("failed to lock store: {}", path.display()));
+            }
+        }
+        if let Some(stale) = read_holder(&mut file) {
+            tracing::debug!(
+                "pid {} exited while {} without releasing the store lock",
+                stale.pid,
+                stale.activity
+            );
+        }
+        file.set_len(0)?;
+        file.seek(SeekFrom::Start(0))?;
+        writeln!(file, "{} {}", std::process::id(), activity)?;
+        file.sync_all()?;
+        Ok(Self { file: Some(file) })
+    }
+}
+
+impl Drop for WriteLock {
+    fn drop(&mut self) {
+        if let Some(file) = &self.file {
+            let _ = file.set_len(0);
+        }
+    }
+}
+
+/// Brief lock around reading or committing the snapshot and log (see
+/// `commit_lock_path`). Locking is best effort: a store in a read-only
+/// directory is read without it.
+#[derive(Debug)]
+pub struct CommitLock {
+    _file: Option<File>,
+}
+
+impl CommitLock {
+    pub fn shared(store_path: &Path) -> Self {
+        Self::take(store_path, false).unwrap_or_else(|err| {
+            tracing::debug!("reading store without a lock: {:#}", err);
+            Self { _file: None }
+        })
+    }
+
+    pub fn exclusive(store_path: &Path) -> Result<Self> {
+        Self::take(store_path, true)
+    }
+
+    fn take(store_path: &Path, exclusive: bool) -> Result<Self> {
+        let path = commit_lock_path(store_path);
+        let Some(file) = open(&path)? else {
+            return Ok(Self { _file: None });
+        };
+        let locked = if exclusive {
+            file.lock()
+        } else {
+            file.lock_shared()
+        };
+        match locked {
+            Ok(()) => Ok(Self { _file: Some(file) }),
+            Err(err) if err.kind() == io::ErrorKind::Unsupported => Ok(Self { _file: None }),
+            Err(err) => {
+                Err(err).with_context(|| format!("failed to lock store: {}", path.display()))
+            }
+        }
+    }
+}
+
+/// Opens (creating) a lock file; `None` when the store's directory does not
+/// exist yet, in which case there is nothing to protect.
+fn open(path: &Path) -> Result<Option<File>> {
+    if path
+        .parent()
+        .is_some_and(|parent| !parent.as_os_str().is_empty() && !parent.exists())
+    {
+        return Ok(None);
+    }
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)
+        .with_context(|| format!("failed to open lock: {}", path.display()))?;
+    Ok(Some(file))
+}
+
+fn read_holder(file: &mut File) -> Option<Holder> {
+    let mut text = String::new();
+    file.seek(SeekFrom::Start(0)).ok()?;
+    file.read_to_string(&mut text).ok()?;
+    let (pid, activity) = text.trim_end().split_once(' ')?;
+    Some(Holder {
+        pid: pid.parse().ok()?,
+        activity: activity.to_string(),
+    })
+}
+
+#[cfg(unix)]
+fn is_alive(pid: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    if pid <= 0 {
+        return false;
+    }
+    // Signal 0 only checks that the process exists.
+    let found = unsafe { libc::kill(pid, 0) } == 0;
+    found || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(not(unix))]
+fn is_alive(_pid: u32) -> bool {
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn temp_store() -> PathBuf {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!(
+            "catalog_test_lock_{}_{}",
+            std::process::id(),
+            nanos
+        ));
+        fs::create_dir_all(&dir).unwrap();
+        dir.join("catalog.bin")
+    }
+
+    #[test]
+    fn second_writer_is_told_who_holds_the_lock() {
+        let store = temp_store();
+        let lock = WriteLock::acquire(&store, "indexing", false).unwrap();
+        let holder = Holder {
+            pid: std::process::id(),
+            activity: "indexing".to_string(),
+        };
+        let err = WriteLock::acquire(&store, "tagging", false).unwrap_err();
+        let busy = err.downcast_ref::<Busy>().unwrap();
+        assert_eq!(busy.holder, Some(holder));
+        assert!(err.to_string().starts_with(&format!(
+            "another catalog process is indexing (pid {})",
+            std::process::id()
+        )));
+
+        drop(lock);
+        assert_eq!(fs::read_to_string(lock_path(&store)).unwrap(), "");
+        let _lock = WriteLock::acquire(&store, "tagging", false).unwrap();
+    }
+
+    #[test]
+    fn records_of_dead_processes_are_ignored() {
+        let store = temp_store();
+        let dead = format!("{} indexing\n", i32::MAX);
+        fs::write(lock_path(&store), &dead).unwrap();
+        let lock = WriteLock::acquire(&store, "watching", false).unwrap();
+        let record = fs::read_to_string(lock_path(&store)).unwrap();
+        assert_eq!(record, format!("{} watching\n", std::process::id()));
+
+        // Held, but by something that left a bogus record.
+        fs::write(lock_path(&store), &dead).unwrap();
+        let err = WriteLock::acquire(&store, "tagging", false).unwrap_err();
+        assert_eq!(err.downcast_ref::<Busy>().unwrap().holder, None);
+        drop(lock);
+    }
+
+    #[test]
+    fn waiting_writer_gets_the_lock_once_released() {
+        let store = temp_store();
+        let lock = WriteLock::acquire(&store, "indexing", false).unwrap();
+        let waiter = {
+            let store = store.clone();
+            std::thread::spawn(move || WriteLock::acquire(&store, "tagging", true).map(|_| ()))
+        };
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        assert!(!waiter.is_finished());
+        drop(lock);
+        waiter.join().unwrap().unwrap();
+    }
+}
@@ -1,14 +1,26 @@
 use anyhow::{Context, Result};
 use catalog::analyze;
 use catalog::analyze_tui;
+use catalog::changes;
 use catalog::cli;
 use catalog::config;
+use catalog::explain;
+use catalog::find_tui;
+use catalog::fsck;
 use catalog::indexer;
+use catalog::interrupt;
+use catalog::lock;
+use catalog::migrate;
 use catalog::output;
+use catalog::preview;
+use catalog::purge;
+use catalog::query;
 use catalog::roots;
 use catalog::search;
 use catalog::store;
+use catalog::tags;
 use catalog::util;
+use catalog::watch;
 use clap::Parser;
 use indicatif::{ProgressBar, ProgressStyle};
 use tracing_subscriber::EnvFilter;
@@ -23,11 +35,13 @@ fn main() -> Result<()> {
     tracing_subscriber::fmt().with_env_filter(filter).init();
 
     let paths = config::Paths::resolve(cli.config.as_deref(), cli.store.as_deref())?;
+    let wait = cli.wait;
 
     match cli.command {
         cli::Commands::Init { preset } => {
             let preset_name = preset.as_ref().map(|p| p.to_string());
             config::init(&paths, preset.clone())?;
+            let _lock = lock::WriteLock::acquire(&paths.store_path, "initializing", wait)?;
             let mut store = store::Store::init(&paths.store_path)?;
             if let Ok(cfg) = config::load(&paths.config_path) {
                 roots::sync_roots(&mut store.data, &cfg, preset_name)?;
@@ -41,39 +55,83 @@ fn main() -> Result<()> {
             let store = store::Store::load(&paths.store_path)?;
             roots::print_roots(&store.data, &cfg)?;
         }
-        cli::Commands::Add { paths: add_paths } => {
+        cli::Commands::Add {
+            paths: add_paths,
+            preview: true,
+        } => {
+            let mut cfg = config::load(&paths.config_path)
+                .with_context(|| "config not found; run `catalog init`")?;
+            let before = cfg.roots.len();
+            roots::add_roots(&mut cfg, &add_paths)?;
+            let new_roots = cfg.roots[before..]
+                .iter()
+                .map(|root| root.path.clone())
+                .collect::<Vec<_>>();
+            if new_roots.is_empty() {
+                println!("No new roots to preview.");
+            } else {
+                preview::print(&preview::preview_roots(&cfg, &new_roots, false)?);
+            }
+        }
+        cli::Commands::Add {
+            paths: add_paths, ..
+        } => {
+            let _lock = lock::WriteLock::acquire(&paths.store_path, "adding roots", wait)?;
             let mut cfg = config::load(&paths.config_path)
                 .with_context(|| "config not found; run `catalog init`")?;
             let added = roots::add_roots(&mut cfg, &add_paths)?;
             config::save(&paths.config_path, &cfg)?;
-            let mut store = store::Store::load(&paths.store_path)?;
+            let mut store = store::Store::open(&paths.store_path)?;
             roots::sync_roots(&mut store.data, &cfg, None)?;
             store.save()?;
             println!("Added {} root(s).", added);
         }
         cli::Commands::Rm { paths: rm_paths } => {
+            let _lock = lock::WriteLock::acquire(&paths.store_path, "removing roots", wait)?;
             let mut cfg = config::load(&paths.config_path)
                 .with_context(|| "config not found; run `catalog init`")?;
             let removed = roots::remove_roots(&mut cfg, &rm_paths)?;
             config::save(&paths.config_path, &cfg)?;
-            let mut store = store::Store::load(&paths.store_path)?;
+            let mut store = store::Store::open(&paths.store_path)?;
             roots::sync_roots(&mut store.data, &cfg, None)?;
             store.save()?;
             println!("Removed {} root(s).", removed);
         }
+        cli::Commands::Index {
+            one_filesystem,
+            dry_run: true,
+            root,
+            ..
+        } => {
+            let cfg = config::load(&paths.config_path)
+                .with_context(|| "config not found; run `catalog init`")?;
+            preview::print(&preview::preview_roots(&cfg, &root, one_filesystem)?);
+        }
         cli::Commands::Index {
             full,
             one_filesystem,
+            ..
         } => {
             let cfg = config::load(&paths.config_path)
                 .with_context(|| "config not found; run `catalog init`")?;
-            let mut store = store::Store::load(&paths.store_path)?;
-            let stats = indexer::run(&mut store, &cfg, full, one_filesystem)?;
+            let lock = lock::WriteLock::acquire(&paths.store_path, "indexing", wait)?;
+            let stop = interrupt::install()?;
+            let mut store = store::Store::open(&paths.store_path)?;
+            let stats = indexer::run_interruptible(&mut store, &cfg, full, one_filesystem, stop)?;
             store.save()?;
-            println!(
-                "Indexed {} files ({} updated, {} deleted, {} skipped).",
-                stats.seen, stats.updated, stats.deleted, stats.skipped
-            );
+            println!("{}", stats.summary());
+            if stats.interrupted {
+                eprintln!("Interrupted; progress saved. Run `catalog index` again to resume.");
+                drop(lock);
+                std::process::exit(interrupt::EXIT_CODE);
+            }
+        }
+        cli::Commands::Explain { path } => {
+            let cfg = config::load(&paths.config_path)
+                .with_context(|| "config not found; run `catalog init`")?;
+            let store = store::Store::load(&paths.store_path)?;
+            let explanation = explain::explain(&store, &cfg, &path)?;
+            explain::print(&explanation);
         }
         cli::Commands::Search {
             query,
@@ -83,61 +141,166 @@ fn main() -> Result<()> {
             min_size,
             max_size,
             root,
+            tag,
+            sort,
+            status,
             json,
             long,
         } => {
             let cfg = config::load(&paths.config_path)
                 .with_context(|| "config not found; run `catalog init`")?;
             let store = store::Store::load(&paths.store_path)?;
-            let results = search::search(
-                &store,
-                &cfg,
-                &query,
-                ext.as_deref(),
-                after.as_deref(),
-                before.as_deref(),
+            let flags = query::Flags {
+                ext,
+                after,
+                before,
                 min_size,
                 max_size,
-                root.as_deref(),
-            )?;
+                root,
+                tags: tag,
+            };
+            let expr = query::build(&query, &flags)?;
+            let results = search::search(&store, &cfg, &expr, sort, status)?;
             let use_json = json || matches!(cfg.output, config::OutputMode::Json);
             output::print_entries(&results, use_json, long)?;
         }
+        cli::Commands::Find { query, root, tag } => {
+            let cfg = config::load(&paths.config_path)
+                .with_context(|| "config not found; run `catalog init`")?;
+            let store = store::Store::load(&paths.store_path)?;
+            let flags = query::Flags {
+                root,
+                tags: tag,
+                ..Default::default()
+            };
+            match find_tui::run_find_tui(&store, &cfg, &query, &flags)? {
+                Some(paths) => {
+                    for path in paths {
+                        println!("{}", path);
+                    }
+                }
+                // Like fzf: no output and a non-zero status, so `cd "$(catalog find)"`
+                // does nothing when cancelled.
+                None => std::process::exit(130),
+            }
+        }
         cli::Commands::Recent {
             days,
             limit,
+            tag,
             json,
             long,
         } => {
             let cfg = config::load(&paths.config_path)
                 .with_context(|| "config not found; run `catalog init`")?;
             let store = store::Store::load(&paths.store_path)?;
-            let results = search::recent(&store, &cfg, days, limit)?;
+            let flags = query::Flags {
+                tags: tag,
+                ..Default::default()
+            };
+            let filter = query::build("", &flags)?;
+            let results = search::recent(&store, &cfg, days, limit, &filter)?;
             let use_json = json || matches!(cfg.output, config::OutputMode::Json);
             output::print_entries(&results, use_json, long)?;
         }
+        cli::Commands::Deleted {
+            since,
+            root,
+            json,
+            long,
+        } => {
+            let cfg = config::load(&paths.config_path).ok();
+            let store = store::Store::load(&paths.store_path)?;
+            let since = query::parse_since(&since, chrono::Local::now().timestamp())?;
+            let flags = query::Flags {
+                root,
+                ..Default::default()
+            };
+            let filter = query::build("", &flags)?;
+            let results = search::deleted(&store, since, &filter)?;
+            let use_json =
+                json || cfg.is_some_and(|c| matches!(c.output, config::OutputMode::Json));
+            output::print_entries(&results, use_json, long)?;
+        }
+        cli::Commands::Changes {
+            since,
+            run,
+            kind,
+            json,
+        } => {
+            let cfg = config::load(&paths.config_path).ok();
+            let store = store::Store::load(&paths.store_path)?;
+            let now = chrono::Local::now().timestamp();
+            let since = match (since, run) {
+                (Some(since), _) => Some(query::parse_since(&since, now)?),
+                (None, Some(_)) => None,
+                (None, None) => Some(query::parse_since("7d", now)?),
+            };
+            let results = changes::query(&store.data, since, run, kind);
+            let use_json =
+                json || cfg.is_some_and(|c| matches!(c.output, config::OutputMode::Json));
+            changes::print_changes(&results, use_json)?;
+        }
+        cli::Commands::Tag { command } => {
+            let _lock = match command {
+                cli::TagCommands::Ls { .. } => None,
+                _ => Some(lock::WriteLock::acquire(&paths.store_path, "tagging", wait)?),
+            };
+            let mut store = store::Store::open(&paths.store_path)?;
+            match command {
+                cli::TagCommands::Add { tag, targets } => {
+                    let name = tags::normalize_name(&tag)?;
+                    let ids = tags::resolve_targets(&store.data, &targets)?;
+                    let added = tags::add_tag(&mut store.data, &name, &ids);
+                    store.save()?;
+                    println!("Tagged {} file(s) with '{}'.", added, name);
+                }
+                cli::TagCommands::Rm { tag, targets } => {
+                    let name = tags::normalize_name(&tag)?;
+                    let ids = tags::resolve_targets(&store.data, &targets)?;
+                    let removed = tags::remove_tag(&mut store.data, &name, &ids);
+                    store.save()?;
+                    println!("Removed '{}' from {} file(s).", name, removed);
+                }
+                cli::TagCommands::Ls { targets } => {
+                    let ids = tags::resolve_targets(&store.data, &targets)?;
+                    let names = tags::names_by_file(&store.data);
+                    for file in store.data.files.iter().filter(|f| ids.contains(&f.id)) {
+                        let tags = names
+                            .get(&file.id)
+                            .map(|t| t.join(","))
+                            .unwrap_or_else(|| "-".to_string());
+                        println!("{}  {}  {}", file.id, tags, file.abs_path);
+                    }
+                }
+            }
+        }
+        cli::Commands::Tags { json } => {
+            let cfg = config::load(&paths.config_path).ok();
+            let store = store::Store::load(&paths.store_path)?;
+            let counts = tags::tag_counts(&store.data);
+            let use_json =
+                json || cfg.is_some_and(|c| matches!(c.output, config::OutputMode::Json));
+            tags::print_tag_counts(&counts, use_json)?;
+        }
         cli::Commands::Watch {
             interval,
             full,
             one_filesystem,
+            poll,
+            debounce,
         } => {
             let cfg = config::load(&paths.config_path)
                 .with_context(|| "config not found; run `catalog init`")?;
-            let mut store = store::Store::load(&paths.store_path)?;
-            let interval = interval.unwrap_or(30);
-            println!(
-                "Watching for changes every {}s. Press Ctrl+C to stop.",
-                interval
-            );
-            loop {
-                let stats = indexer::run(&mut store, &cfg, full, one_filesystem)?;
-                store.save()?;
-                println!(
-                    "Indexed {} files ({} updated, {} deleted, {} skipped).",
-                    stats.seen, stats.updated, stats.deleted, stats.skipped
-                );
-                std::thread::sleep(std::time::Duration::from_secs(interval));
-            }
+            let opts = watch::WatchOptions {
+                interval: std::time::Duration::from_secs(interval.unwrap_or(30)),
+                debounce: std::time::Duration::from_millis(debounce.unwrap_or(500)),
+                full,
+                one_filesystem,
+                poll,
+                wait,
+            };
+            watch::run(&paths.store_path, &cfg, &opts)?;
         }
         cli::Commands::Export { output } => {
             let store = store::Store::load(&paths.store_path)?;
@@ -161,6 +324,7 @@ fn main() -> Result<()> {
             }
         }
         cli::Commands::Prune => {
+            let _lock = lock::WriteLock::acquire(&paths.store_path, "pruning", wait)?;
             let removed = store::prune_store(&paths.store_path)?;
             if removed == 0 {
                 println!("No store found to remove.");
@@ -168,6 +332,40 @@ fn main() -> Result<()> {
                 println!("Pruned {} store file(s).", removed);
             }
         }
+        cli::Commands::Purge { older_than, root } => {
+            let _lock = lock::WriteLock::acquire(&paths.store_path, "purging", wait)?;
+            let mut store = store::Store::open(&paths.store_path)?;
+            let now = chrono::Local::now().timestamp();
+            let cutoff = older_than
+                .map(|value| query::parse_since(&value, now))
+                .transpose()?;
+            let root_id = root
+                .map(|root| purge::resolve_root(&store.data, &root))
+                .transpose()?;
+            let purged = purge::purge_deleted(&mut store.data, cutoff, root_id, now);
+            store.save()?;
+            println!("Purged {} deleted entries.", purged);
+        }
+        cli::Commands::Fsck { repair } => {
+            let _lock = repair
+                .then(|| lock::WriteLock::acquire(&paths.store_path, "repairing", wait))
+                .transpose()?;
+            let report = fsck::run(&paths.store_path, repair)?;
+            println!("{}", report.summary(repair));
+            let unresolved = report.unresolved();
+            if unresolved > 0 || (!repair && !report.issues.is_empty()) {
+                anyhow::bail!("store check failed");
+            }
+        }
+        cli::Commands::Store { command } => match command {
+            cli::StoreCommands::Migrate { dry_run } => {
+                let _lock = (!dry_run)
+                    .then(|| lock::WriteLock::acquire(&paths.store_path, "migrating", wait))
+                    .transpose()?;
+                let report = migrate::run(&paths.store_path, dry_run)?;
+                println!("{}", report.summary(dry_run));
+            }
+        },
         cli::Commands::Analyze { path, top, files, json, raw, tui } => {
             let cfg = config::load(&paths.config_path)
                 .with_context(|| "config not found; run `catalog init`")?;
@@ -181,6 +379,25 @@ fn main() -> Result<()> {
                 filter.as_deref(),
                 chrono::Duration::days(1),
             );
+            // Refreshing writes the store; while another process holds it,
+            // analyze the index as last saved instead.
+            let refresh_lock = if stale {
+                match lock::WriteLock::acquire(&paths.store_path, "indexing", wait) {
+                    Ok(lock) => Some(lock),
+                    Err(err) if err.is::<lock::Busy>() => {
+                        eprintln!("{}. Analyzing the existing index.", err);
+                        None
+                    }
+                    Err(err) => return Err(err),
+                }
+            } else {
+                None
+            };
+            if refresh_lock.is_some() {
+                // Another process may have saved since the store was loaded.
+                store = store::Store::load(&paths.store_path)?;
+            }
+            let stale = refresh_lock.is_some();
             let use_tui = tui || (!json && !raw);
             if use_tui {
                 let browse_index = if stale {
@@ -238,10 +455,7 @@ fn main() -> Result<()> {
                     store.save()?;
                     let report = analyzer.finalize();
                     if !json {
-                        println!(
-                            "\nIndexed {} files ({} updated, {} deleted, {} skipped).",
-                            stats.seen, stats.updated, stats.deleted, stats.skipped
-                        );
+                        println!("\n{}", stats.summary());
                     }
                     report
                 } else {
@@ -23,22 +23,51 @@ pub fn print_entries(entries: &[SearchEntry], json: bool, long: bool) -> Result<
                 "file"
             };
             let ext = e.ext.as_deref().unwrap_or("-");
+            let tags = if e.tags.is_empty() {
+                "-".to_string()
+            } else {
+                e.tags.join(",")
+            };
             println!(
-                "{}  {}  {}  {}  {}  {}  {}  {}",
-                e.id, mtime, e.size, kind, ext, e.status, e.root, e.path
+                "{}  {}  {}  {}  {}  {}  {}  {}  {}",
+                e.id, mtime, e.size, kind, ext, e.status, tags, e.root, e.path
             );
         } else {
             let dt = Local.timestamp_opt(e.mtime, 0).single();
             let mtime = dt
                 .map(|d| d.format("%Y-%m-%d").to_string())
                 .unwrap_or_else(|| "-".to_string());
-            println!("{}  {}  {}", e.path, human_size(e.size), mtime);
+            match (&e.volume, e.status.as_str()) {
+                (Some(volume), _) => println!(
+                    "{}  {}  {}  [offline: {}]",
+                    e.path,
+                    human_size(e.size),
+                    mtime,
+                    volume
+                ),
+                (None, "deleted") => println!(
+                    "{}  {}  {}  [deleted: {}]",
+                    e.path,
+                    human_size(e.size),
+                    mtime,
+                    e.deleted_at.map_or_else(|| "-".to_string(), format_time)
+                ),
+                (None, _) => println!("{}  {}  {}", e.path, human_size(e.size), mtime),
+            }
         }
     }
     Ok(())
 }
 
-fn human_size(bytes: i64) -> String {
+pub fn format_time(at: i64) -> String {
+    Local
+        .timestamp_opt(at, 0)
+        .single()
+        .map(|d| d.format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_else(|| "-".to_string())
+}
+
+pub fn human_size(bytes: i64) -> String {
     let size = if bytes < 0 { 0.0 } else { bytes as f64 };
     let units = ["B", "KB", "MB", "GB", "TB"];
     let mut value = size;
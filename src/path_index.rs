@@ -0,0 +1,391 @@
+use crate::query::{Expr, Term};
+use crate::store::FileRef;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+const INDEX_VERSION: u32 = 1;
+
+/// Below this many candidates, intersecting further posting lists costs more
+/// than letting the filter check the entries directly.
+const SMALL_CANDIDATE_SET: usize = 256;
+
+/// Rebuild from scratch once this many entries are stale (renamed, removed,
+/// or appended out of id order), or a quarter of the index, whichever is
+/// larger.
+const MIN_REBUILD: usize = 1024;
+
+/// Trigram index over lowercase `abs_path`s, stored next to the snapshot
+/// (`catalog.bin` -> `catalog.idx`). It only narrows candidates: stale
+/// postings are allowed, so every hit is still checked by the query filter.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PathIndex {
+    version: u32,
+    /// Size and mtime of the snapshot this index was last synced with. Log
+    /// records on top of it are applied with `update` once loaded.
+    stamp: Option<(u64, u128)>,
+    /// `(file id, path hash)` of every indexed entry, ordered by id.
+    docs: Vec<(i64, u64)>,
+    grams: HashMap<u32, Posting>,
+    stale: usize,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Posting {
+    /// Ascending ids, delta + LEB128 encoded.
+    packed: Vec<u8>,
+    last: i64,
+    /// Ids added out of order (renamed entries keep their id); merged on the
+    /// next rebuild.
+    extra: Vec<i64>,
+}
+
+impl Posting {
+    fn push(&mut self, id: i64) -> bool {
+        if id > self.last {
+            write_varint(&mut self.packed, (id - self.last) as u64);
+            self.last = id;
+            true
+        } else {
+            self.extra.push(id);
+            false
+        }
+    }
+
+    fn ids(&self) -> Vec<i64> {
+        let mut out = Vec::with_capacity(self.packed.len() / 2 + self.extra.len());
+        let mut pos = 0;
+        let mut id = 0i64;
+        while pos < self.packed.len() {
+            id += read_varint(&self.packed, &mut pos) as i64;
+            out.push(id);
+        }
+        if !self.extra.is_empty() {
+            out.extend_from_slice(&self.extra);
+            out.sort_unstable();
+            out.dedup();
+        }
+        out
+    }
+
+    fn cost(&self) -> usize {
+        self.packed.len() + self.extra.len() * 8
+    }
+}
+
+impl PathIndex {
+    pub fn sidecar_path(store_path: &Path) -> PathBuf {
+        store_path.with_extension("idx")
+    }
+
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let raw = fs::read(path)
+            .with_context(|| format!("failed to read path index: {}", path.display()))?;
+        let index: PathIndex = bincode::deserialize(&raw).context("failed to parse path index")?;
+        if index.version != INDEX_VERSION {
+            return Ok(None);
+        }
+        Ok(Some(index))
+    }
+
+    /// Writes the sidecar through a temporary file and a rename. Read-only
+    /// commands save it without holding a store lock, so each process writes
+    /// its own temporary file and the last rename wins whole.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut tmp = path.as_os_str().to_os_string();
+        tmp.push(format!(".{}.tmp", std::process::id()));
+        let tmp = PathBuf::from(tmp);
+        let data = bincode::serialize(self).context("failed to serialize path index")?;
+        let mut file = File::create(&tmp)
+            .with_context(|| format!("failed to write path index: {}", tmp.display()))?;
+        file.write_all(&data)?;
+        file.sync_all()?;
+        fs::rename(&tmp, path)
+            .with_context(|| format!("failed to finalize path index: {}", path.display()))?;
+        Ok(())
+    }
+
+    /// Whether the index was synced with the snapshot currently on disk.
+    pub fn is_current(&self, store_path: &Path) -> bool {
+        self.stamp.is_some() && self.stamp == snapshot_stamp(store_path)
+    }
+
+    pub fn mark_current(&mut self, store_path: &Path) {
+        self.stamp = snapshot_stamp(store_path);
+    }
+
+    /// Brings the index in line with `files`: new and renamed entries are
+    /// added, removed ones are left as stale postings until enough pile up to
+    /// justify a rebuild.
+    pub fn sync<'a>(&mut self, files: impl Iterator<Item = FileRef<'a>>) {
+        self.version = INDEX_VERSION;
+        let files: Vec<FileRef<'a>> = files.collect();
+        let mut current: Vec<(i64, u64, usize)> = files
+            .iter()
+            .enumerate()
+            .map(|(idx, f)| (f.id, path_hash(&f.abs_path()), idx))
+            .collect();
+        current.sort_unstable_by_key(|c| c.0);
+
+        let mut added = Vec::new();
+        let mut old = self.docs.iter().peekable();
+        for &(id, hash, idx) in &current {
+            while old.next_if(|(old_id, _)| *old_id < id).is_some() {
+                self.stale += 1;
+            }
+            match old.next_if(|(old_id, _)| *old_id == id) {
+                Some((_, old_hash)) if *old_hash == hash => {}
+                Some(_) => {
+                    self.stale += 1;
+                    added.push(idx);
+                }
+                None => added.push(idx),
+            }
+        }
+        self.stale += old.count();
+
+        let threshold = MIN_REBUILD.max(current.len() / 4);
+        if self.stale + added.len() > threshold && self.stale > 0 {
+            self.grams.clear();
+            self.stale = 0;
+            added = current.iter().map(|c| c.2).collect();
+        }
+        for idx in added {
+            self.add(&files[idx]);
+        }
+        self.docs = current
+            .into_iter()
+            .map(|(id, hash, _)| (id, hash))
+            .collect();
+    }
+
+    /// Applies the entries a log record changed, each given with what it is
+    /// now (`None` once removed), without going over the rest of the index.
+    pub fn update<'a>(&mut self, changed: impl Iterator<Item = (i64, Option<FileRef<'a>>)>) {
+        for (id, file) in changed {
+            let pos = self.docs.binary_search_by_key(&id, |d| d.0);
+            match (pos, file) {
+                (Ok(pos), Some(file)) => {
+                    let hash = path_hash(&file.abs_path());
+                    if self.docs[pos].1 != hash {
+                        self.stale += 1;
+                        self.docs[pos].1 = hash;
+                        self.add(&file);
+                    }
+                }
+                (Err(pos), Some(file)) => {
+                    self.docs.insert(pos, (id, path_hash(&file.abs_path())));
+                    self.add(&file);
+                }
+                (Ok(pos), None) => {
+                    self.docs.remove(pos);
+                    self.stale += 1;
+                }
+                (Err(_), None) => {}
+            }
+        }
+    }
+
+    fn add(&mut self, file: &FileRef<'_>) {
+        let mut in_order = true;
+        for gram in grams(&file.abs_path().to_lowercase()) {
+            in_order &= self.grams.entry(gram).or_default().push(file.id);
+        }
+        if !in_order {
+            self.stale += 1;
+        }
+    }
+
+    /// Ids of entries that may match `expr`, ascending, or `None` when the
+    /// query has no text long enough to narrow by.
+    pub fn candidates(&self, expr: &Expr) -> Option<Vec<i64>> {
+        match expr {
+            Expr::Term(Term::Text(text) | Term::Name(text) | Term::Path(text)) => {
+                self.text_candidates(text)
+            }
+            Expr::Term(_) | Expr::Not(_) => None,
+            Expr::And(parts) => parts
+                .iter()
+                .filter_map(|p| self.candidates(p))
+                .reduce(|a, b| intersect(&a, &b)),
+            Expr::Or(parts) => {
+                let mut out = Vec::new();
+                for part in parts {
+                    out = union(&out, &self.candidates(part)?);
+                }
+                Some(out)
+            }
+        }
+    }
+
+    fn text_candidates(&self, text: &str) -> Option<Vec<i64>> {
+        let wanted = grams(text);
+        if wanted.is_empty() {
+            return None;
+        }
+        let mut postings = Vec::with_capacity(wanted.len());
+        for gram in wanted {
+            match self.grams.get(&gram) {
+                Some(posting) => postings.push(posting),
+                None => return Some(Vec::new()),
+            }
+        }
+        postings.sort_by_key(|p| p.cost());
+        let mut acc = postings[0].ids();
+        for posting in &postings[1..] {
+            if acc.len() <= SMALL_CANDIDATE_SET {
+                break;
+            }
+            acc = intersect(&acc, &posting.ids());
+        }
+        Some(acc)
+    }
+}
+
+/// Size and mtime of the snapshot, used to tell whether the sidecar was
+/// written together with it.
+fn snapshot_stamp(store_path: &Path) -> Option<(u64, u128)> {
+    let meta = fs::metadata(store_path).ok()?;
+    let mtime = meta.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    Some((meta.len(), mtime.as_nanos()))
+}
+
+/// Distinct byte trigrams of `text`, packed into the low 24 bits.
+fn grams(text: &str) -> Vec<u32> {
+    let mut out: Vec<u32> = text
+        .as_bytes()
+        .windows(3)
+        .map(|w| (w[0] as u32) << 16 | (w[1] as u32) << 8 | w[2] as u32)
+        .collect();
+    out.sort_unstable();
+    out.dedup();
+    out
+}
+
+/// FNV-1a; only used to notice changed paths, so stability across builds
+/// matters more than quality.
+fn path_hash(path: &str) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in path.as_bytes() {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(data: &[u8], pos: &mut usize) -> u64 {
+    let mut value = 0u64;
+    let mut shift = 0;
+    while let Some(&byte) = data.get(*pos) {
+        *pos += 1;
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            break;
+        }
+        shift += 7;
+    }
+    value
+}
+
+fn intersect(a: &[i64], b: &[i64]) -> Vec<i64> {
+    let mut out = Vec::with_capacity(a.len().min(b.len()));
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        match a[i].cmp(&b[j]) {
+            std::cmp::Ordering::Less => i += 1,
+            std::cmp::Ordering::Greater => j += 1,
+            std::cmp::Ordering::Equal => {
+                out.push(a[i]);
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    out
+}
+
+fn union(a: &[i64], b: &[i64]) -> Vec<i64> {
+    let mut out = Vec::with_capacity(a.len() + b.len());
+    out.extend_from_slice(a);
+    out.extend_from_slice(b);
+    out.sort_unstable();
+    out.dedup();
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query;
+    use crate::store::FileEntry;
+
+    fn file(id: i64, abs_path: &str) -> FileEntry {
+        FileEntry {
+            id,
+            root_id: 1,
+            rel_path: abs_path.trim_start_matches('/').to_string(),
+            abs_path: abs_path.to_string(),
+            is_dir: false,
+            is_symlink: false,
+            size: 1,
+            mtime: 1,
+            ext: None,
+            status: "active".to_string(),
+            last_seen_run: 1,
+            dev: None,
+            ino: None,
+            prev_path: None,
+            deleted_at: None,
+        }
+    }
+
+    fn candidates(index: &PathIndex, query: &str) -> Option<Vec<i64>> {
+        index.candidates(&query::parse(query).unwrap())
+    }
+
+    #[test]
+    fn sync_tracks_new_renamed_and_removed_entries() {
+        let mut files = vec![
+            file(1, "/docs/Report.pdf"),
+            file(2, "/docs/notes.txt"),
+            file(3, "/src/main.rs"),
+        ];
+        let mut index = PathIndex::default();
+        index.sync(files.iter().map(FileEntry::view));
+
+        assert_eq!(candidates(&index, "report"), Some(vec![1]));
+        assert_eq!(candidates(&index, "docs OR main"), Some(vec![1, 2, 3]));
+        assert_eq!(candidates(&index, "docs -notes"), Some(vec![1, 2]));
+        assert_eq!(candidates(&index, "ext:rs"), None);
+        assert_eq!(candidates(&index, "zzz"), Some(vec![]));
+
+        // Rename keeps the id; removal leaves a stale posting behind.
+        files[0].abs_path = "/docs/summary.pdf".to_string();
+        files.remove(1);
+        files.push(file(4, "/docs/report-2.pdf"));
+        index.sync(files.iter().map(FileEntry::view));
+
+        assert_eq!(candidates(&index, "summary"), Some(vec![1]));
+        assert_eq!(candidates(&index, "report"), Some(vec![1, 4]));
+        assert_eq!(index.stale, 3);
+        assert_eq!(
+            index.docs.iter().map(|d| d.0).collect::<Vec<_>>(),
+            vec![1, 3, 4]
+        );
+    }
+}
@@ -0,0 +1,103 @@
+use crate::config::{Config, RootConfig};
+use crate::explain;
+use crate::indexer::{self, RootPreview, Tally};
+use crate::output::human_size;
+use crate::util::{normalize_path_allow_missing, path_to_string};
+use anyhow::Result;
+use indicatif::ProgressBar;
+
+/// Top-level subtrees listed per root.
+const TOP_SUBTREES: usize = 10;
+
+/// A root and what indexing it would pick up; `result` is `None` when the
+/// root does not exist.
+#[derive(Debug)]
+pub struct Preview {
+    pub root: RootConfig,
+    pub result: Option<RootPreview>,
+}
+
+/// Walks the roots of `cfg` (only those at `paths`, if any are given) with
+/// their effective settings and reports what `catalog index` would index.
+/// Nothing is read from or written to the store.
+pub fn preview_roots(
+    cfg: &Config,
+    paths: &[String],
+    one_filesystem_override: bool,
+) -> Result<Vec<Preview>> {
+    let mut roots = Vec::new();
+    for path in paths {
+        let normalized = path_to_string(&normalize_path_allow_missing(path)?);
+        match cfg.root(&normalized) {
+            Some(root) => roots.push(root.clone()),
+            None => anyhow::bail!("not a configured root: {} (see `catalog roots`)", path),
+        }
+    }
+    if paths.is_empty() {
+        roots = cfg.roots.clone();
+    }
+
+    let mut previews = Vec::new();
+    for root in roots {
+        if !normalize_path_allow_missing(&root.path)?.exists() {
+            previews.push(Preview { root, result: None });
+            continue;
+        }
+        let mut options = cfg.root_options(&root.path);
+        options.one_filesystem |= one_filesystem_override;
+        let pb = ProgressBar::new_spinner();
+        let result = indexer::preview_root(&options, &root.path, pb.clone())?;
+        pb.finish_and_clear();
+        previews.push(Preview {
+            root,
+            result: Some(result),
+        });
+    }
+    Ok(previews)
+}
+
+pub fn print(previews: &[Preview]) {
+    let mut total = Tally::default();
+    for preview in previews {
+        println!("{}", preview.root.path);
+        let Some(result) = &preview.result else {
+            println!("  missing");
+            continue;
+        };
+        total.entries += result.indexed.entries;
+        total.bytes += result.indexed.bytes;
+        println!("  Would index: {}", describe(result.indexed));
+        if !result.subtrees.is_empty() {
+            println!("  Top subtrees:");
+            for (idx, (name, tally)) in result.subtrees.iter().take(TOP_SUBTREES).enumerate() {
+                println!("    {}. {}  {}", idx + 1, name, describe(*tally));
+            }
+        }
+        if !result.excluded.is_empty() {
+            println!("  Excluded:");
+            for (rule, tally) in &result.excluded {
+                println!(
+                    "    {} ({})  {}",
+                    rule,
+                    explain::rule_source(&preview.root, rule),
+                    describe(*tally)
+                );
+            }
+        }
+        if result.skipped > 0 {
+            println!("  Skipped {} unreadable entries", result.skipped);
+        }
+    }
+    if previews.len() > 1 {
+        println!("Total: {}", describe(total));
+    }
+    println!("Dry run; the store and config were not changed.");
+}
+
+fn describe(tally: Tally) -> String {
+    format!(
+        "{} entries, {}",
+        tally.entries,
+        human_size(tally.bytes as i64)
+    )
+}
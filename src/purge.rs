@@ -0,0 +1,162 @@
+use crate::config::Config;
+use crate::store::StoreData;
+use crate::tags;
+use crate::util::{normalize_path_allow_missing, path_to_string};
+use anyhow::Result;
+use std::collections::HashSet;
+
+/// Removes soft-deleted entries (optionally only those of `root_id`) that
+/// were deleted before `cutoff`, or all of them when `cutoff` is `None`,
+/// together with their tag links. Tags left without links are dropped, as
+/// when tags or roots are removed. Returns how many entries were removed.
+///
+/// Entries deleted before deletion times were recorded are stamped `now`
+/// first, so their age counts from the first purge that sees them.
+pub fn purge_deleted(
+    store: &mut StoreData,
+    cutoff: Option<i64>,
+    root_id: Option<i64>,
+    now: i64,
+) -> usize {
+    let mut removed = HashSet::new();
+    for file in &mut store.files {
+        if file.status != "deleted" || root_id.is_some_and(|id| id != file.root_id) {
+            continue;
+        }
+        let deleted_at = *file.deleted_at.get_or_insert(now);
+        if cutoff.is_none_or(|cutoff| deleted_at < cutoff) {
+            removed.insert(file.id);
+        }
+    }
+    if removed.is_empty() {
+        return 0;
+    }
+    store.files.retain(|f| !removed.contains(&f.id));
+    store.file_tags.retain(|ft| !removed.contains(&ft.file_id));
+    tags::prune_orphans(store);
+    removed.len()
+}
+
+/// Applies `deleted_retention_days` (0 keeps deleted entries forever).
+pub fn apply_retention(store: &mut StoreData, cfg: &Config, now: i64) -> usize {
+    if cfg.deleted_retention_days == 0 {
+        return 0;
+    }
+    let cutoff = now - i64::from(cfg.deleted_retention_days) * 86400;
+    purge_deleted(store, Some(cutoff), None, now)
+}
+
+/// Id of the indexed root at `root`, for `catalog purge --root`.
+pub fn resolve_root(store: &StoreData, root: &str) -> Result<i64> {
+    let path = path_to_string(&normalize_path_allow_missing(root)?);
+    match store.roots.iter().find(|r| r.path == path) {
+        Some(entry) => Ok(entry.id),
+        None => anyhow::bail!("root not indexed: {}", path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::{FileEntry, FileTagEntry, TagEntry};
+
+    const DAY: i64 = 86400;
+
+    fn file(id: i64, root_id: i64, deleted_at: Option<i64>, status: &str) -> FileEntry {
+        FileEntry {
+            id,
+            root_id,
+            rel_path: format!("{}.txt", id),
+            abs_path: format!("/root{}/{}.txt", root_id, id),
+            is_dir: false,
+            is_symlink: false,
+            size: 1,
+            mtime: 0,
+            ext: Some("txt".to_string()),
+            status: status.to_string(),
+            last_seen_run: 1,
+            dev: None,
+            ino: None,
+            prev_path: None,
+            deleted_at,
+        }
+    }
+
+    fn sample(now: i64) -> StoreData {
+        let mut store = StoreData::new();
+        store.files = vec![
+            file(1, 1, None, "active"),
+            file(2, 1, Some(now - 200 * DAY), "deleted"),
+            file(3, 1, Some(now - 10 * DAY), "deleted"),
+            file(4, 2, Some(now - 400 * DAY), "deleted"),
+            file(5, 2, None, "deleted"),
+        ];
+        store.tags = vec![
+            TagEntry {
+                id: 1,
+                name: "old".to_string(),
+            },
+            TagEntry {
+                id: 2,
+                name: "keep".to_string(),
+            },
+        ];
+        store.file_tags = vec![
+            FileTagEntry {
+                file_id: 2,
+                tag_id: 1,
+            },
+            FileTagEntry {
+                file_id: 3,
+                tag_id: 2,
+            },
+        ];
+        store
+    }
+
+    fn ids(store: &StoreData) -> Vec<i64> {
+        store.files.iter().map(|f| f.id).collect()
+    }
+
+    #[test]
+    fn retention_drops_old_deletions_and_their_tag_links() {
+        let now = 1_000 * DAY;
+        let mut store = sample(now);
+        let mut cfg = Config {
+            deleted_retention_days: 0,
+            ..Config::default()
+        };
+        assert_eq!(apply_retention(&mut store, &cfg, now), 0);
+
+        cfg.deleted_retention_days = 90;
+        assert_eq!(apply_retention(&mut store, &cfg, now), 2);
+        assert_eq!(ids(&store), vec![1, 3, 5]);
+        assert_eq!(store.file_tags.len(), 1);
+        assert_eq!(store.file_tags[0].file_id, 3);
+        // The tag that lost its last link goes with it.
+        assert_eq!(store.tags.len(), 1);
+        assert_eq!(store.tags[0].name, "keep");
+        // Deleted before times were recorded: the clock starts now.
+        assert_eq!(store.files[2].deleted_at, Some(now));
+        assert_eq!(store.files[0].deleted_at, None);
+    }
+
+    #[test]
+    fn purge_filters_by_age_and_root() {
+        let now = 1_000 * DAY;
+        let mut store = sample(now);
+        assert_eq!(
+            purge_deleted(&mut store, Some(now - 300 * DAY), Some(1), now),
+            0
+        );
+        assert_eq!(
+            purge_deleted(&mut store, Some(now - 5 * DAY), Some(1), now),
+            2
+        );
+        assert_eq!(ids(&store), vec![1, 4, 5]);
+        assert_eq!(purge_deleted(&mut store, None, None, now), 2);
+        assert_eq!(ids(&store), vec![1]);
+        assert!(store.file_tags.is_empty());
+        assert!(store.tags.is_empty());
+    }
+}
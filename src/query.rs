@@ -0,0 +1,756 @@
+use crate::rank;
+use crate::store::{FileRef, StoreData};
+use crate::tags;
+use crate::util::{normalize_path_allow_missing, path_to_string};
+use anyhow::{Context, Result};
+use chrono::{Local, NaiveDate, TimeZone};
+use std::cell::OnceCell;
+use std::collections::HashSet;
+
+/// Field names a `field:` prefix is recognised for; any other `word:` (a
+/// time like `10:30`, a drive like `C:`) is searched for as text.
+const FIELD_NAMES: [&str; 8] = [
+    "name", "path", "ext", "size", "modified", "mtime", "tag", "root",
+];
+
+/// Parsed search query. Terms next to each other are ANDed; `OR`, `NOT`/`-`
+/// and parentheses work as usual, with NOT binding tighter than AND, and AND
+/// tighter than OR.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    And(Vec<Expr>),
+    Or(Vec<Expr>),
+    Not(Box<Expr>),
+    Term(Term),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Term {
+    /// Bare word: substring of the full path. When nothing matches that way,
+    /// search retries with fuzzy (subsequence) matching on the file name.
+    Text(String),
+    Name(String),
+    Path(String),
+    Ext(Vec<String>),
+    Size(Cmp, i64),
+    Modified(Cmp, When),
+    Tag(String),
+    Root(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Cmp {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum When {
+    /// Relative age in seconds (`30d`); comparisons are on age, so `<30d`
+    /// means "modified within the last 30 days".
+    Ago(i64),
+    /// Local calendar day; comparisons are on the date.
+    Date(NaiveDate),
+}
+
+impl Expr {
+    /// Matches every entry.
+    pub fn all() -> Self {
+        Expr::And(Vec::new())
+    }
+
+    /// Text the results are ranked by: bare words and `name:` terms that are
+    /// not negated.
+    pub fn rank_terms(&self) -> Vec<String> {
+        let mut out = Vec::new();
+        self.collect_rank_terms(&mut out);
+        out
+    }
+
+    fn collect_rank_terms(&self, out: &mut Vec<String>) {
+        match self {
+            Expr::And(parts) | Expr::Or(parts) => {
+                for part in parts {
+                    part.collect_rank_terms(out);
+                }
+            }
+            Expr::Not(_) => {}
+            Expr::Term(Term::Text(text) | Term::Name(text)) => out.push(text.clone()),
+            Expr::Term(_) => {}
+        }
+    }
+}
+
+/// The classic `search`/`recent` flags, expressed as query terms.
+#[derive(Debug, Default)]
+pub struct Flags {
+    pub ext: Option<String>,
+    pub after: Option<String>,
+    pub before: Option<String>,
+    pub min_size: Option<u64>,
+    pub max_size: Option<u64>,
+    pub root: Option<String>,
+    pub tags: Vec<String>,
+}
+
+impl Flags {
+    pub fn terms(&self) -> Result<Vec<Expr>> {
+        let mut terms = Vec::new();
+        if let Some(ext) = &self.ext {
+            let exts = parse_ext_list(ext);
+            if !exts.is_empty() {
+                terms.push(Term::Ext(exts));
+            }
+        }
+        if let Some(after) = &self.after {
+            terms.push(Term::Modified(Cmp::Ge, When::Date(parse_date(after)?)));
+        }
+        if let Some(before) = &self.before {
+            terms.push(Term::Modified(Cmp::Le, When::Date(parse_date(before)?)));
+        }
+        if let Some(min) = self.min_size {
+            terms.push(Term::Size(Cmp::Ge, min as i64));
+        }
+        if let Some(max) = self.max_size {
+            terms.push(Term::Size(Cmp::Le, max as i64));
+        }
+        if let Some(root) = &self.root {
+            terms.push(Term::Root(root.clone()));
+        }
+        for tag in &self.tags {
+            terms.push(Term::Tag(tags::normalize_name(tag)?));
+        }
+        Ok(terms.into_iter().map(Expr::Term).collect())
+    }
+}
+
+/// Parses `query` and ANDs it with the terms implied by `flags`.
+pub fn build(query: &str, flags: &Flags) -> Result<Expr> {
+    let mut parts = vec![parse(query)?];
+    parts.extend(flags.terms()?);
+    Ok(simplify(Expr::And(parts)))
+}
+
+pub fn parse(input: &str) -> Result<Expr> {
+    let tokens = tokenize(input)?;
+    let mut parser = Parser { tokens, pos: 0 };
+    let expr = parser.or_expr()?;
+    if parser.pos < parser.tokens.len() {
+        anyhow::bail!("unexpected ')' in query");
+    }
+    Ok(expr)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    And,
+    Or,
+    Not,
+    Word {
+        field: Option<String>,
+        value: String,
+    },
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        if c == '(' || c == ')' {
+            chars.next();
+            tokens.push(if c == '(' { Token::Open } else { Token::Close });
+            continue;
+        }
+        if c == '-' {
+            chars.next();
+            if chars.peek().is_some_and(|n| !n.is_whitespace()) {
+                tokens.push(Token::Not);
+                continue;
+            }
+            tokens.push(Token::Word {
+                field: None,
+                value: "-".to_string(),
+            });
+            continue;
+        }
+
+        let mut value = String::new();
+        let mut field = None;
+        let mut quoted = false;
+        let mut in_quotes = false;
+        while let Some(&c) = chars.peek() {
+            if c == '"' {
+                in_quotes = !in_quotes;
+                quoted = true;
+                chars.next();
+                continue;
+            }
+            if !in_quotes && (c.is_whitespace() || c == '(' || c == ')') {
+                break;
+            }
+            if c == ':'
+                && !in_quotes
+                && !quoted
+                && field.is_none()
+                && FIELD_NAMES.contains(&value.to_lowercase().as_str())
+            {
+                field = Some(std::mem::take(&mut value).to_lowercase());
+                chars.next();
+                continue;
+            }
+            value.push(c);
+            chars.next();
+        }
+        if in_quotes {
+            anyhow::bail!("unterminated quote in query");
+        }
+        if !quoted && field.is_none() {
+            match value.as_str() {
+                "AND" => {
+                    tokens.push(Token::And);
+                    continue;
+                }
+                "OR" => {
+                    tokens.push(Token::Or);
+                    continue;
+                }
+                "NOT" => {
+                    tokens.push(Token::Not);
+                    continue;
+                }
+                _ => {}
+            }
+        }
+        tokens.push(Token::Word { field, value });
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn or_expr(&mut self) -> Result<Expr> {
+        let mut parts = vec![self.and_expr()?];
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            let next = self.and_expr()?;
+            if next == Expr::all() {
+                anyhow::bail!("OR must be between two terms");
+            }
+            parts.push(next);
+        }
+        Ok(simplify(Expr::Or(parts)))
+    }
+
+    fn and_expr(&mut self) -> Result<Expr> {
+        let mut parts = Vec::new();
+        loop {
+            match self.peek() {
+                None | Some(Token::Close) | Some(Token::Or) => break,
+                Some(Token::And) => {
+                    self.pos += 1;
+                    if matches!(self.peek(), None | Some(Token::Close) | Some(Token::Or)) {
+                        anyhow::bail!("AND must be followed by a term");
+                    }
+                }
+                _ => parts.push(self.unary()?),
+            }
+        }
+        if parts.is_empty() && self.peek() == Some(&Token::Or) {
+            anyhow::bail!("OR must be between two terms");
+        }
+        Ok(simplify(Expr::And(parts)))
+    }
+
+    fn unary(&mut self) -> Result<Expr> {
+        let Some(token) = self.tokens.get(self.pos).cloned() else {
+            anyhow::bail!("query ended unexpectedly");
+        };
+        self.pos += 1;
+        match token {
+            Token::Not => {
+                if matches!(self.peek(), None | Some(Token::Close) | Some(Token::Or)) {
+                    anyhow::bail!("NOT must be followed by a term");
+                }
+                Ok(Expr::Not(Box::new(self.unary()?)))
+            }
+            Token::Open => {
+                let inner = self.or_expr()?;
+                if self.peek() != Some(&Token::Close) {
+                    anyhow::bail!("missing ')' in query");
+                }
+                self.pos += 1;
+                Ok(inner)
+            }
+            Token::Word { field, value } => Ok(Expr::Term(parse_term(field.as_deref(), &value)?)),
+            Token::Close | Token::And | Token::Or => unreachable!("handled by and_expr"),
+        }
+    }
+}
+
+fn simplify(expr: Expr) -> Expr {
+    match expr {
+        Expr::And(parts) => {
+            let mut flat = Vec::new();
+            for part in parts {
+                match part {
+                    Expr::And(inner) => flat.extend(inner),
+                    other => flat.push(other),
+                }
+            }
+            if flat.len() == 1 {
+                flat.pop().unwrap()
+            } else {
+                Expr::And(flat)
+            }
+        }
+        Expr::Or(mut parts) if parts.len() == 1 => parts.pop().unwrap(),
+        other => other,
+    }
+}
+
+fn parse_term(field: Option<&str>, value: &str) -> Result<Term> {
+    let Some(field) = field else {
+        return Ok(Term::Text(value.to_lowercase()));
+    };
+    if value.is_empty() {
+        anyhow::bail!("missing value for '{}:'", field);
+    }
+    let term = match field {
+        "name" => Term::Name(value.to_lowercase()),
+        "path" => Term::Path(value.to_lowercase()),
+        "ext" => {
+            let exts = parse_ext_list(value);
+            if exts.is_empty() {
+                anyhow::bail!("missing value for 'ext:'");
+            }
+            Term::Ext(exts)
+        }
+        "size" => {
+            let (cmp, rest) = split_cmp(value);
+            Term::Size(cmp, parse_size(rest)?)
+        }
+        "modified" | "mtime" => {
+            let (cmp, rest) = split_cmp(value);
+            let when = parse_when(rest)?;
+            // An age without an operator means "within": `30d` is `<=30d`.
+            let cmp = match when {
+                When::Ago(_) if cmp == Cmp::Eq => Cmp::Le,
+                _ => cmp,
+            };
+            Term::Modified(cmp, when)
+        }
+        "tag" => Term::Tag(tags::normalize_name(value)?),
+        "root" => Term::Root(value.to_string()),
+        other => unreachable!("'{}:' is not in FIELD_NAMES", other),
+    };
+    Ok(term)
+}
+
+fn split_cmp(value: &str) -> (Cmp, &str) {
+    for (prefix, cmp) in [
+        ("<=", Cmp::Le),
+        (">=", Cmp::Ge),
+        ("<", Cmp::Lt),
+        (">", Cmp::Gt),
+        ("=", Cmp::Eq),
+    ] {
+        if let Some(rest) = value.strip_prefix(prefix) {
+            return (cmp, rest);
+        }
+    }
+    (Cmp::Eq, value)
+}
+
+fn parse_ext_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|s| s.trim().trim_start_matches('.').to_lowercase())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+/// Parses sizes such as `512`, `10KB`, `1.5G` (binary units, like the
+/// human-readable output).
+fn parse_size(value: &str) -> Result<i64> {
+    let split = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number
+        .parse()
+        .with_context(|| format!("invalid size '{}' (expected e.g. 500KB or 10MB)", value))?;
+    let multiplier: f64 = match unit.to_ascii_uppercase().as_str() {
+        "" | "B" => 1.0,
+        "K" | "KB" => 1024.0,
+        "M" | "MB" => 1024.0 * 1024.0,
+        "G" | "GB" => 1024.0 * 1024.0 * 1024.0,
+        "T" | "TB" => 1024.0 * 1024.0 * 1024.0 * 1024.0,
+        _ => anyhow::bail!(
+            "invalid size unit '{}' (expected B, KB, MB, GB or TB)",
+            unit
+        ),
+    };
+    Ok((number * multiplier).round() as i64)
+}
+
+/// Parses `YYYY-MM-DD` or a relative age such as `12h`, `30d`, `2w`, `1y`.
+fn parse_when(value: &str) -> Result<When> {
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Ok(When::Date(date));
+    }
+    match age_seconds(value) {
+        Some(seconds) => Ok(When::Ago(seconds)),
+        None => anyhow::bail!(
+            "invalid time '{}' (expected YYYY-MM-DD or an age like 12h, 30d, 2w, 1y)",
+            value
+        ),
+    }
+}
+
+/// Seconds in an age such as `12h`, `30d`, `2w`, `1y`.
+pub fn age_seconds(value: &str) -> Option<i64> {
+    let split = value.find(|c: char| !c.is_ascii_digit())?;
+    let (number, unit) = value.split_at(split);
+    let number: i64 = number.parse().ok()?;
+    let seconds = match unit {
+        "h" => 3600,
+        "d" => 86400,
+        "w" => 7 * 86400,
+        "y" => 365 * 86400,
+        _ => return None,
+    };
+    Some(number * seconds)
+}
+
+/// Resolves a `--since` style value (`YYYY-MM-DD` or an age like `2d`) to
+/// unix seconds.
+pub fn parse_since(value: &str, now: i64) -> Result<i64> {
+    Ok(match parse_when(value)? {
+        When::Date(date) => day_start(date),
+        When::Ago(seconds) => now - seconds,
+    })
+}
+
+pub fn parse_date(date: &str) -> Result<NaiveDate> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d").with_context(|| "invalid date, expected YYYY-MM-DD")
+}
+
+fn day_start(date: NaiveDate) -> i64 {
+    Local
+        .from_local_datetime(&date.and_hms_opt(0, 0, 0).unwrap())
+        .earliest()
+        .map(|dt| dt.timestamp())
+        .unwrap_or_else(|| date.and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp())
+}
+
+/// A query resolved against a store (tags and roots looked up, relative
+/// times anchored), ready to test entries.
+pub struct Filter {
+    node: Node,
+    rank_terms: Vec<String>,
+    fuzzy: bool,
+}
+
+enum Node {
+    All(Vec<Node>),
+    Any(Vec<Node>),
+    Not(Box<Node>),
+    Text(String),
+    Path(String),
+    Name(String),
+    Ext(HashSet<String>),
+    Size(Cmp, i64),
+    /// mtime in `[from, until)`.
+    Modified(Option<i64>, Option<i64>),
+    Files(HashSet<i64>),
+    Root(Option<i64>),
+}
+
+impl Filter {
+    pub fn compile(expr: &Expr, store: &StoreData) -> Result<Self> {
+        Self::compile_at(expr, store, Local::now().timestamp())
+    }
+
+    pub fn compile_at(expr: &Expr, store: &StoreData, now: i64) -> Result<Self> {
+        Ok(Self {
+            node: compile_node(expr, store, now)?,
+            rank_terms: expr.rank_terms(),
+            fuzzy: false,
+        })
+    }
+
+    /// Also accept bare words whose letters appear in order in the file name.
+    pub fn with_fuzzy(mut self) -> Self {
+        self.fuzzy = true;
+        self
+    }
+
+    pub fn has_rank_terms(&self) -> bool {
+        !self.rank_terms.is_empty()
+    }
+
+    pub fn matches(&self, file: &FileRef<'_>) -> bool {
+        eval(&self.node, file, &OnceCell::new(), self.fuzzy)
+    }
+
+    /// Match-quality score (see `rank::score`) when the entry matches.
+    pub fn score(&self, file: &FileRef<'_>) -> Option<u32> {
+        let path_lc = OnceCell::new();
+        if !eval(&self.node, file, &path_lc, self.fuzzy) {
+            return None;
+        }
+        Some(rank::score(
+            &self.rank_terms,
+            lowercase_path(file, &path_lc),
+        ))
+    }
+}
+
+fn compile_node(expr: &Expr, store: &StoreData, now: i64) -> Result<Node> {
+    let node = match expr {
+        Expr::And(parts) => Node::All(
+            parts
+                .iter()
+                .map(|p| compile_node(p, store, now))
+                .collect::<Result<_>>()?,
+        ),
+        Expr::Or(parts) => Node::Any(
+            parts
+                .iter()
+                .map(|p| compile_node(p, store, now))
+                .collect::<Result<_>>()?,
+        ),
+        Expr::Not(inner) => Node::Not(Box::new(compile_node(inner, store, now)?)),
+        Expr::Term(term) => match term {
+            Term::Text(text) => Node::Text(text.clone()),
+            Term::Path(text) => Node::Path(text.clone()),
+            Term::Name(text) => Node::Name(text.clone()),
+            Term::Ext(exts) => Node::Ext(exts.iter().cloned().collect()),
+            Term::Size(cmp, size) => Node::Size(*cmp, *size),
+            Term::Modified(cmp, when) => {
+                let (from, until) = time_range(*cmp, *when, now);
+                Node::Modified(from, until)
+            }
+            Term::Tag(name) => Node::Files(
+                tags::files_with_all(store, std::slice::from_ref(name))?.unwrap_or_default(),
+            ),
+            Term::Root(root) => {
+                let root = path_to_string(&normalize_path_allow_missing(root)?);
+                Node::Root(store.roots.iter().find(|r| r.path == root).map(|r| r.id))
+            }
+        },
+    };
+    Ok(node)
+}
+
+fn time_range(cmp: Cmp, when: When, now: i64) -> (Option<i64>, Option<i64>) {
+    match when {
+        When::Date(date) => {
+            let start = day_start(date);
+            let end = day_start(date.succ_opt().unwrap_or(date));
+            match cmp {
+                Cmp::Lt => (None, Some(start)),
+                Cmp::Le => (None, Some(end)),
+                Cmp::Gt => (Some(end), None),
+                Cmp::Ge => (Some(start), None),
+                Cmp::Eq => (Some(start), Some(end)),
+            }
+        }
+        When::Ago(seconds) => {
+            let at = now - seconds;
+            match cmp {
+                Cmp::Lt => (Some(at + 1), None),
+                Cmp::Le | Cmp::Eq => (Some(at), None),
+                Cmp::Gt => (None, Some(at)),
+                Cmp::Ge => (None, Some(at + 1)),
+            }
+        }
+    }
+}
+
+/// The lowercased absolute path, built the first time a node needs it so
+/// that filters on size, extension or date never assemble the path.
+fn lowercase_path<'c>(file: &FileRef<'_>, path_lc: &'c OnceCell<String>) -> &'c str {
+    path_lc.get_or_init(|| file.abs_path().to_lowercase())
+}
+
+fn eval(node: &Node, file: &FileRef<'_>, path_lc: &OnceCell<String>, fuzzy: bool) -> bool {
+    match node {
+        Node::All(parts) => parts.iter().all(|p| eval(p, file, path_lc, fuzzy)),
+        Node::Any(parts) => parts.iter().any(|p| eval(p, file, path_lc, fuzzy)),
+        Node::Not(inner) => !eval(inner, file, path_lc, fuzzy),
+        Node::Text(text) => {
+            let path_lc = lowercase_path(file, path_lc);
+            path_lc.contains(text.as_str()) || (fuzzy && rank::is_fuzzy_match(text, path_lc))
+        }
+        Node::Path(text) => lowercase_path(file, path_lc).contains(text.as_str()),
+        Node::Name(text) => file.name().to_lowercase().contains(text.as_str()),
+        Node::Ext(exts) => file.ext.is_some_and(|ext| exts.contains(ext)),
+        Node::Size(cmp, size) => match cmp {
+            Cmp::Lt => file.size < *size,
+            Cmp::Le => file.size <= *size,
+            Cmp::Gt => file.size > *size,
+            Cmp::Ge => file.size >= *size,
+            Cmp::Eq => file.size == *size,
+        },
+        Node::Modified(from, until) => {
+            from.is_none_or(|ts| file.mtime >= ts) && until.is_none_or(|ts| file.mtime < ts)
+        }
+        Node::Files(ids) => ids.contains(&file.id),
+        Node::Root(root_id) => *root_id == Some(file.root_id),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::FileEntry;
+
+    fn file(id: i64, abs_path: &str, size: i64, mtime: i64) -> FileEntry {
+        FileEntry {
+            id,
+            root_id: 1,
+            rel_path: abs_path.trim_start_matches('/').to_string(),
+            abs_path: abs_path.to_string(),
+            is_dir: false,
+            is_symlink: false,
+            size,
+            mtime,
+            ext: abs_path.rsplit_once('.').map(|(_, e)| e.to_string()),
+            status: "active".to_string(),
+            last_seen_run: 1,
+            dev: None,
+            ino: None,
+            prev_path: None,
+            deleted_at: None,
+        }
+    }
+
+    #[test]
+    fn parse_handles_operators_and_grouping() {
+        let expr =
+            parse("name:report ext:pdf,.DOCX size:>10MB -path:archive (tag:work OR foo)").unwrap();
+        assert_eq!(
+            expr,
+            Expr::And(vec![
+                Expr::Term(Term::Name("report".to_string())),
+                Expr::Term(Term::Ext(vec!["pdf".to_string(), "docx".to_string()])),
+                Expr::Term(Term::Size(Cmp::Gt, 10 * 1024 * 1024)),
+                Expr::Not(Box::new(Expr::Term(Term::Path("archive".to_string())))),
+                Expr::Or(vec![
+                    Expr::Term(Term::Tag("work".to_string())),
+                    Expr::Term(Term::Text("foo".to_string())),
+                ]),
+            ])
+        );
+        assert_eq!(
+            parse("a OR b c").unwrap(),
+            Expr::Or(vec![
+                Expr::Term(Term::Text("a".to_string())),
+                Expr::And(vec![
+                    Expr::Term(Term::Text("b".to_string())),
+                    Expr::Term(Term::Text("c".to_string())),
+                ]),
+            ])
+        );
+        assert_eq!(
+            parse("name:\"my report\" \"x:y\"").unwrap(),
+            Expr::And(vec![
+                Expr::Term(Term::Name("my report".to_string())),
+                Expr::Term(Term::Text("x:y".to_string())),
+            ])
+        );
+        assert_eq!(parse("").unwrap(), Expr::all());
+        assert!(parse("(a").is_err());
+        assert!(parse("a)").is_err());
+        assert!(parse("OR a").is_err());
+        assert!(parse("a OR").is_err());
+        // Unknown fields are plain text; a bare age means "at most".
+        assert_eq!(
+            parse("10:30 C:Stuff").unwrap(),
+            Expr::And(vec![
+                Expr::Term(Term::Text("10:30".to_string())),
+                Expr::Term(Term::Text("c:stuff".to_string())),
+            ])
+        );
+        assert_eq!(
+            parse("Name:a:b").unwrap(),
+            Expr::Term(Term::Name("a:b".to_string()))
+        );
+        assert_eq!(
+            parse("modified:30d").unwrap(),
+            parse("modified:<=30d").unwrap()
+        );
+        assert!(parse("size:>10XB").is_err());
+        assert!(parse("modified:<30q").is_err());
+    }
+
+    #[test]
+    fn filter_matches_entries() {
+        let now = 1_700_000_000;
+        let mut store = StoreData::new();
+        store.files.push(file(
+            1,
+            "/docs/Report-2024.pdf",
+            20 * 1024 * 1024,
+            now - 86400,
+        ));
+        store.files.push(file(
+            2,
+            "/docs/archive/report-old.pdf",
+            20 * 1024 * 1024,
+            now - 86400,
+        ));
+        store
+            .files
+            .push(file(3, "/docs/notes.txt", 10, now - 90 * 86400));
+        tags::add_tag(&mut store, "work", &[3]);
+
+        let ids = |query: &str| -> Vec<i64> {
+            // A leading `~` turns on the fuzzy fallback used by search.
+            let (fuzzy, query) = match query.strip_prefix('~') {
+                Some(rest) => (true, rest),
+                None => (false, query),
+            };
+            let mut filter = Filter::compile_at(&parse(query).unwrap(), &store, now).unwrap();
+            if fuzzy {
+                filter = filter.with_fuzzy();
+            }
+            store
+                .files
+                .iter()
+                .filter(|f| filter.matches(&f.view()))
+                .map(|f| f.id)
+                .collect()
+        };
+
+        assert_eq!(
+            ids("name:report ext:pdf size:>10MB modified:<30d -path:archive"),
+            vec![1]
+        );
+        assert_eq!(ids("report OR tag:work"), vec![1, 2, 3]);
+        assert_eq!(ids("NOT (ext:pdf) modified:>30d"), vec![3]);
+        assert_eq!(ids("name:docs"), Vec::<i64>::new());
+        assert_eq!(ids("rprt"), Vec::<i64>::new());
+        assert_eq!(ids("~rprt"), vec![1, 2]);
+        assert_eq!(ids("~name:rprt"), Vec::<i64>::new());
+        assert_eq!(ids("tag:missing OR size:10"), vec![3]);
+    }
+}
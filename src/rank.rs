@@ -0,0 +1,120 @@
+use std::path::MAIN_SEPARATOR;
+
+const EXACT: u32 = 100;
+const PREFIX: u32 = 80;
+const WORD_BOUNDARY: u32 = 60;
+const NAME_SUBSTRING: u32 = 45;
+const PATH_SUBSTRING: u32 = 30;
+const SUBSEQUENCE: u32 = 10;
+
+/// Scores how well `terms` (lowercase) match an entry, judged mostly on the
+/// file name: exact name or stem > name prefix > word-boundary hit > anywhere
+/// in the name > elsewhere in the path > fuzzy subsequence of the name. The
+/// score is the average over terms, in `0..=100`.
+pub fn score(terms: &[String], path_lc: &str) -> u32 {
+    if terms.is_empty() {
+        return 0;
+    }
+    let name = basename(path_lc);
+    let total: u32 = terms.iter().map(|t| term_score(t, name, path_lc)).sum();
+    total / terms.len() as u32
+}
+
+/// Whether `term` occurs in the file name as a subsequence, so `rprt` finds
+/// `report.pdf`.
+pub fn is_fuzzy_match(term: &str, path_lc: &str) -> bool {
+    subsequence_span(term, basename(path_lc)).is_some()
+}
+
+pub fn basename(path: &str) -> &str {
+    path.rsplit(MAIN_SEPARATOR).next().unwrap_or(path)
+}
+
+/// Number of path components, used to prefer shallower entries on ties.
+pub fn depth(path: &str) -> usize {
+    path.matches(MAIN_SEPARATOR).count()
+}
+
+fn term_score(term: &str, name: &str, path_lc: &str) -> u32 {
+    if term.is_empty() {
+        return 0;
+    }
+    let stem = name.split_once('.').map_or(name, |(stem, _)| stem);
+    if name == term || (!stem.is_empty() && stem == term) {
+        return EXACT;
+    }
+    if name.starts_with(term) {
+        return PREFIX;
+    }
+    if let Some(pos) = name.find(term) {
+        if name
+            .match_indices(term)
+            .any(|(i, _)| at_word_start(name, i))
+        {
+            return WORD_BOUNDARY;
+        }
+        // Earlier hits read as more relevant than ones buried in a long name.
+        let penalty = (pos * 10 / name.len().max(1)) as u32;
+        return NAME_SUBSTRING - penalty.min(10);
+    }
+    if path_lc.contains(term) {
+        return PATH_SUBSTRING;
+    }
+    match subsequence_span(term, name) {
+        // Tighter spans (fewer gaps) score higher, up to twice the base.
+        Some(span) => SUBSEQUENCE + (SUBSEQUENCE * term.len() as u32) / span.max(1) as u32,
+        None => 0,
+    }
+}
+
+fn at_word_start(name: &str, idx: usize) -> bool {
+    name[..idx]
+        .chars()
+        .next_back()
+        .is_some_and(|c| !c.is_alphanumeric())
+}
+
+/// Length of the leftmost greedy window of `name` that contains `term` as a
+/// subsequence.
+fn subsequence_span(term: &str, name: &str) -> Option<usize> {
+    let mut wanted = term.chars().peekable();
+    let mut start = None;
+    for (i, c) in name.char_indices() {
+        if wanted.peek() == Some(&c) {
+            start.get_or_insert(i);
+            wanted.next();
+            if wanted.peek().is_none() {
+                return Some(i + c.len_utf8() - start.unwrap_or(0));
+            }
+        }
+    }
+    if term.is_empty() { Some(0) } else { None }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn s(term: &str, path: &str) -> u32 {
+        score(&[term.to_string()], path)
+    }
+
+    #[test]
+    fn match_quality_ordering() {
+        let exact = s("report", "/docs/report.pdf");
+        let prefix = s("report", "/docs/reports-2024.pdf");
+        let boundary = s("report", "/docs/q3-report-final.pdf");
+        let inner = s("report", "/docs/misreporting.txt");
+        let in_path = s("report", "/report/summary.txt");
+        let fuzzy = s("rprt", "/docs/report.pdf");
+        assert!(exact > prefix, "{} > {}", exact, prefix);
+        assert!(prefix > boundary);
+        assert!(boundary > inner);
+        assert!(inner > in_path);
+        assert!(in_path > fuzzy);
+        assert!(fuzzy > 0);
+        assert_eq!(s("xyz", "/docs/report.pdf"), 0);
+        assert!(is_fuzzy_match("rprt", "/docs/report.pdf"));
+        assert!(!is_fuzzy_match("docs", "/docs/report.pdf"));
+    }
+}
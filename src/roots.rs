@@ -1,13 +1,27 @@
-use crate::config::Config;
-use crate::store::{RootEntry, StoreData};
+use crate::config::{Config, RootConfig};
+use crate::store::{RootEntry, RootVolume, StoreData};
+use crate::tags;
 use crate::util::{normalize_path, path_to_string};
+use crate::volume::{self, Volume};
 use anyhow::Result;
 use chrono::Local;
 use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// Where a root can be found right now.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RootState {
+    Online(PathBuf),
+    /// The root's volume is mounted somewhere else; the root lives here now.
+    Remounted(PathBuf),
+    /// The root's volume is not mounted. Carries the volume name.
+    Offline(String),
+    Missing,
+}
 
 pub fn add_roots(cfg: &mut Config, paths: &[String]) -> Result<usize> {
     let mut added = 0;
-    let mut existing: HashSet<String> = cfg.roots.iter().cloned().collect();
+    let mut existing: HashSet<String> = cfg.roots.iter().map(|r| r.path.clone()).collect();
     for p in paths {
         let normalized = match normalize_path(p) {
             Ok(path) => path,
@@ -18,7 +32,7 @@ pub fn add_roots(cfg: &mut Config, paths: &[String]) -> Result<usize> {
         };
         let s = path_to_string(&normalized);
         if existing.insert(s.clone()) {
-            cfg.roots.push(s);
+            cfg.roots.push(RootConfig::from(s));
             added += 1;
         }
     }
@@ -32,7 +46,7 @@ pub fn remove_roots(cfg: &mut Config, paths: &[String]) -> Result<usize> {
         to_remove.insert(path_to_string(&normalized));
     }
     let before = cfg.roots.len();
-    cfg.roots.retain(|p| !to_remove.contains(p));
+    cfg.roots.retain(|r| !to_remove.contains(&r.path));
     Ok(before - cfg.roots.len())
 }
 
@@ -44,24 +58,27 @@ pub fn sync_roots(store: &mut StoreData, cfg: &Config, preset_name: Option<Strin
 
     let now = Local::now().to_rfc3339();
     for root in &cfg.roots {
-        if let Some(id) = existing.get(root) {
+        let one_filesystem = cfg.root_options(&root.path).one_filesystem;
+        if let Some(id) = existing.get(&root.path) {
             if let Some(entry) = store.roots.iter_mut().find(|r| r.id == *id) {
-                entry.one_filesystem = cfg.one_filesystem;
+                entry.one_filesystem = one_filesystem;
             }
         } else {
             let id = store.next_root_id();
             store.roots.push(RootEntry {
                 id,
-                path: root.to_string(),
+                path: root.path.clone(),
                 added_at: now.clone(),
                 preset_name: preset_name.clone(),
                 last_indexed_at: None,
-                one_filesystem: cfg.one_filesystem,
+                one_filesystem,
+                volume: None,
+                scan: None,
             });
         }
     }
 
-    let desired: HashSet<&String> = cfg.roots.iter().collect();
+    let desired: HashSet<&String> = cfg.roots.iter().map(|r| &r.path).collect();
     let removed_root_ids: HashSet<i64> = store
         .roots
         .iter()
@@ -83,23 +100,124 @@ pub fn sync_roots(store: &mut StoreData, cfg: &Config, preset_name: Option<Strin
             .file_tags
             .retain(|ft| !removed_file_ids.contains(&ft.file_id));
 
-        prune_orphan_tags(store);
+        tags::prune_orphans(store);
     }
 
     Ok(())
 }
 
+/// Resolves every root in `roots`, reading the mount table at most once.
+pub fn resolve_roots(roots: &[RootEntry]) -> HashMap<i64, RootState> {
+    let mounted = if roots.iter().any(|r| r.volume.is_some()) {
+        volume::mounted_volumes()
+    } else {
+        Vec::new()
+    };
+    roots
+        .iter()
+        .map(|root| (root.id, resolve_root(root, &mounted)))
+        .collect()
+}
+
+/// Volume names of the roots that are offline, for display. Only roots
+/// tied to a volume whose path is gone are checked, so the mount table is
+/// not read while every root is reachable; an empty mount point left by an
+/// unplugged drive passes for online here (unlike `resolve_roots`).
+pub fn offline_roots(roots: &[RootEntry]) -> HashMap<i64, String> {
+    let gone: Vec<RootEntry> = roots
+        .iter()
+        .filter(|r| r.volume.is_some() && !Path::new(&r.path).exists())
+        .cloned()
+        .collect();
+    if gone.is_empty() {
+        return HashMap::new();
+    }
+    resolve_roots(&gone)
+        .into_iter()
+        .filter_map(|(id, state)| match state {
+            RootState::Offline(name) => Some((id, name)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// A root tied to a volume only counts as online when that volume is what is
+/// mounted there; an empty mount point left behind by an unplugged drive
+/// must not be indexed as if every file had been deleted.
+fn resolve_root(root: &RootEntry, mounted: &[Volume]) -> RootState {
+    let path = PathBuf::from(&root.path);
+    let recorded = match &root.volume {
+        Some(recorded) if !mounted.is_empty() => recorded,
+        _ if path.exists() => return RootState::Online(path),
+        _ => return RootState::Missing,
+    };
+    let is_same = |v: &&Volume| match (&recorded.uuid, &v.uuid) {
+        (Some(a), Some(b)) => a == b,
+        (Some(_), None) => false,
+        (None, _) => recorded.label.is_some() && recorded.label == v.label,
+    };
+    if path.exists() && volume::containing(mounted, &path).is_some_and(|v| is_same(&v)) {
+        return RootState::Online(path);
+    }
+
+    let mut volume_mounted = false;
+    for volume in mounted.iter().filter(is_same) {
+        volume_mounted = true;
+        let candidate = if recorded.rel_path.is_empty() {
+            volume.mount_point.clone()
+        } else {
+            volume.mount_point.join(&recorded.rel_path)
+        };
+        if candidate.exists() {
+            return if candidate == path {
+                RootState::Online(path)
+            } else {
+                RootState::Remounted(candidate)
+            };
+        }
+    }
+    if volume_mounted {
+        RootState::Missing
+    } else {
+        RootState::Offline(recorded.name().to_string())
+    }
+}
+
+/// Identifies the volume `path` is on. Returns `None` for filesystems
+/// without a UUID or label (tmpfs, network shares, unsupported platforms).
+pub fn root_volume(path: &Path) -> Option<RootVolume> {
+    let volume = volume::volume_for(path).filter(Volume::has_identity)?;
+    let rel = path.strip_prefix(&volume.mount_point).ok()?;
+    Some(RootVolume {
+        uuid: volume.uuid,
+        label: volume.label,
+        mount_point: path_to_string(&volume.mount_point),
+        rel_path: path_to_string(rel),
+    })
+}
+
 pub fn print_roots(store: &StoreData, cfg: &Config) -> Result<()> {
     println!("Roots:");
-    for root in &cfg.roots {
-        let last_indexed = store
-            .roots
-            .iter()
-            .find(|r| r.path == *root)
-            .and_then(|r| r.last_indexed_at.clone());
-        match last_indexed {
-            Some(ts) => println!("  {} (last indexed {})", root, ts),
-            None => println!("  {} (never indexed)", root),
+    let states = resolve_roots(&store.roots);
+    for root_cfg in &cfg.roots {
+        let root = &root_cfg.path;
+        let entry = store.roots.iter().find(|r| r.path == *root);
+        let last_indexed = match entry.and_then(|r| r.last_indexed_at.as_deref()) {
+            Some(ts) => format!("last indexed {}", ts),
+            None => "never indexed".to_string(),
+        };
+        match entry.and_then(|r| states.get(&r.id)) {
+            Some(RootState::Offline(name)) => {
+                println!("  {} (offline: volume {} not mounted; {})", root, name, last_indexed)
+            }
+            Some(RootState::Remounted(path)) => {
+                println!("  {} (now at {}; {})", root, path.display(), last_indexed)
+            }
+            _ => println!("  {} ({})", root, last_indexed),
+        }
+        let overrides = describe_overrides(cfg, root_cfg);
+        if !overrides.is_empty() {
+            println!("    {}", overrides.join(", "));
         }
     }
 
@@ -109,21 +227,42 @@ pub fn print_roots(store: &StoreData, cfg: &Config) -> Result<()> {
     }
     println!("\ninclude_hidden: {}", cfg.include_hidden);
     println!("one_filesystem: {}", cfg.one_filesystem);
+    println!("ignore files: {}", cfg.ignore_files().describe());
     Ok(())
 }
 
-fn prune_orphan_tags(store: &mut StoreData) {
-    let mut used = HashSet::new();
-    for ft in &store.file_tags {
-        used.insert(ft.tag_id);
+/// A root's settings that differ from the global ones, as `key = value`.
+fn describe_overrides(cfg: &Config, root: &RootConfig) -> Vec<String> {
+    let mut out = Vec::new();
+    if !root.excludes.is_empty() {
+        out.push(format!("excludes += [{}]", root.excludes.join(", ")));
+    }
+    if let Some(hidden) = root.include_hidden {
+        out.push(format!("include_hidden = {}", hidden));
+    }
+    if let Some(one_fs) = root.one_filesystem {
+        out.push(format!("one_filesystem = {}", one_fs));
+    }
+    if let Some(depth) = root.max_depth {
+        out.push(format!("max_depth = {}", depth));
+    }
+    if root.follow_symlinks {
+        out.push("follow_symlinks = true".to_string());
+    }
+    if let Some(interval) = &root.interval {
+        out.push(format!("interval = {}", interval));
+    }
+    let ignore_files = root.ignore_files(cfg.ignore_files());
+    if ignore_files != cfg.ignore_files() {
+        out.push(format!("ignore files: {}", ignore_files.describe()));
     }
-    store.tags.retain(|t| used.contains(&t.id));
+    out
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::{Config, OutputMode};
+    use crate::config::Config;
 
     #[test]
     fn sync_roots_prunes_removed_root_data() {
@@ -135,6 +274,8 @@ mod tests {
             preset_name: None,
             last_indexed_at: None,
             one_filesystem: true,
+            volume: None,
+            scan: None,
         });
         store.roots.push(RootEntry {
             id: 2,
@@ -143,6 +284,8 @@ mod tests {
             preset_name: None,
             last_indexed_at: None,
             one_filesystem: true,
+            volume: None,
+            scan: None,
         });
         store.files.push(crate::store::FileEntry {
             id: 10,
@@ -156,6 +299,10 @@ mod tests {
             ext: Some("txt".to_string()),
             status: "active".to_string(),
             last_seen_run: 1,
+            dev: None,
+            ino: None,
+            prev_path: None,
+            deleted_at: None,
         });
         store.files.push(crate::store::FileEntry {
             id: 11,
@@ -169,6 +316,10 @@ mod tests {
             ext: Some("txt".to_string()),
             status: "active".to_string(),
             last_seen_run: 1,
+            dev: None,
+            ino: None,
+            prev_path: None,
+            deleted_at: None,
         });
         store.tags.push(crate::store::TagEntry {
             id: 1,
@@ -188,12 +339,9 @@ mod tests {
         });
 
         let cfg = Config {
-            version: 1,
-            output: OutputMode::Plain,
-            include_hidden: false,
-            one_filesystem: true,
-            roots: vec!["/tmp/root-a".to_string()],
+            roots: vec!["/tmp/root-a".to_string().into()],
             excludes: vec![],
+            ..Config::default()
         };
 
         sync_roots(&mut store, &cfg, None).unwrap();
@@ -207,4 +355,69 @@ mod tests {
         assert_eq!(store.tags.len(), 1);
         assert_eq!(store.tags[0].name, "keep");
     }
+
+    fn volume(uuid: &str, mount_point: &Path) -> Volume {
+        Volume {
+            uuid: Some(uuid.to_string()),
+            label: None,
+            mount_point: mount_point.to_path_buf(),
+        }
+    }
+
+    #[test]
+    fn resolve_root_follows_volume_identity() {
+        let dir = std::env::temp_dir()
+            .join(format!("catalog_test_volumes_{}", std::process::id()));
+        let mnt_a = dir.join("mnt_a");
+        let mnt_b = dir.join("mnt_b");
+        std::fs::create_dir_all(&mnt_a).unwrap();
+        std::fs::create_dir_all(mnt_b.join("photos")).unwrap();
+
+        let system = volume("system", Path::new("/"));
+        let backup_at_b = volume("backup", &mnt_b);
+        let mut root = RootEntry {
+            id: 1,
+            path: path_to_string(&mnt_a.join("photos")),
+            added_at: "now".to_string(),
+            preset_name: None,
+            last_indexed_at: None,
+            one_filesystem: true,
+            volume: Some(RootVolume {
+                uuid: Some("backup".to_string()),
+                label: Some("Backup".to_string()),
+                mount_point: path_to_string(&mnt_a),
+                rel_path: "photos".to_string(),
+            }),
+            scan: None,
+        };
+
+        let offline = RootState::Offline("Backup".to_string());
+        assert_eq!(resolve_root(&root, std::slice::from_ref(&system)), offline);
+        assert_eq!(
+            resolve_root(&root, &[system.clone(), backup_at_b.clone()]),
+            RootState::Remounted(mnt_b.join("photos"))
+        );
+        let elsewhere = volume("backup", &dir.join("mnt_c"));
+        assert_eq!(
+            resolve_root(&root, &[system.clone(), elsewhere]),
+            RootState::Missing
+        );
+
+        std::fs::create_dir_all(mnt_a.join("photos")).unwrap();
+        let backup_at_a = volume("backup", &mnt_a);
+        assert_eq!(
+            resolve_root(&root, &[system.clone(), backup_at_a]),
+            RootState::Online(mnt_a.join("photos"))
+        );
+
+        // A leftover, empty mount point is not the volume.
+        root.path = path_to_string(&mnt_a);
+        root.volume.as_mut().unwrap().rel_path = String::new();
+        assert_eq!(resolve_root(&root, std::slice::from_ref(&system)), offline);
+
+        root.volume = None;
+        assert_eq!(resolve_root(&root, &[system]), RootState::Online(mnt_a));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
 }
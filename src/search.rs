@@ -1,9 +1,35 @@
 use crate::config::Config;
-use crate::store::Store;
-use crate::util::{normalize_path_allow_missing, path_to_string};
-use anyhow::{Context, Result};
-use chrono::{Local, NaiveDate, TimeZone};
-use std::collections::{HashMap, HashSet};
+use crate::query::{Expr, Filter};
+use crate::rank;
+use crate::roots;
+use crate::store::{FileRef, Store};
+use crate::tags;
+use anyhow::Result;
+use chrono::Local;
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+pub enum SortOrder {
+    Score,
+    Mtime,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum StatusFilter {
+    Active,
+    Deleted,
+    All,
+}
+
+impl StatusFilter {
+    pub fn matches(&self, status: &str) -> bool {
+        match self {
+            StatusFilter::Active => status == "active",
+            StatusFilter::Deleted => status == "deleted",
+            StatusFilter::All => true,
+        }
+    }
+}
 
 #[derive(Debug, serde::Serialize)]
 pub struct SearchEntry {
@@ -15,117 +41,78 @@ pub struct SearchEntry {
     pub is_symlink: bool,
     pub ext: Option<String>,
     pub root: String,
+    /// `offline` when the entry's volume is not mounted, otherwise the
+    /// stored status.
     pub status: String,
+    /// Name of the unmounted volume for `offline` entries.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub volume: Option<String>,
+    /// When a `deleted` entry was found missing, if recorded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<i64>,
+    pub tags: Vec<String>,
+    /// Match quality (`search` only); higher is better.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub score: Option<u32>,
 }
 
 pub fn search(
     store: &Store,
-    _cfg: &Config,
-    query: &str,
-    ext: Option<&str>,
-    after: Option<&str>,
-    before: Option<&str>,
-    min_size: Option<u64>,
-    max_size: Option<u64>,
-    root: Option<&str>,
+    cfg: &Config,
+    query: &Expr,
+    sort: SortOrder,
+    status: StatusFilter,
 ) -> Result<Vec<SearchEntry>> {
-    let query_lc = query.to_lowercase();
-    let mut root_filter: Option<i64> = None;
-    if let Some(root) = root {
-        let normalized = normalize_path_allow_missing(root)?;
-        let root_str = path_to_string(&normalized);
-        if let Some(entry) = store.data.roots.iter().find(|r| r.path == root_str) {
-            root_filter = Some(entry.id);
-        } else {
-            return Ok(Vec::new());
-        }
-    }
-
-    let ext_set: Option<HashSet<String>> = ext.and_then(|exts| {
-        let set: HashSet<String> = exts
-            .split(',')
-            .map(|s| s.trim().to_lowercase())
-            .filter(|s| !s.is_empty())
-            .collect();
-        if set.is_empty() {
-            None
-        } else {
-            Some(set)
-        }
-    });
+    search_top(store, cfg, query, sort, status, None)
+}
 
-    let after_ts = match after {
-        Some(v) => Some(parse_date_start(v)?),
-        None => None,
-    };
-    let before_ts = match before {
-        Some(v) => Some(parse_date_end_exclusive(v)?),
-        None => None,
+/// Like `search`, but only materializes the best `limit` results.
+pub fn search_top(
+    store: &Store,
+    _cfg: &Config,
+    query: &Expr,
+    sort: SortOrder,
+    status: StatusFilter,
+    limit: Option<usize>,
+) -> Result<Vec<SearchEntry>> {
+    let filter = Filter::compile(query, &store.data)?;
+    let candidates = store.path_index().candidates(query);
+    let mut matched = match &candidates {
+        Some(ids) => collect_matches(&filter, status, ids.iter().filter_map(|id| store.file(*id))),
+        None => collect_matches(&filter, status, store.files()),
     };
-
-    let mut root_map = HashMap::new();
-    for root in &store.data.roots {
-        root_map.insert(root.id, root.path.clone());
+    // Nothing contains the words: fall back to fuzzy name matching, which the
+    // path index cannot narrow.
+    if matched.is_empty() && filter.has_rank_terms() {
+        matched = collect_matches(&filter.with_fuzzy(), status, store.files());
     }
 
-    let mut out = Vec::new();
-    for file in &store.data.files {
-        if file.status != "active" {
-            continue;
-        }
-        if let Some(root_id) = root_filter {
-            if file.root_id != root_id {
-                continue;
-            }
-        }
-        if let Some(ref set) = ext_set {
-            match &file.ext {
-                Some(ext) if set.contains(ext) => {}
-                _ => continue,
-            }
-        }
-        if let Some(ts) = after_ts {
-            if file.mtime < ts {
-                continue;
-            }
-        }
-        if let Some(ts) = before_ts {
-            if file.mtime >= ts {
-                continue;
-            }
-        }
-        if let Some(min) = min_size {
-            if file.size < min as i64 {
-                continue;
-            }
-        }
-        if let Some(max) = max_size {
-            if file.size > max as i64 {
-                continue;
-            }
-        }
-        if !file.abs_path.to_lowercase().contains(&query_lc) {
-            continue;
+    let order = |(a, a_score): &(FileRef<'_>, u32), (b, b_score): &(FileRef<'_>, u32)| match sort {
+        SortOrder::Score => b_score
+            .cmp(a_score)
+            .then_with(|| b.mtime.cmp(&a.mtime))
+            .then_with(|| rank::depth(&a.abs_path()).cmp(&rank::depth(&b.abs_path())))
+            .then_with(|| a.abs_path().cmp(&b.abs_path())),
+        SortOrder::Mtime => b.mtime.cmp(&a.mtime).then_with(|| a.id.cmp(&b.id)),
+    };
+    // Only the best `limit` are ordered; the find TUI asks for a screenful
+    // on every keystroke, often of the whole store.
+    if let Some(limit) = limit.filter(|&limit| limit < matched.len()) {
+        if limit > 0 {
+            matched.select_nth_unstable_by(limit - 1, order);
         }
-        let root_path = root_map
-            .get(&file.root_id)
-            .cloned()
-            .unwrap_or_else(|| "-".to_string());
-
-        out.push(SearchEntry {
-            id: file.id,
-            path: file.abs_path.clone(),
-            mtime: file.mtime,
-            size: file.size,
-            is_dir: file.is_dir,
-            is_symlink: file.is_symlink,
-            ext: file.ext.clone(),
-            root: root_path,
-            status: file.status.clone(),
-        });
+        matched.truncate(limit);
     }
+    matched.sort_by(order);
 
-    out.sort_by(|a, b| b.mtime.cmp(&a.mtime));
+    let mut tag_names = tags::names_by_file(&store.data);
+    let root_map = root_map(store);
+    let mut out = Vec::with_capacity(matched.len());
+    for (file, score) in matched {
+        let mut entry = entry(file, &root_map, &mut tag_names);
+        entry.score = Some(score);
+        out.push(entry);
+    }
     Ok(out)
 }
 
@@ -134,36 +121,25 @@ pub fn recent(
     _cfg: &Config,
     days: Option<u32>,
     limit: Option<u32>,
+    filter: &Expr,
 ) -> Result<Vec<SearchEntry>> {
     let days = days.unwrap_or(7) as i64;
     let limit = limit.unwrap_or(50) as i64;
     let now = Local::now().timestamp();
     let threshold = now - (days * 86400);
-    let mut root_map = HashMap::new();
-    for root in &store.data.roots {
-        root_map.insert(root.id, root.path.clone());
-    }
+    let filter = Filter::compile_at(filter, &store.data, now)?;
+    let mut tag_names = tags::names_by_file(&store.data);
+    let root_map = root_map(store);
 
     let mut out = Vec::new();
-    for file in &store.data.files {
+    for file in store.files() {
         if file.status != "active" || file.mtime < threshold {
             continue;
         }
-        let root_path = root_map
-            .get(&file.root_id)
-            .cloned()
-            .unwrap_or_else(|| "-".to_string());
-        out.push(SearchEntry {
-            id: file.id,
-            path: file.abs_path.clone(),
-            mtime: file.mtime,
-            size: file.size,
-            is_dir: file.is_dir,
-            is_symlink: file.is_symlink,
-            ext: file.ext.clone(),
-            root: root_path,
-            status: file.status.clone(),
-        });
+        if !filter.matches(&file) {
+            continue;
+        }
+        out.push(entry(file, &root_map, &mut tag_names));
     }
 
     out.sort_by(|a, b| b.mtime.cmp(&a.mtime));
@@ -171,32 +147,90 @@ pub fn recent(
     Ok(out)
 }
 
-fn parse_date_start(date: &str) -> Result<i64> {
-    let d = NaiveDate::parse_from_str(date, "%Y-%m-%d")
-        .with_context(|| "invalid date, expected YYYY-MM-DD")?;
-    Ok(Local
-        .from_local_datetime(&d.and_hms_opt(0, 0, 0).unwrap())
-        .single()
-        .unwrap()
-        .timestamp())
+/// Deleted entries whose recorded deletion time is at or after `since`,
+/// most recently deleted first. Entries deleted before deletion times were
+/// recorded have none and are left out.
+pub fn deleted(store: &Store, since: i64, filter: &Expr) -> Result<Vec<SearchEntry>> {
+    let filter = Filter::compile(filter, &store.data)?;
+    let mut tag_names = tags::names_by_file(&store.data);
+    let root_map = root_map(store);
+
+    let mut out = Vec::new();
+    for file in store.files() {
+        if file.status != "deleted" || file.deleted_at.is_none_or(|at| at < since) {
+            continue;
+        }
+        if !filter.matches(&file) {
+            continue;
+        }
+        out.push(entry(file, &root_map, &mut tag_names));
+    }
+
+    out.sort_by(|a, b| {
+        b.deleted_at
+            .cmp(&a.deleted_at)
+            .then_with(|| a.path.cmp(&b.path))
+    });
+    Ok(out)
 }
 
-fn parse_date_end_exclusive(date: &str) -> Result<i64> {
-    let d = NaiveDate::parse_from_str(date, "%Y-%m-%d")
-        .with_context(|| "invalid date, expected YYYY-MM-DD")?;
-    let next = d.succ_opt().unwrap_or(d);
-    Ok(Local
-        .from_local_datetime(&next.and_hms_opt(0, 0, 0).unwrap())
-        .single()
-        .unwrap()
-        .timestamp())
+fn collect_matches<'a>(
+    filter: &Filter,
+    status: StatusFilter,
+    files: impl Iterator<Item = FileRef<'a>>,
+) -> Vec<(FileRef<'a>, u32)> {
+    files
+        .filter(|file| status.matches(file.status))
+        .filter_map(|file| filter.score(&file).map(|score| (file, score)))
+        .collect()
+}
+
+/// Root path per root id, plus the volume name for roots that are offline.
+fn root_map(store: &Store) -> HashMap<i64, (String, Option<String>)> {
+    let mut offline = roots::offline_roots(&store.data.roots);
+    let mut root_map = HashMap::new();
+    for root in &store.data.roots {
+        root_map.insert(root.id, (root.path.clone(), offline.remove(&root.id)));
+    }
+    root_map
+}
+
+fn entry(
+    file: FileRef<'_>,
+    root_map: &HashMap<i64, (String, Option<String>)>,
+    tag_names: &mut HashMap<i64, Vec<String>>,
+) -> SearchEntry {
+    let (root_path, volume) = root_map
+        .get(&file.root_id)
+        .cloned()
+        .unwrap_or_else(|| ("-".to_string(), None));
+    let volume = volume.filter(|_| file.status == "active");
+    let status = match volume {
+        Some(_) => "offline".to_string(),
+        None => file.status.to_string(),
+    };
+    SearchEntry {
+        id: file.id,
+        path: file.abs_path().into_owned(),
+        mtime: file.mtime,
+        size: file.size,
+        is_dir: file.is_dir,
+        is_symlink: file.is_symlink,
+        ext: file.ext.map(str::to_string),
+        root: root_path,
+        status,
+        volume,
+        deleted_at: file.deleted_at,
+        tags: tag_names.remove(&file.id).unwrap_or_default(),
+        score: None,
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::{Config, OutputMode};
-    use crate::{indexer, store};
+    use crate::config::Config;
+    use crate::{indexer, query, store};
     use std::fs;
     use std::path::PathBuf;
     use std::time::{SystemTime, UNIX_EPOCH};
@@ -231,12 +265,9 @@ mod tests {
         write_file(&file2, "world");
 
         let cfg = Config {
-            version: 1,
-            output: OutputMode::Plain,
-            include_hidden: false,
-            one_filesystem: true,
-            roots: vec![root.to_string_lossy().to_string()],
+            roots: vec![root.to_string_lossy().to_string().into()],
             excludes: vec![],
+            ..Config::default()
         };
 
         let store_path = dir.join("catalog.bin");
@@ -244,19 +275,110 @@ mod tests {
         indexer::run(&mut store, &cfg, false, false).unwrap();
         store.save().unwrap();
 
+        let flags = query::Flags {
+            ext: Some("rs".to_string()),
+            ..Default::default()
+        };
         let results = search(
             &store,
             &cfg,
-            "file",
-            Some("rs"),
-            None,
-            None,
-            None,
-            None,
-            None,
-        )
-        .unwrap();
+            &query::build("file", &flags).unwrap(),
+            SortOrder::Score,
+            StatusFilter::Active,
+        ).unwrap();
         assert_eq!(results.len(), 1);
         assert!(results[0].path.ends_with("file2.rs"));
+
+        // A later run lands in the log; a read-only load sees it on top of
+        // the mapped snapshot.
+        write_file(&root.join("sub/file3.rs"), "again");
+        let mut store = store::Store::open(&store_path).unwrap();
+        indexer::run(&mut store, &cfg, false, false).unwrap();
+        store.save().unwrap();
+        let store = store::Store::load(&store_path).unwrap();
+        let results = search(
+            &store,
+            &cfg,
+            &query::build("file", &flags).unwrap(),
+            SortOrder::Score,
+            StatusFilter::Active,
+        ).unwrap();
+        assert_eq!(results.len(), 2);
+    }
+
+    #[test]
+    fn top_results_match_the_full_ordering() {
+        let dir = temp_dir("search_top");
+        let root = dir.join("root");
+        for idx in 0..20 {
+            write_file(&root.join(format!("d{}/notes{}.txt", idx % 3, idx)), "x");
+        }
+
+        let cfg = Config {
+            roots: vec![root.to_string_lossy().to_string().into()],
+            excludes: vec![],
+            ..Config::default()
+        };
+        let mut store = store::Store::open(&dir.join("catalog.bin")).unwrap();
+        indexer::run(&mut store, &cfg, false, false).unwrap();
+
+        for text in ["", "notes1"] {
+            let query = query::build(text, &query::Flags::default()).unwrap();
+            for sort in [SortOrder::Score, SortOrder::Mtime] {
+                let paths = |limit| {
+                    search_top(&store, &cfg, &query, sort, StatusFilter::Active, limit)
+                        .unwrap()
+                        .into_iter()
+                        .map(|e| e.path)
+                        .collect::<Vec<_>>()
+                };
+                let all = paths(None);
+                for limit in [0, 1, 5, all.len(), all.len() + 3] {
+                    assert_eq!(paths(Some(limit)), all[..limit.min(all.len())]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn deleted_entries_are_searchable_on_request() {
+        let dir = temp_dir("search_deleted");
+        let root = dir.join("root");
+        write_file(&root.join("setup.pkg"), "installer");
+        write_file(&root.join("notes.txt"), "notes");
+
+        let cfg = Config {
+            roots: vec![root.to_string_lossy().to_string().into()],
+            excludes: vec![],
+            ..Config::default()
+        };
+
+        let store_path = dir.join("catalog.bin");
+        let mut store = store::Store::open(&store_path).unwrap();
+        indexer::run(&mut store, &cfg, false, false).unwrap();
+        fs::remove_file(root.join("setup.pkg")).unwrap();
+        let before = Local::now().timestamp();
+        indexer::run(&mut store, &cfg, false, false).unwrap();
+        store.save().unwrap();
+
+        let store = store::Store::load(&store_path).unwrap();
+        let query = query::build("setup", &query::Flags::default()).unwrap();
+        let find = |status| search(&store, &cfg, &query, SortOrder::Score, status).unwrap();
+        assert!(find(StatusFilter::Active).is_empty());
+        let results = find(StatusFilter::Deleted);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].status, "deleted");
+        assert!(results[0].deleted_at.unwrap() >= before);
+        assert_eq!(find(StatusFilter::All).len(), 1);
+
+        let all = query::build("", &query::Flags::default()).unwrap();
+        let listed = deleted(&store, before, &all).unwrap();
+        assert_eq!(listed.len(), 1);
+        assert!(listed[0].path.ends_with("setup.pkg"));
+        assert!(
+            deleted(&store, Local::now().timestamp() + 60, &all)
+                .unwrap()
+                .is_empty()
+        );
     }
 }
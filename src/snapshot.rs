@@ -0,0 +1,589 @@
+use crate::dir_tree::{self, DirTree};
+use crate::store::{
+    ChangeEntry, DirSizeEntry, FileEntry, FileRef, FileTagEntry, PathParts, RootEntry, StoreData,
+    TagEntry,
+};
+use crate::wal::SnapshotId;
+use anyhow::{Context, Result};
+use memmap2::Mmap;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::OnceLock;
+
+const MAGIC: &[u8; 8] = b"CATSNAP1";
+const FOOTER_MAGIC: &[u8; 8] = b"CATSNEND";
+const FORMAT_VERSION: u32 = 4;
+/// Format 1 stored both full paths in every record; still readable.
+const FORMAT_V1: u32 = 1;
+/// First format that ends in a checksummed footer.
+const FORMAT_FOOTER: u32 = 3;
+/// First format whose records carry `deleted_at`.
+const FORMAT_DELETED_AT: u32 = 4;
+const FOOTER_LEN: usize = 16;
+const SECTION_LEN: usize = 24;
+const RECORD_LEN: usize = 128;
+/// Records of formats 2 and 3, without `deleted_at`.
+const RECORD_LEN_V2: usize = 120;
+const RECORD_LEN_V1: usize = 112;
+const NODE_LEN: usize = 16;
+const NO_PARENT: u32 = u32::MAX;
+
+const IS_DIR: u32 = 1;
+const IS_SYMLINK: u32 = 1 << 1;
+const DELETED: u32 = 1 << 2;
+const HAS_DEV: u32 = 1 << 3;
+const HAS_INO: u32 = 1 << 4;
+const HAS_EXT: u32 = 1 << 5;
+const HAS_PREV: u32 = 1 << 6;
+/// Paths that do not split into a directory node and a name (no `/`, or a
+/// `rel_path` that is not a suffix of `abs_path`) are stored whole.
+const WHOLE_PATHS: u32 = 1 << 7;
+const HAS_DELETED_AT: u32 = 1 << 8;
+
+/// Snapshot laid out to be memory-mapped and read in place:
+///
+/// - header: magic, format version, then `(offset, len)` of each section
+/// - meta: bincode of everything but `files` (counters, roots, tags, ...)
+/// - sections: `(root_id, first record, record count)` per root
+/// - records: one fixed-width record per file entry, grouped by root and
+///   ordered by id within a root
+/// - strings: UTF-8 bytes the records point into, each distinct string once
+/// - dirs: the parent directories of every entry as a `DirTree`, one
+///   `(parent node, name)` per node, parents first
+/// - footer: a second magic and the CRC32 of everything before it, so a
+///   truncated file is caught on open and a damaged one by `verify`
+///
+/// A record names its entry by directory node and base name; `abs_path` is
+/// rebuilt from the tree and `rel_path` is its last `rel_len` bytes.
+///
+/// Integers are little-endian. Readers only touch the pages of the records
+/// and strings they visit; writers decode everything into `StoreData`.
+pub struct Snapshot {
+    map: Mmap,
+    format: u32,
+    sections: Vec<Section>,
+    records: Range<usize>,
+    strings: Range<usize>,
+    dirs: Range<usize>,
+    /// Full path of every directory node, built on first use.
+    dir_paths: OnceLock<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Section {
+    root_id: i64,
+    first: usize,
+    count: usize,
+}
+
+/// Everything but `files`, in `StoreData` field order.
+#[derive(Serialize)]
+struct MetaOut<'a> {
+    version: u32,
+    last_run_id: i64,
+    next_root_id: i64,
+    next_file_id: i64,
+    next_tag_id: i64,
+    roots: &'a [RootEntry],
+    tags: &'a [TagEntry],
+    file_tags: &'a [FileTagEntry],
+    dir_sizes_run_id: i64,
+    dir_sizes: &'a [DirSizeEntry],
+    changes: &'a [ChangeEntry],
+}
+
+#[derive(Deserialize)]
+struct Meta {
+    version: u32,
+    last_run_id: i64,
+    next_root_id: i64,
+    next_file_id: i64,
+    next_tag_id: i64,
+    roots: Vec<RootEntry>,
+    tags: Vec<TagEntry>,
+    file_tags: Vec<FileTagEntry>,
+    dir_sizes_run_id: i64,
+    dir_sizes: Vec<DirSizeEntry>,
+    changes: Vec<ChangeEntry>,
+}
+
+impl std::fmt::Debug for Snapshot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Snapshot")
+            .field("len", &self.map.len())
+            .field("records", &self.len())
+            .finish()
+    }
+}
+
+/// Whether `raw` starts like a mappable snapshot rather than a bincode one.
+pub fn is_snapshot(raw: &[u8]) -> bool {
+    raw.starts_with(MAGIC)
+}
+
+impl Snapshot {
+    /// Maps the snapshot at `path` and decodes its meta section into a
+    /// `StoreData` whose `files` are left empty.
+    pub fn open(path: &Path) -> Result<(Self, StoreData)> {
+        let file = File::open(path)
+            .with_context(|| format!("failed to read store: {}", path.display()))?;
+        // Snapshots are only ever replaced by renaming a new file over them,
+        // never written in place, so the mapping stays valid and unchanged.
+        let map = unsafe { Mmap::map(&file) }
+            .with_context(|| format!("failed to map store: {}", path.display()))?;
+        Self::parse(map)
+    }
+
+    fn parse(map: Mmap) -> Result<(Self, StoreData)> {
+        if map.len() < header_len(FORMAT_V1) || !is_snapshot(&map) {
+            anyhow::bail!("not a catalog snapshot");
+        }
+        let format = read_u32(&map, 8);
+        if !(FORMAT_V1..=FORMAT_VERSION).contains(&format) {
+            anyhow::bail!(
+                "unsupported snapshot format {} (expected {})",
+                format,
+                FORMAT_VERSION
+            );
+        }
+        let body_len = match format {
+            FORMAT_FOOTER.. => footer(&map).map(|_| map.len() - FOOTER_LEN),
+            _ => Some(map.len()),
+        };
+        let body_len = match body_len {
+            Some(len) if len >= header_len(format) => len,
+            _ => anyhow::bail!("store snapshot is truncated"),
+        };
+        let range = |idx: usize| -> Result<Range<usize>> {
+            let off = read_u64(&map, 16 + idx * 16) as usize;
+            let len = read_u64(&map, 24 + idx * 16) as usize;
+            let end = off.checked_add(len).filter(|&end| end <= body_len);
+            match end {
+                Some(end) => Ok(off..end),
+                None => anyhow::bail!("store snapshot is truncated"),
+            }
+        };
+        let meta = range(0)?;
+        let sections = range(1)?;
+        let records = range(2)?;
+        let strings = range(3)?;
+        let dirs = match format {
+            FORMAT_V1 => 0..0,
+            _ => range(4)?,
+        };
+        let record_len = record_len(format);
+        if sections.len() % SECTION_LEN != 0
+            || records.len() % record_len != 0
+            || dirs.len() % NODE_LEN != 0
+        {
+            anyhow::bail!("store snapshot is corrupt");
+        }
+
+        let meta: Meta =
+            bincode::deserialize(&map[meta]).context("failed to parse store snapshot")?;
+        let total = records.len() / record_len;
+        let sections = map[sections]
+            .chunks_exact(SECTION_LEN)
+            .map(|raw| Section {
+                root_id: read_i64(raw, 0),
+                first: read_u64(raw, 8) as usize,
+                count: read_u64(raw, 16) as usize,
+            })
+            .collect::<Vec<_>>();
+        if sections
+            .iter()
+            .any(|s| s.first.checked_add(s.count).is_none_or(|end| end > total))
+        {
+            anyhow::bail!("store snapshot is corrupt");
+        }
+
+        let data = StoreData {
+            version: meta.version,
+            last_run_id: meta.last_run_id,
+            next_root_id: meta.next_root_id,
+            next_file_id: meta.next_file_id,
+            next_tag_id: meta.next_tag_id,
+            roots: meta.roots,
+            files: Vec::new(),
+            tags: meta.tags,
+            file_tags: meta.file_tags,
+            dir_sizes_run_id: meta.dir_sizes_run_id,
+            dir_sizes: meta.dir_sizes,
+            changes: meta.changes,
+        };
+        let snapshot = Self {
+            map,
+            format,
+            sections,
+            records,
+            strings,
+            dirs,
+            dir_paths: OnceLock::new(),
+        };
+        Ok((snapshot, data))
+    }
+
+    /// The raw file contents.
+    pub fn bytes(&self) -> &[u8] {
+        &self.map
+    }
+
+    /// Identity the log records its base as: the footer checksum, so opening
+    /// a snapshot does not have to read all of it. Snapshots without a
+    /// footer are hashed whole.
+    pub fn id(&self) -> SnapshotId {
+        id_of(&self.map)
+    }
+
+    /// Whether the file carries a checksum `verify` can check.
+    pub fn has_checksum(&self) -> bool {
+        self.format >= FORMAT_FOOTER
+    }
+
+    /// Checks the footer checksum against the contents; reads the whole
+    /// file. Older formats without a footer always pass.
+    pub fn verify(&self) -> Result<()> {
+        let Some(expected) = footer(&self.map).filter(|_| self.has_checksum()) else {
+            return Ok(());
+        };
+        let actual = crc32fast::hash(&self.map[..self.map.len() - FOOTER_LEN]);
+        if actual != expected {
+            anyhow::bail!(
+                "store checksum mismatch (expected {:08x}, found {:08x}); the file is damaged, run `catalog fsck`",
+                expected,
+                actual
+            );
+        }
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len() / record_len(self.format)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Number of interned directory nodes.
+    pub fn dir_count(&self) -> usize {
+        self.dirs.len() / NODE_LEN
+    }
+
+    /// Record `idx` (in file order) as a borrowed entry.
+    pub fn get(&self, idx: usize) -> FileRef<'_> {
+        if self.format == FORMAT_V1 {
+            return self.get_v1(idx);
+        }
+        let record_len = record_len(self.format);
+        let start = self.records.start + idx * record_len;
+        let raw = &self.map[start..start + record_len];
+        let flags = read_u32(raw, 56);
+        let opt = |flag: u32, value: u64| (flags & flag != 0).then_some(value);
+        let path = if flags & WHOLE_PATHS != 0 {
+            PathParts::Whole {
+                abs: self.str_at(raw, 72),
+                rel: self.str_at(raw, 108),
+            }
+        } else {
+            let dir = self.dir_path(read_u32(raw, 60));
+            let name = self.str_at(raw, 72);
+            PathParts::Split {
+                dir,
+                name,
+                rel_len: (read_u32(raw, 64) as usize).min(dir.len() + 1 + name.len()),
+            }
+        };
+        FileRef {
+            id: read_i64(raw, 0),
+            root_id: read_i64(raw, 8),
+            size: read_i64(raw, 16),
+            mtime: read_i64(raw, 24),
+            last_seen_run: read_i64(raw, 32),
+            dev: opt(HAS_DEV, read_u64(raw, 40)),
+            ino: opt(HAS_INO, read_u64(raw, 48)),
+            is_dir: flags & IS_DIR != 0,
+            is_symlink: flags & IS_SYMLINK != 0,
+            status: if flags & DELETED != 0 { "deleted" } else { "active" },
+            path,
+            ext: (flags & HAS_EXT != 0).then(|| self.str_at(raw, 84)),
+            prev_path: (flags & HAS_PREV != 0).then(|| self.str_at(raw, 96)),
+            deleted_at: (self.format >= FORMAT_DELETED_AT && flags & HAS_DELETED_AT != 0)
+                .then(|| read_i64(raw, 120)),
+        }
+    }
+
+    fn get_v1(&self, idx: usize) -> FileRef<'_> {
+        let start = self.records.start + idx * RECORD_LEN_V1;
+        let raw = &self.map[start..start + RECORD_LEN_V1];
+        let flags = read_u32(raw, 56);
+        let opt = |flag: u32, value: u64| (flags & flag != 0).then_some(value);
+        FileRef {
+            id: read_i64(raw, 0),
+            root_id: read_i64(raw, 8),
+            size: read_i64(raw, 16),
+            mtime: read_i64(raw, 24),
+            last_seen_run: read_i64(raw, 32),
+            dev: opt(HAS_DEV, read_u64(raw, 40)),
+            ino: opt(HAS_INO, read_u64(raw, 48)),
+            is_dir: flags & IS_DIR != 0,
+            is_symlink: flags & IS_SYMLINK != 0,
+            status: if flags & DELETED != 0 { "deleted" } else { "active" },
+            path: PathParts::Whole {
+                abs: self.str_at(raw, 76),
+                rel: self.str_at(raw, 64),
+            },
+            ext: (flags & HAS_EXT != 0).then(|| self.str_at(raw, 88)),
+            prev_path: (flags & HAS_PREV != 0).then(|| self.str_at(raw, 100)),
+            deleted_at: None,
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = FileRef<'_>> + '_ {
+        (0..self.len()).map(move |idx| self.get(idx))
+    }
+
+    /// Records of one root, ordered by id.
+    pub fn root(&self, root_id: i64) -> impl Iterator<Item = FileRef<'_>> + '_ {
+        self.sections
+            .iter()
+            .filter(move |s| s.root_id == root_id)
+            .flat_map(move |s| (s.first..s.first + s.count).map(move |idx| self.get(idx)))
+    }
+
+    pub fn find(&self, id: i64) -> Option<FileRef<'_>> {
+        self.sections.iter().find_map(|s| {
+            let (mut lo, mut hi) = (s.first, s.first + s.count);
+            while lo < hi {
+                let mid = (lo + hi) / 2;
+                let at = self.records.start + mid * record_len(self.format);
+                let mid_id = read_i64(&self.map, at);
+                match mid_id.cmp(&id) {
+                    std::cmp::Ordering::Less => lo = mid + 1,
+                    std::cmp::Ordering::Greater => hi = mid,
+                    std::cmp::Ordering::Equal => return Some(self.get(mid)),
+                }
+            }
+            None
+        })
+    }
+
+    /// Full path of directory node `node`; empty for a node out of range.
+    fn dir_path(&self, node: u32) -> &str {
+        let paths = self.dir_paths.get_or_init(|| {
+            let nodes = &self.map[self.dirs.clone()];
+            let mut paths: Vec<String> = Vec::with_capacity(nodes.len() / NODE_LEN);
+            for raw in nodes.chunks_exact(NODE_LEN) {
+                let name = self.str_at(raw, 4);
+                // Parents precede their children; anything else is treated
+                // as a top-level node rather than trusted.
+                let path = match paths.get(read_u32(raw, 0) as usize) {
+                    Some(parent) => format!("{}/{}", parent, name),
+                    None => name.to_string(),
+                };
+                paths.push(path);
+            }
+            paths
+        });
+        paths.get(node as usize).map(String::as_str).unwrap_or_default()
+    }
+
+    fn str_at(&self, raw: &[u8], at: usize) -> &str {
+        let off = read_u64(raw, at) as usize;
+        let len = read_u32(raw, at + 8) as usize;
+        let strings = &self.map[self.strings.clone()];
+        off.checked_add(len)
+            .and_then(|end| strings.get(off..end))
+            .and_then(|bytes| std::str::from_utf8(bytes).ok())
+            .unwrap_or_default()
+    }
+}
+
+/// Encodes `data` in the mappable layout.
+pub fn encode(data: &StoreData) -> Result<Vec<u8>> {
+    let meta = bincode::serialize(&MetaOut {
+        version: data.version,
+        last_run_id: data.last_run_id,
+        next_root_id: data.next_root_id,
+        next_file_id: data.next_file_id,
+        next_tag_id: data.next_tag_id,
+        roots: &data.roots,
+        tags: &data.tags,
+        file_tags: &data.file_tags,
+        dir_sizes_run_id: data.dir_sizes_run_id,
+        dir_sizes: &data.dir_sizes,
+        changes: &data.changes,
+    })
+    .context("failed to serialize store")?;
+
+    let mut order: Vec<&FileEntry> = data.files.iter().collect();
+    order.sort_by_key(|f| (f.root_id, f.id));
+
+    let mut sections: Vec<Section> = Vec::new();
+    let mut records = Vec::with_capacity(order.len() * RECORD_LEN);
+    let mut strings = Strings::default();
+    let mut tree = DirTree::default();
+    // Entries of one directory are mostly adjacent; skip re-interning them.
+    let mut last_dir: Option<(&str, u32)> = None;
+    for (idx, file) in order.iter().enumerate() {
+        match sections.last_mut() {
+            Some(section) if section.root_id == file.root_id => section.count += 1,
+            _ => sections.push(Section {
+                root_id: file.root_id,
+                first: idx,
+                count: 1,
+            }),
+        }
+        let split = dir_tree::split(&file.abs_path)
+            .filter(|_| file.abs_path.ends_with(file.rel_path.as_str()));
+        let mut flags = 0;
+        for (set, flag) in [
+            (file.is_dir, IS_DIR),
+            (file.is_symlink, IS_SYMLINK),
+            (file.status == "deleted", DELETED),
+            (file.dev.is_some(), HAS_DEV),
+            (file.ino.is_some(), HAS_INO),
+            (file.ext.is_some(), HAS_EXT),
+            (file.prev_path.is_some(), HAS_PREV),
+            (split.is_none(), WHOLE_PATHS),
+            (file.deleted_at.is_some(), HAS_DELETED_AT),
+        ] {
+            if set {
+                flags |= flag;
+            }
+        }
+        let (dir, name, rel_len, whole_rel) = match split {
+            Some((dir, name)) => {
+                let node = match last_dir {
+                    Some((last, node)) if last == dir => node,
+                    _ => tree.intern(dir),
+                };
+                last_dir = Some((dir, node));
+                (node, name, file.rel_path.len(), "")
+            }
+            None => (0, file.abs_path.as_str(), 0, file.rel_path.as_str()),
+        };
+        records.extend_from_slice(&file.id.to_le_bytes());
+        records.extend_from_slice(&file.root_id.to_le_bytes());
+        records.extend_from_slice(&file.size.to_le_bytes());
+        records.extend_from_slice(&file.mtime.to_le_bytes());
+        records.extend_from_slice(&file.last_seen_run.to_le_bytes());
+        records.extend_from_slice(&file.dev.unwrap_or(0).to_le_bytes());
+        records.extend_from_slice(&file.ino.unwrap_or(0).to_le_bytes());
+        records.extend_from_slice(&flags.to_le_bytes());
+        records.extend_from_slice(&dir.to_le_bytes());
+        records.extend_from_slice(&(rel_len as u32).to_le_bytes());
+        records.extend_from_slice(&0u32.to_le_bytes());
+        for text in [
+            name,
+            file.ext.as_deref().unwrap_or_default(),
+            file.prev_path.as_deref().unwrap_or_default(),
+            whole_rel,
+        ] {
+            strings.push(text, &mut records);
+        }
+        records.extend_from_slice(&file.deleted_at.unwrap_or(0).to_le_bytes());
+    }
+
+    let mut section_bytes = Vec::with_capacity(sections.len() * SECTION_LEN);
+    for section in &sections {
+        section_bytes.extend_from_slice(&section.root_id.to_le_bytes());
+        section_bytes.extend_from_slice(&(section.first as u64).to_le_bytes());
+        section_bytes.extend_from_slice(&(section.count as u64).to_le_bytes());
+    }
+
+    let mut dirs = Vec::with_capacity(tree.len() * NODE_LEN);
+    for node in tree.nodes() {
+        dirs.extend_from_slice(&node.parent.unwrap_or(NO_PARENT).to_le_bytes());
+        strings.push(&node.name, &mut dirs);
+    }
+
+    let parts = [&meta, &section_bytes, &records, &strings.bytes, &dirs];
+    let header_len = header_len(FORMAT_VERSION);
+    let total = header_len + parts.iter().map(|p| p.len()).sum::<usize>();
+    let mut out = Vec::with_capacity(total);
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    out.extend_from_slice(&0u32.to_le_bytes());
+    let mut offset = header_len as u64;
+    for part in parts {
+        out.extend_from_slice(&offset.to_le_bytes());
+        out.extend_from_slice(&(part.len() as u64).to_le_bytes());
+        offset += part.len() as u64;
+    }
+    for part in parts {
+        out.extend_from_slice(part);
+    }
+    let crc = crc32fast::hash(&out);
+    out.extend_from_slice(FOOTER_MAGIC);
+    out.extend_from_slice(&crc.to_le_bytes());
+    out.extend_from_slice(&0u32.to_le_bytes());
+    Ok(out)
+}
+
+/// Identity of an encoded snapshot (see `Snapshot::id`).
+pub fn id_of(raw: &[u8]) -> SnapshotId {
+    let format = (raw.len() >= 12 && is_snapshot(raw)).then(|| read_u32(raw, 8));
+    match format.filter(|&f| f >= FORMAT_FOOTER).and_then(|_| footer(raw)) {
+        Some(crc) => SnapshotId {
+            len: raw.len() as u64,
+            crc,
+        },
+        None => SnapshotId::of(raw),
+    }
+}
+
+/// Checksum stored in the footer, if the file ends in one.
+fn footer(raw: &[u8]) -> Option<u32> {
+    let start = raw.len().checked_sub(FOOTER_LEN)?;
+    let footer = &raw[start..];
+    (footer[..8] == FOOTER_MAGIC[..]).then(|| read_u32(footer, 8))
+}
+
+/// The strings area, storing each distinct string once.
+#[derive(Default)]
+struct Strings<'a> {
+    bytes: Vec<u8>,
+    offsets: HashMap<&'a str, u64>,
+}
+
+impl<'a> Strings<'a> {
+    /// Adds `text` if it is new and writes its `(offset, len)` to `out`.
+    fn push(&mut self, text: &'a str, out: &mut Vec<u8>) {
+        let offset = *self.offsets.entry(text).or_insert_with(|| {
+            let offset = self.bytes.len() as u64;
+            self.bytes.extend_from_slice(text.as_bytes());
+            offset
+        });
+        out.extend_from_slice(&offset.to_le_bytes());
+        out.extend_from_slice(&(text.len() as u32).to_le_bytes());
+    }
+}
+
+fn header_len(format: u32) -> usize {
+    let parts = if format == FORMAT_V1 { 4 } else { 5 };
+    8 + 4 + 4 + parts * 16
+}
+
+fn record_len(format: u32) -> usize {
+    match format {
+        FORMAT_V1 => RECORD_LEN_V1,
+        ..FORMAT_DELETED_AT => RECORD_LEN_V2,
+        _ => RECORD_LEN,
+    }
+}
+
+fn read_u32(raw: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(raw[at..at + 4].try_into().expect("4-byte slice"))
+}
+
+fn read_u64(raw: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(raw[at..at + 8].try_into().expect("8-byte slice"))
+}
+
+fn read_i64(raw: &[u8], at: usize) -> i64 {
+    i64::from_le_bytes(raw[at..at + 8].try_into().expect("8-byte slice"))
+}
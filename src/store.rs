@@ -1,11 +1,20 @@
+use crate::checkpoint;
+use crate::lock::CommitLock;
+use crate::migrate;
+use crate::path_index::PathIndex;
+use crate::snapshot::{self, Snapshot};
+use crate::wal::{self, Committed, SnapshotId, WalState};
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use std::borrow::Cow;
+use std::cell::{Cell, Ref, RefCell};
 use std::fs::{self, File};
-use std::io::Write;
+use std::collections::{BTreeMap, HashSet};
+use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
 
-const STORE_VERSION: u32 = 2;
+pub const STORE_VERSION: u32 = 8;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StoreData {
@@ -31,9 +40,12 @@ pub struct StoreData {
     pub dir_sizes_run_id: i64,
     #[serde(default)]
     pub dir_sizes: Vec<DirSizeEntry>,
+    /// Change journal, oldest first.
+    #[serde(default)]
+    pub changes: Vec<ChangeEntry>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Hash)]
 pub struct RootEntry {
     pub id: i64,
     pub path: String,
@@ -41,9 +53,49 @@ pub struct RootEntry {
     pub preset_name: Option<String>,
     pub last_indexed_at: Option<String>,
     pub one_filesystem: bool,
+    /// Volume the root was last indexed on, if it could be identified.
+    #[serde(default)]
+    pub volume: Option<RootVolume>,
+    /// When and how the root was last scanned.
+    #[serde(default)]
+    pub scan: Option<ScanRecord>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Bookkeeping for the directory-mtime shortcut: a directory whose mtime
+/// predates `started_at` and still matches the store is not re-listed.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Hash)]
+pub struct ScanRecord {
+    /// Unix time the last scan started.
+    pub started_at: i64,
+    /// Unix time of the last scan that listed every directory.
+    pub verified_at: i64,
+    /// Fingerprint of the excludes and walk options the scan used.
+    pub settings: u64,
+}
+
+/// Identity of the filesystem holding a root, so the root can be found again
+/// when the volume is mounted somewhere else.
+#[derive(Debug, Clone, Serialize, Deserialize, Hash)]
+pub struct RootVolume {
+    pub uuid: Option<String>,
+    pub label: Option<String>,
+    /// Where the volume was mounted when the root was last indexed.
+    pub mount_point: String,
+    /// Root path relative to `mount_point`.
+    pub rel_path: String,
+}
+
+impl RootVolume {
+    /// Human-readable name: the label, falling back to the UUID.
+    pub fn name(&self) -> &str {
+        self.label
+            .as_deref()
+            .or(self.uuid.as_deref())
+            .unwrap_or(&self.mount_point)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Hash)]
 pub struct FileEntry {
     pub id: i64,
     pub root_id: i64,
@@ -56,21 +108,176 @@ pub struct FileEntry {
     pub ext: Option<String>,
     pub status: String,
     pub last_seen_run: i64,
+    #[serde(default)]
+    pub dev: Option<u64>,
+    #[serde(default)]
+    pub ino: Option<u64>,
+    /// Absolute path the entry had before it was last detected as moved.
+    #[serde(default)]
+    pub prev_path: Option<String>,
+    /// When the entry was marked deleted (unix seconds); `None` while it is
+    /// active, and for entries deleted before version 8 until a retention
+    /// pass stamps them.
+    #[serde(default)]
+    pub deleted_at: Option<i64>,
+}
+
+/// A file entry borrowed from a mapped snapshot or from `StoreData::files`.
+/// Mapped entries keep their path as a directory and a name; `abs_path`
+/// only joins them when asked, so filters on other fields (or on the name)
+/// do not build a path per entry.
+#[derive(Debug, Clone, Copy)]
+pub struct FileRef<'a> {
+    pub id: i64,
+    pub root_id: i64,
+    pub(crate) path: PathParts<'a>,
+    pub is_dir: bool,
+    pub is_symlink: bool,
+    pub size: i64,
+    pub mtime: i64,
+    pub ext: Option<&'a str>,
+    pub status: &'a str,
+    pub last_seen_run: i64,
+    pub dev: Option<u64>,
+    pub ino: Option<u64>,
+    pub prev_path: Option<&'a str>,
+    pub deleted_at: Option<i64>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Where a `FileRef`'s paths live.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum PathParts<'a> {
+    Whole {
+        abs: &'a str,
+        rel: &'a str,
+    },
+    /// `dir/name`, whose last `rel_len` bytes are the relative path.
+    Split {
+        dir: &'a str,
+        name: &'a str,
+        rel_len: usize,
+    },
+}
+
+impl<'a> FileRef<'a> {
+    pub fn abs_path(&self) -> Cow<'a, str> {
+        match self.path {
+            PathParts::Whole { abs, .. } => Cow::Borrowed(abs),
+            PathParts::Split { dir, name, .. } => Cow::Owned(format!("{}/{}", dir, name)),
+        }
+    }
+
+    pub fn rel_path(&self) -> Cow<'a, str> {
+        match self.path {
+            PathParts::Whole { rel, .. } => Cow::Borrowed(rel),
+            PathParts::Split { name, rel_len, .. } if rel_len <= name.len() => {
+                Cow::Borrowed(&name[name.len() - rel_len..])
+            }
+            PathParts::Split { .. } => {
+                let abs = self.abs_path();
+                let start = abs.len().saturating_sub(self.rel_len());
+                Cow::Owned(abs.get(start..).unwrap_or_default().to_string())
+            }
+        }
+    }
+
+    /// Last component of `abs_path`, without building it.
+    pub fn name(&self) -> &'a str {
+        match self.path {
+            PathParts::Whole { abs, .. } => abs.rsplit('/').next().unwrap_or(abs),
+            PathParts::Split { name, .. } => name,
+        }
+    }
+
+    fn rel_len(&self) -> usize {
+        match self.path {
+            PathParts::Whole { rel, .. } => rel.len(),
+            PathParts::Split { rel_len, .. } => rel_len,
+        }
+    }
+
+    pub fn to_entry(&self) -> FileEntry {
+        FileEntry {
+            id: self.id,
+            root_id: self.root_id,
+            rel_path: self.rel_path().into_owned(),
+            abs_path: self.abs_path().into_owned(),
+            is_dir: self.is_dir,
+            is_symlink: self.is_symlink,
+            size: self.size,
+            mtime: self.mtime,
+            ext: self.ext.map(str::to_string),
+            status: self.status.to_string(),
+            last_seen_run: self.last_seen_run,
+            dev: self.dev,
+            ino: self.ino,
+            prev_path: self.prev_path.map(str::to_string),
+            deleted_at: self.deleted_at,
+        }
+    }
+}
+
+impl FileEntry {
+    pub fn view(&self) -> FileRef<'_> {
+        FileRef {
+            id: self.id,
+            root_id: self.root_id,
+            path: PathParts::Whole {
+                abs: &self.abs_path,
+                rel: &self.rel_path,
+            },
+            is_dir: self.is_dir,
+            is_symlink: self.is_symlink,
+            size: self.size,
+            mtime: self.mtime,
+            ext: self.ext.as_deref(),
+            status: &self.status,
+            last_seen_run: self.last_seen_run,
+            dev: self.dev,
+            ino: self.ino,
+            prev_path: self.prev_path.as_deref(),
+            deleted_at: self.deleted_at,
+        }
+    }
+}
+
+/// Entries the log changed on top of a mapped snapshot; `None` marks a
+/// removed entry.
+pub type FileOverlay = BTreeMap<i64, Option<FileEntry>>;
+
+/// One created/modified/deleted/moved entry observed by an index run or a
+/// watch batch. Size and mtime are `None` on the side that did not exist.
+#[derive(Debug, Clone, Serialize, Deserialize, Hash)]
+pub struct ChangeEntry {
+    pub run_id: i64,
+    /// Unix seconds when the change was recorded.
+    pub at: i64,
+    pub file_id: i64,
+    pub root_id: i64,
+    pub kind: String,
+    pub path: String,
+    /// Previous absolute path (`moved` only).
+    pub prev_path: Option<String>,
+    pub is_dir: bool,
+    pub old_size: Option<i64>,
+    pub new_size: Option<i64>,
+    pub old_mtime: Option<i64>,
+    pub new_mtime: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Hash)]
 pub struct TagEntry {
     pub id: i64,
     pub name: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Hash)]
 pub struct FileTagEntry {
     pub file_id: i64,
     pub tag_id: i64,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Hash)]
 pub struct DirSizeEntry {
     pub path: String,
     pub size: u64,
@@ -79,71 +286,364 @@ pub struct DirSizeEntry {
 #[derive(Debug)]
 pub struct Store {
     pub path: PathBuf,
+    /// Everything but the file entries of a store loaded read-only, which
+    /// stay in the mapped snapshot; use `files` and `file` to read entries.
+    /// `open` and `make_writable` decode them into `data.files`.
     pub data: StoreData,
+    snapshot: Option<Snapshot>,
+    overlay: FileOverlay,
+    index: RefCell<Option<PathIndex>>,
+    /// What the snapshot and log on disk hold; only kept for stores opened
+    /// for writing.
+    committed: RefCell<Option<Committed>>,
+    /// The log on top of the snapshot, once the snapshot is known.
+    wal: RefCell<Option<WalState>>,
+    /// Ids of the file entries the log changed; the path index sidecar only
+    /// covers the snapshot.
+    logged: RefCell<Vec<i64>>,
+    /// Version of a snapshot written by an older catalog, until `compact`
+    /// rewrites it (after backing it up).
+    migrated_from: Cell<Option<u32>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Read,
+    Write,
+    Inspect,
 }
 
 impl Store {
+    /// Loads the store for reading. A snapshot in the mappable layout is
+    /// mapped rather than decoded; older bincode snapshots are decoded whole.
     pub fn load(path: &Path) -> Result<Self> {
-        if path.exists() {
-            let raw = fs::read(path)
-                .with_context(|| format!("failed to read store: {}", path.display()))?;
-            let mut data: StoreData =
-                bincode::deserialize(&raw).context("failed to parse store binary")?;
-            if data.version > STORE_VERSION {
-                anyhow::bail!(
-                    "unsupported store version {} (expected <= {})",
-                    data.version,
-                    STORE_VERSION
-                );
+        Self::read(path, Mode::Read)
+    }
+
+    /// Loads the store for a command that will `save` it: decodes every
+    /// entry and remembers what is on disk so the save only has to append
+    /// the difference to the log.
+    pub fn open(path: &Path) -> Result<Self> {
+        Self::read(path, Mode::Write)
+    }
+
+    /// Opens the store as `open` does, but as found on disk: the checksum is
+    /// not enforced and id counters are not repaired. For `fsck`.
+    pub fn inspect(path: &Path) -> Result<Self> {
+        Self::read(path, Mode::Inspect)
+    }
+
+    fn read(path: &Path, mode: Mode) -> Result<Self> {
+        let writable = mode != Mode::Read;
+        if !path.exists() {
+            let mut store = Self::from_data(path.to_path_buf(), StoreData::new());
+            if writable {
+                store.make_writable();
             }
-            if data.version < STORE_VERSION {
-                data.version = STORE_VERSION;
+            return Ok(store);
+        }
+
+        // Keeps a writer from swapping the snapshot or dropping the log
+        // between mapping one and replaying the other.
+        let commit = CommitLock::shared(path);
+        let mut raw = fs::File::open(path)
+            .with_context(|| format!("failed to read store: {}", path.display()))?;
+        let mut magic = [0u8; 8];
+        let mapped = raw.read_exact(&mut magic).is_ok() && snapshot::is_snapshot(&magic);
+        drop(raw);
+        let (snapshot, base, data, written_as) = if mapped {
+            let (snapshot, mut data) = Snapshot::open(path)?;
+            migrate::check_version(data.version)?;
+            if mode == Mode::Write {
+                // Everything is about to be decoded anyway; refuse to build
+                // on (and later rewrite) a damaged file.
+                snapshot.verify()?;
             }
-            data.ensure_counters();
-            Ok(Self {
-                path: path.to_path_buf(),
-                data,
-            })
+            let base = snapshot.id();
+            let version = data.version;
+            // Only entries changed since version 7; they are upgraded as
+            // they are read.
+            data.version = STORE_VERSION;
+            (Some(snapshot), base, data, version)
         } else {
-            Ok(Self {
-                path: path.to_path_buf(),
-                data: StoreData::new(),
-            })
+            // Bincode snapshot from before version 7; upgraded here and
+            // rewritten in the mappable layout on the next compaction.
+            let raw = fs::read(path)
+                .with_context(|| format!("failed to read store: {}", path.display()))?;
+            let (data, version) = migrate::decode(&raw)?;
+            (None, SnapshotId::of(&raw), data, version)
+        };
+
+        let mut store = Self::from_data(path.to_path_buf(), data);
+        store.snapshot = snapshot;
+        if written_as < STORE_VERSION {
+            store.migrated_from.set(Some(written_as));
+        }
+        let wal_path = wal::wal_path(path);
+        if writable || wal_path.exists() {
+            store.data.ensure_sorted();
+            let overlay = store.snapshot.as_ref().map(|_| &mut store.overlay);
+            let (len, logged) = wal::replay(&wal_path, base, written_as, &mut store.data, overlay)?;
+            *store.wal.borrow_mut() = Some(WalState { base, len });
+            *store.logged.borrow_mut() = logged;
+        }
+        drop(commit);
+        store.data.ensure_sorted();
+        if writable {
+            store.make_writable();
+        }
+        if written_as < 8 {
+            migrate::backfill_deleted_at(&mut store.data);
+        }
+        if mode != Mode::Inspect {
+            store.data.ensure_counters();
+        }
+        Ok(store)
+    }
+
+    /// Decodes the mapped entries (and the log's changes to them) into
+    /// `data.files` and starts tracking changes for `save`, as `open` does.
+    /// Does nothing for a store that is already writable.
+    pub fn make_writable(&mut self) {
+        if let Some(snapshot) = self.snapshot.take() {
+            let mut files: Vec<FileEntry> = snapshot
+                .iter()
+                .filter(|f| !self.overlay.contains_key(&f.id))
+                .map(|f| f.to_entry())
+                .collect();
+            files.extend(std::mem::take(&mut self.overlay).into_values().flatten());
+            files.sort_by_key(|f| f.id);
+            self.data.files = files;
+        }
+        let mut committed = self.committed.borrow_mut();
+        if committed.is_none() {
+            *committed = Some(Committed::of(&self.data));
+        }
+    }
+
+    /// Every file entry, active and deleted. Entries of a writable store come
+    /// in id order; mapped ones are grouped by root.
+    pub fn files(&self) -> impl Iterator<Item = FileRef<'_>> + '_ {
+        let mapped = self.snapshot.iter().flat_map(move |snapshot| {
+            snapshot
+                .iter()
+                .filter(move |f| self.overlay.is_empty() || !self.overlay.contains_key(&f.id))
+        });
+        let overlaid = self.overlay.values().flatten().map(FileEntry::view);
+        self.data
+            .files
+            .iter()
+            .map(FileEntry::view)
+            .chain(mapped)
+            .chain(overlaid)
+    }
+
+    pub fn file(&self, id: i64) -> Option<FileRef<'_>> {
+        if let Some(entry) = self.overlay.get(&id) {
+            return entry.as_ref().map(FileEntry::view);
+        }
+        match &self.snapshot {
+            Some(snapshot) => snapshot.find(id),
+            None => self.data.file_by_id(id).map(FileEntry::view),
+        }
+    }
+
+    /// Wraps data that was not read from `path` (tests, synthetic stores).
+    pub fn from_data(path: PathBuf, data: StoreData) -> Self {
+        Self {
+            path,
+            data,
+            snapshot: None,
+            overlay: FileOverlay::new(),
+            index: RefCell::new(None),
+            committed: RefCell::new(None),
+            wal: RefCell::new(None),
+            logged: RefCell::new(Vec::new()),
+            migrated_from: Cell::new(None),
         }
     }
 
+    /// Bytes of the log that replayed cleanly, once the snapshot is known.
+    pub fn log_len(&self) -> Option<u64> {
+        self.wal.borrow().map(|state| state.len)
+    }
+
+    /// Version the snapshot on disk was written with, when an older catalog
+    /// wrote it; `compact` backs it up and rewrites it.
+    pub fn migrated_from(&self) -> Option<u32> {
+        self.migrated_from.get()
+    }
+
     pub fn init(path: &Path) -> Result<Self> {
-        let store = Self::load(path)?;
+        let store = Self::open(path)?;
         store.save()?;
         Ok(store)
     }
 
+    /// Persists the data. A store opened with `open` appends what changed
+    /// since the last save to the log; otherwise, or once the log has grown
+    /// past its threshold, the snapshot is rewritten and the log dropped.
     pub fn save(&self) -> Result<()> {
+        if self.append_to_log(None)? {
+            return Ok(());
+        }
+        self.compact()
+    }
+
+    /// Saves as `save` does, for a caller that knows the only file entries it
+    /// changed (or removed) are those with the given ids, so the rest are not
+    /// compared.
+    pub fn save_changed(&self, ids: &HashSet<i64>) -> Result<()> {
+        if self.append_to_log(Some(ids))? {
+            return Ok(());
+        }
+        self.compact()
+    }
+
+    /// Rewrites the snapshot from the in-memory data and drops the log.
+    pub fn compact(&self) -> Result<()> {
+        if self.snapshot.is_some() {
+            anyhow::bail!("store was loaded read-only; call `make_writable` before saving");
+        }
         ensure_parent_dir(&self.path)?;
         let tmp_path = tmp_path(&self.path);
-        let data = bincode::serialize(&self.data).context("failed to serialize store")?;
+        let data = snapshot::encode(&self.data)?;
         let mut file = File::create(&tmp_path)
             .with_context(|| format!("failed to write store: {}", tmp_path.display()))?;
         file.write_all(&data)?;
         file.sync_all()?;
+        let commit = CommitLock::exclusive(&self.path)?;
+        if let Some(version) = self.migrated_from.get().filter(|_| self.path.exists()) {
+            migrate::backup(&self.path, version)?;
+        }
         fs::rename(&tmp_path, &self.path)
             .with_context(|| format!("failed to finalize store: {}", self.path.display()))?;
+        self.migrated_from.set(None);
+        // A crash before the old log is gone leaves one whose header names
+        // the previous snapshot, which `load` ignores.
+        wal::remove(&wal::wal_path(&self.path))?;
+        drop(commit);
+        *self.wal.borrow_mut() = Some(WalState::new(snapshot::id_of(&data)));
+        self.logged.borrow_mut().clear();
+        let mut committed = self.committed.borrow_mut();
+        if committed.is_some() {
+            *committed = Some(Committed::of(&self.data));
+        }
+        drop(committed);
+        self.refresh_index();
         Ok(())
     }
 
+    /// Appends the changes since the last save to the log. Returns false when
+    /// the snapshot has to be rewritten instead.
+    fn append_to_log(&self, only: Option<&HashSet<i64>>) -> Result<bool> {
+        let mut committed = self.committed.borrow_mut();
+        let mut wal = self.wal.borrow_mut();
+        let (Some(prev), Some(state)) = (committed.as_mut(), wal.as_mut()) else {
+            return Ok(false);
+        };
+        // The log holds records in the layout of the snapshot's version; an
+        // upgraded store is rewritten before anything new is logged.
+        if state.should_compact() || !self.path.exists() || self.migrated_from.get().is_some() {
+            return Ok(false);
+        }
+        let Some(record) = prev.diff(&self.data, only) else {
+            return Ok(false);
+        };
+        if prev.is_noop(&record) {
+            return Ok(true);
+        }
+        let commit = CommitLock::exclusive(&self.path)?;
+        wal::append(&wal::wal_path(&self.path), state, &record)?;
+        drop(commit);
+        prev.commit(&record);
+
+        let ids: Vec<i64> = record.file_ids().collect();
+        if let Some(index) = self.index.borrow_mut().as_mut() {
+            index.update(ids.iter().map(|&id| (id, self.file(id))));
+        }
+        self.logged.borrow_mut().extend(ids);
+        Ok(true)
+    }
+
+    /// Path index for the current data, loaded from the sidecar (or rebuilt
+    /// when it is missing or out of date) on first use.
+    pub fn path_index(&self) -> Ref<'_, PathIndex> {
+        if self.index.borrow().is_none() {
+            let sidecar = PathIndex::sidecar_path(&self.path);
+            let mut index = self.load_index(&sidecar);
+            if index.is_current(&self.path) {
+                let mut logged = self.logged.borrow().clone();
+                logged.sort_unstable();
+                logged.dedup();
+                index.update(logged.into_iter().map(|id| (id, self.file(id))));
+            } else {
+                index.sync(self.files());
+                if self.path.exists() {
+                    index.mark_current(&self.path);
+                    if let Err(err) = index.save(&sidecar) {
+                        tracing::warn!("failed to save path index: {:#}", err);
+                    }
+                }
+            }
+            *self.index.borrow_mut() = Some(index);
+        }
+        Ref::map(self.index.borrow(), |index| {
+            index.as_ref().expect("path index loaded above")
+        })
+    }
+
+    /// Syncs the path index with the snapshot just written. The index is a
+    /// cache, so failures are logged rather than failing the save.
+    fn refresh_index(&self) {
+        let sidecar = PathIndex::sidecar_path(&self.path);
+        let mut slot = self.index.borrow_mut();
+        let mut index = slot.take().unwrap_or_else(|| self.load_index(&sidecar));
+        index.sync(self.files());
+        index.mark_current(&self.path);
+        if let Err(err) = index.save(&sidecar) {
+            tracing::warn!("failed to save path index: {:#}", err);
+        }
+        *slot = Some(index);
+    }
+
+    fn load_index(&self, sidecar: &Path) -> PathIndex {
+        match PathIndex::load(sidecar) {
+            Ok(index) => index.unwrap_or_default(),
+            Err(err) => {
+                tracing::debug!("rebuilding path index: {:#}", err);
+                PathIndex::default()
+            }
+        }
+    }
+
     pub fn export_json(&self) -> Result<String> {
-        let json =
-            serde_json::to_string_pretty(&self.data).context("failed to serialize store json")?;
+        let json = if self.snapshot.is_some() {
+            let mut data = self.data.clone();
+            data.files = self.files().map(|f| f.to_entry()).collect();
+            data.files.sort_by_key(|f| f.id);
+            serde_json::to_string_pretty(&data)
+        } else {
+            serde_json::to_string_pretty(&self.data)
+        }
+        .context("failed to serialize store json")?;
         Ok(json)
     }
 }
 
 pub fn prune_store(path: &Path) -> Result<usize> {
     let mut removed = 0;
-    if path.exists() {
-        fs::remove_file(path)
-            .with_context(|| format!("failed to remove store: {}", path.display()))?;
-        removed += 1;
+    for file in [
+        path.to_path_buf(),
+        wal::wal_path(path),
+        PathIndex::sidecar_path(path),
+        checkpoint::checkpoint_path(path),
+    ] {
+        if file.exists() {
+            fs::remove_file(&file)
+                .with_context(|| format!("failed to remove store: {}", file.display()))?;
+            removed += 1;
+        }
     }
     Ok(removed)
 }
@@ -194,6 +694,7 @@ impl StoreData {
             file_tags: Vec::new(),
             dir_sizes_run_id: 0,
             dir_sizes: Vec::new(),
+            changes: Vec::new(),
         }
     }
 
@@ -215,6 +716,21 @@ impl StoreData {
         }
     }
 
+    /// Entries are kept ordered by id (new ids are only ever appended), which
+    /// `file_by_id` relies on.
+    pub fn ensure_sorted(&mut self) {
+        if !self.files.is_sorted_by_key(|f| f.id) {
+            self.files.sort_by_key(|f| f.id);
+        }
+    }
+
+    pub fn file_by_id(&self, id: i64) -> Option<&FileEntry> {
+        self.files
+            .binary_search_by_key(&id, |f| f.id)
+            .ok()
+            .map(|idx| &self.files[idx])
+    }
+
     pub fn next_root_id(&mut self) -> i64 {
         let id = self.next_root_id;
         self.next_root_id += 1;
@@ -227,6 +743,12 @@ impl StoreData {
         id
     }
 
+    pub fn next_tag_id(&mut self) -> i64 {
+        let id = self.next_tag_id;
+        self.next_tag_id += 1;
+        id
+    }
+
     pub fn next_run_id(&mut self) -> i64 {
         self.last_run_id += 1;
         self.last_run_id
@@ -291,6 +813,8 @@ mod tests {
             preset_name: Some("preset".to_string()),
             last_indexed_at: None,
             one_filesystem: true,
+            volume: None,
+            scan: None,
         });
         let file_id = store.data.next_file_id();
         store.data.files.push(FileEntry {
@@ -305,15 +829,305 @@ mod tests {
             ext: Some("txt".to_string()),
             status: "active".to_string(),
             last_seen_run: 1,
+            dev: None,
+            ino: None,
+            prev_path: None,
+            deleted_at: None,
         });
 
         store.save().unwrap();
 
         let loaded = Store::load(&path).unwrap();
+        let files: Vec<_> = loaded.files().collect();
         assert_eq!(loaded.data.roots.len(), 1);
-        assert_eq!(loaded.data.files.len(), 1);
+        assert_eq!(files.len(), 1);
         assert_eq!(loaded.data.roots[0].path, "/tmp/root");
-        assert_eq!(loaded.data.files[0].abs_path, "/tmp/root/file.txt");
+        assert_eq!(files[0].abs_path(), "/tmp/root/file.txt");
+        assert_eq!(files[0].ext, Some("txt"));
+        assert_eq!(loaded.file(file_id).map(|f| f.size), Some(12));
+
+        let opened = Store::open(&path).unwrap();
+        assert_eq!(opened.data.files.len(), 1);
+        assert_eq!(opened.data.files[0].abs_path, "/tmp/root/file.txt");
+    }
+
+    fn file(id: i64, rel_path: &str) -> FileEntry {
+        FileEntry {
+            id,
+            root_id: 1,
+            rel_path: rel_path.to_string(),
+            abs_path: format!("/tmp/root/{}", rel_path),
+            is_dir: false,
+            is_symlink: false,
+            size: 1,
+            mtime: 2,
+            ext: None,
+            status: "active".to_string(),
+            last_seen_run: 1,
+            dev: None,
+            ino: None,
+            prev_path: None,
+            deleted_at: None,
+        }
+    }
+
+    fn paths(store: &Store) -> Vec<(i64, String, String)> {
+        let mut out: Vec<_> = store
+            .files()
+            .map(|f| (f.id, f.abs_path().into_owned(), f.status.to_string()))
+            .collect();
+        out.sort();
+        out
+    }
+
+    #[test]
+    fn save_appends_to_log_and_load_replays_it() {
+        let dir = temp_dir("wal");
+        let path = dir.join("store.bin");
+
+        let mut store = Store::open(&path).unwrap();
+        for rel in ["a.txt", "b.txt", "c.txt"] {
+            let id = store.data.next_file_id();
+            store.data.files.push(file(id, rel));
+        }
+        store.save().unwrap();
+        let snapshot = fs::read(&path).unwrap();
+        assert!(!wal::wal_path(&path).exists());
+
+        store.data.files[0].status = "deleted".to_string();
+        store.data.files.remove(1);
+        let id = store.data.next_file_id();
+        store.data.files.push(file(id, "d.txt"));
+        let tag_id = store.data.next_tag_id();
+        store.data.tags.push(TagEntry {
+            id: tag_id,
+            name: "work".to_string(),
+        });
+        store.save().unwrap();
+        store.data.files[1].size = 99;
+        store.save().unwrap();
+
+        assert_eq!(fs::read(&path).unwrap(), snapshot, "snapshot is not rewritten");
+        assert!(wal::wal_path(&path).exists());
+        let loaded = Store::load(&path).unwrap();
+        assert_eq!(paths(&loaded), paths(&store));
+        assert_eq!(loaded.file(store.data.files[1].id).map(|f| f.size), Some(99));
+        assert_eq!(loaded.data.tags.len(), 1);
+        assert_eq!(loaded.data.next_file_id, 5);
+
+        store.compact().unwrap();
+        assert!(!wal::wal_path(&path).exists());
+        let loaded = Store::load(&path).unwrap();
+        assert_eq!(paths(&loaded), paths(&store));
+    }
+
+    #[test]
+    fn journal_prunes_and_appends_replay_from_the_log() {
+        let dir = temp_dir("wal_changes");
+        let path = dir.join("store.bin");
+        let change = |at: i64, path: &str| ChangeEntry {
+            run_id: 1,
+            at,
+            file_id: 1,
+            root_id: 1,
+            kind: "created".to_string(),
+            path: path.to_string(),
+            prev_path: None,
+            is_dir: false,
+            old_size: None,
+            new_size: Some(1),
+            old_mtime: None,
+            new_mtime: Some(at),
+        };
+        let journal = |store: &Store| {
+            store
+                .data
+                .changes
+                .iter()
+                .map(|c| (c.at, c.path.clone()))
+                .collect::<Vec<_>>()
+        };
+
+        // Identical records ahead of the kept ones must not be taken for
+        // their start.
+        let mut store = Store::open(&path).unwrap();
+        store.data.changes = vec![
+            change(1, "/a"),
+            change(2, "/b"),
+            change(1, "/a"),
+            change(3, "/c"),
+        ];
+        store.save().unwrap();
+        store.data.changes.drain(..2);
+        store.data.changes.push(change(4, "/d"));
+        store.save().unwrap();
+        assert_eq!(journal(&Store::load(&path).unwrap()), journal(&store));
+
+        store.data.changes.clear();
+        store.save().unwrap();
+        assert!(journal(&Store::load(&path).unwrap()).is_empty());
+
+        // Anything but a prune and an append replaces the whole journal.
+        store.data.changes = vec![change(5, "/e"), change(6, "/f")];
+        store.save().unwrap();
+        store.data.changes[0].path = "/g".to_string();
+        store.save().unwrap();
+        assert_eq!(journal(&Store::load(&path).unwrap()), journal(&store));
+    }
+
+    #[test]
+    fn torn_log_tail_is_dropped() {
+        let dir = temp_dir("wal_torn");
+        let path = dir.join("store.bin");
+
+        let mut store = Store::open(&path).unwrap();
+        let id = store.data.next_file_id();
+        store.data.files.push(file(id, "a.txt"));
+        store.save().unwrap();
+        let id = store.data.next_file_id();
+        store.data.files.push(file(id, "b.txt"));
+        store.save().unwrap();
+
+        let wal_path = wal::wal_path(&path);
+        let intact = fs::read(&wal_path).unwrap();
+        let mut torn = intact.clone();
+        torn.extend_from_slice(&[42, 0, 0, 0, 1, 2, 3]);
+        fs::write(&wal_path, &torn).unwrap();
+
+        let mut store = Store::open(&path).unwrap();
+        assert_eq!(store.data.files.len(), 2);
+        let id = store.data.next_file_id();
+        store.data.files.push(file(id, "c.txt"));
+        store.save().unwrap();
+
+        let loaded = Store::load(&path).unwrap();
+        assert_eq!(paths(&loaded), paths(&store));
+        assert!(fs::read(&wal_path).unwrap().starts_with(&intact));
+    }
+
+    #[test]
+    fn log_appends_keep_the_path_index_without_rewriting_it() {
+        let dir = temp_dir("wal_index");
+        let path = dir.join("store.bin");
+        let candidates = |store: &Store, text: &str| {
+            store
+                .path_index()
+                .candidates(&crate::query::parse(text).unwrap())
+                .unwrap()
+        };
+
+        let mut store = Store::open(&path).unwrap();
+        for rel in ["a.txt", "b.txt", "c.txt"] {
+            let id = store.data.next_file_id();
+            store.data.files.push(file(id, rel));
+        }
+        store.save().unwrap();
+        let sidecar = PathIndex::sidecar_path(&path);
+        let written = fs::read(&sidecar).unwrap();
+        assert_eq!(candidates(&store, "b.txt"), vec![2]);
+
+        store.data.files[0].abs_path = "/tmp/root/renamed.txt".to_string();
+        store.data.files[2].size = 99;
+        store.data.files.remove(1);
+        let id = store.data.next_file_id();
+        store.data.files.push(file(id, "d.txt"));
+        // Entry 3 changed too, but is not named, so it is not compared.
+        store.save_changed(&HashSet::from([1, 2, id])).unwrap();
+
+        assert_eq!(
+            fs::read(&sidecar).unwrap(),
+            written,
+            "sidecar is not rewritten"
+        );
+        assert_eq!(candidates(&store, "renamed"), vec![1]);
+        assert_eq!(candidates(&store, "/d.txt"), vec![4]);
+
+        let loaded = Store::load(&path).unwrap();
+        assert_eq!(candidates(&loaded, "renamed"), vec![1]);
+        assert_eq!(candidates(&loaded, "/d.txt"), vec![4]);
+        assert!(loaded.file(2).is_none());
+        assert_eq!(loaded.file(3).map(|f| f.size), Some(1));
+        assert_eq!(fs::read(&sidecar).unwrap(), written);
+    }
+
+    #[test]
+    fn log_from_previous_snapshot_is_ignored() {
+        let dir = temp_dir("wal_stale");
+        let path = dir.join("store.bin");
+
+        let mut store = Store::open(&path).unwrap();
+        let id = store.data.next_file_id();
+        store.data.files.push(file(id, "a.txt"));
+        store.save().unwrap();
+        let id = store.data.next_file_id();
+        store.data.files.push(file(id, "b.txt"));
+        store.save().unwrap();
+        let old_log = fs::read(wal::wal_path(&path)).unwrap();
+
+        store.data.files.remove(1);
+        store.compact().unwrap();
+        // As if the process died between writing the snapshot and removing
+        // the log.
+        fs::write(wal::wal_path(&path), old_log).unwrap();
+
+        let loaded = Store::load(&path).unwrap();
+        assert_eq!(loaded.files().count(), 1);
+    }
+
+    #[test]
+    fn bincode_snapshot_is_rewritten_in_mapped_layout() {
+        let dir = temp_dir("legacy");
+        let path = dir.join("store.bin");
+        let mut data = StoreData::new();
+        // Stores before version 7 were bincode snapshots; read one of the current layout.
+        data.version = STORE_VERSION;
+        data.files.push(file(1, "a.txt"));
+        data.files.push(file(2, "b.txt"));
+        data.next_file_id = 3;
+        fs::write(&path, bincode::serialize(&data).unwrap()).unwrap();
+
+        let loaded = Store::load(&path).unwrap();
+        assert_eq!(loaded.data.version, STORE_VERSION);
+        assert_eq!(loaded.files().count(), 2);
+
+        let store = Store::open(&path).unwrap();
+        store.compact().unwrap();
+        assert!(snapshot::is_snapshot(&fs::read(&path).unwrap()));
+        let loaded = Store::load(&path).unwrap();
+        assert_eq!(paths(&loaded), paths(&store));
+        assert_eq!(loaded.data.next_file_id, 3);
+    }
+
+    #[test]
+    fn snapshot_shares_directories_and_rebuilds_paths() {
+        let dir = temp_dir("dir_tree");
+        let path = dir.join("store.bin");
+        let mut store = Store::open(&path).unwrap();
+        for rel in ["docs/a.txt", "docs/b.txt", "docs/old/c.txt", "src/main.rs", ""] {
+            let id = store.data.next_file_id();
+            store.data.files.push(file(id, rel));
+        }
+        // Paths that do not split into a directory and a relative tail are
+        // kept whole.
+        let mut odd = file(store.data.next_file_id(), "elsewhere.txt");
+        odd.abs_path = "/mnt/other/elsewhere.txt.bak".to_string();
+        store.data.files.push(odd);
+        let mut top = file(store.data.next_file_id(), "top");
+        top.abs_path = "/top".to_string();
+        store.data.files.push(top);
+        store.compact().unwrap();
+
+        let loaded = Store::load(&path).unwrap();
+        let snapshot = loaded.snapshot.as_ref().unwrap();
+        // "", tmp, root, docs, old, src
+        assert_eq!(snapshot.dir_count(), 6);
+        assert_eq!(paths(&loaded), paths(&store));
+        let entries: Vec<FileEntry> = loaded.files().map(|f| f.to_entry()).collect();
+        assert_eq!(format!("{:?}", entries), format!("{:?}", store.data.files));
+
+        let raw = fs::read(&path).unwrap();
+        let needle = b"/tmp/root/docs";
+        assert!(!raw.windows(needle.len()).any(|w| w == needle));
     }
 
     #[test]
@@ -328,6 +1142,8 @@ mod tests {
             preset_name: None,
             last_indexed_at: None,
             one_filesystem: true,
+            volume: None,
+            scan: None,
         });
         data.files.push(FileEntry {
             id: 7,
@@ -341,6 +1157,10 @@ mod tests {
             ext: Some("txt".to_string()),
             status: "active".to_string(),
             last_seen_run: 1,
+            dev: None,
+            ino: None,
+            prev_path: None,
+            deleted_at: None,
         });
         data.ensure_counters();
         assert_eq!(data.next_root_id, 6);
@@ -349,10 +1169,7 @@ mod tests {
 
     #[test]
     fn export_json_round_trip() {
-        let mut store = Store {
-            path: PathBuf::from("/tmp/catalog.bin"),
-            data: StoreData::new(),
-        };
+        let mut store = Store::from_data(PathBuf::from("/tmp/catalog.bin"), StoreData::new());
         let root_id = store.data.next_root_id();
         let file_id = store.data.next_file_id();
         store.data.roots.push(RootEntry {
@@ -362,6 +1179,8 @@ mod tests {
             preset_name: None,
             last_indexed_at: None,
             one_filesystem: true,
+            volume: None,
+            scan: None,
         });
         store.data.files.push(FileEntry {
             id: file_id,
@@ -375,6 +1194,10 @@ mod tests {
             ext: Some("txt".to_string()),
             status: "active".to_string(),
             last_seen_run: 1,
+            dev: None,
+            ino: None,
+            prev_path: None,
+            deleted_at: None,
         });
 
         let json = store.export_json().unwrap();
@@ -395,6 +1218,8 @@ mod tests {
             preset_name: None,
             last_indexed_at: Some((Utc::now() - ChronoDuration::hours(2)).to_rfc3339()),
             one_filesystem: true,
+            volume: None,
+            scan: None,
         });
         assert!(
             !index_is_stale(&data, None, ChronoDuration::days(1)),
@@ -422,6 +1247,8 @@ mod tests {
             preset_name: None,
             last_indexed_at: Some((Utc::now() - ChronoDuration::hours(2)).to_rfc3339()),
             one_filesystem: true,
+            volume: None,
+            scan: None,
         });
         let filter = Path::new("/root/sub");
         assert!(
@@ -0,0 +1,249 @@
+use crate::store::{FileTagEntry, StoreData, TagEntry};
+use crate::util::{normalize_path_allow_missing, path_to_string};
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, Serialize)]
+pub struct TagCount {
+    pub name: String,
+    pub files: usize,
+}
+
+pub fn normalize_name(name: &str) -> Result<String> {
+    let name = name.trim().to_lowercase();
+    if name.is_empty() {
+        anyhow::bail!("tag name must not be empty");
+    }
+    if name.contains(',') || name.chars().any(char::is_whitespace) {
+        anyhow::bail!("tag name must not contain commas or whitespace: {}", name);
+    }
+    Ok(name)
+}
+
+/// Resolves CLI targets to file ids. A target is treated as an id when it is
+/// numeric and such an entry exists; otherwise it is normalized as a path and
+/// matched against `abs_path` (every root that indexed the path).
+pub fn resolve_targets(store: &StoreData, targets: &[String]) -> Result<Vec<i64>> {
+    let mut ids = Vec::new();
+    for target in targets {
+        let known_id = target
+            .parse::<i64>()
+            .ok()
+            .filter(|id| store.files.iter().any(|f| f.id == *id));
+        if let Some(id) = known_id {
+            ids.push(id);
+            continue;
+        }
+        let path = path_to_string(&normalize_path_allow_missing(target)?);
+        let before = ids.len();
+        ids.extend(
+            store
+                .files
+                .iter()
+                .filter(|f| f.abs_path == path)
+                .map(|f| f.id),
+        );
+        if ids.len() == before {
+            anyhow::bail!("not in the index: {} (run `catalog index`?)", target);
+        }
+    }
+    ids.sort_unstable();
+    ids.dedup();
+    Ok(ids)
+}
+
+/// Tags the given files, creating the tag if needed. Returns how many files
+/// newly received the tag.
+pub fn add_tag(store: &mut StoreData, name: &str, file_ids: &[i64]) -> usize {
+    let tag_id = match store.tags.iter().find(|t| t.name == name) {
+        Some(tag) => tag.id,
+        None => {
+            let id = store.next_tag_id();
+            store.tags.push(TagEntry {
+                id,
+                name: name.to_string(),
+            });
+            id
+        }
+    };
+
+    let existing: HashSet<i64> = store
+        .file_tags
+        .iter()
+        .filter(|ft| ft.tag_id == tag_id)
+        .map(|ft| ft.file_id)
+        .collect();
+    let mut added = 0;
+    for &file_id in file_ids {
+        if !existing.contains(&file_id) {
+            store.file_tags.push(FileTagEntry { file_id, tag_id });
+            added += 1;
+        }
+    }
+    added
+}
+
+/// Removes the tag from the given files and drops tags that end up unused.
+/// Returns how many files lost the tag.
+pub fn remove_tag(store: &mut StoreData, name: &str, file_ids: &[i64]) -> usize {
+    let Some(tag_id) = store.tags.iter().find(|t| t.name == name).map(|t| t.id) else {
+        return 0;
+    };
+    let targets: HashSet<i64> = file_ids.iter().copied().collect();
+    let before = store.file_tags.len();
+    store
+        .file_tags
+        .retain(|ft| !(ft.tag_id == tag_id && targets.contains(&ft.file_id)));
+    let removed = before - store.file_tags.len();
+    prune_orphans(store);
+    removed
+}
+
+/// Tag names per file id, sorted by name.
+pub fn names_by_file(store: &StoreData) -> HashMap<i64, Vec<String>> {
+    let names: HashMap<i64, &str> = store
+        .tags
+        .iter()
+        .map(|t| (t.id, t.name.as_str()))
+        .collect();
+    let mut out: HashMap<i64, Vec<String>> = HashMap::new();
+    for ft in &store.file_tags {
+        if let Some(name) = names.get(&ft.tag_id) {
+            out.entry(ft.file_id).or_default().push(name.to_string());
+        }
+    }
+    for tags in out.values_mut() {
+        tags.sort();
+    }
+    out
+}
+
+/// Ids of files carrying every one of `names`. Returns `None` when no tag
+/// filter was requested.
+pub fn files_with_all(store: &StoreData, names: &[String]) -> Result<Option<HashSet<i64>>> {
+    if names.is_empty() {
+        return Ok(None);
+    }
+    let mut result: Option<HashSet<i64>> = None;
+    for name in names {
+        let name = normalize_name(name)?;
+        let files: HashSet<i64> = match store.tags.iter().find(|t| t.name == name) {
+            Some(tag) => store
+                .file_tags
+                .iter()
+                .filter(|ft| ft.tag_id == tag.id)
+                .map(|ft| ft.file_id)
+                .collect(),
+            None => HashSet::new(),
+        };
+        result = Some(match result {
+            Some(acc) => acc.intersection(&files).copied().collect(),
+            None => files,
+        });
+    }
+    Ok(result)
+}
+
+pub fn tag_counts(store: &StoreData) -> Vec<TagCount> {
+    let mut counts: HashMap<i64, usize> = HashMap::new();
+    for ft in &store.file_tags {
+        *counts.entry(ft.tag_id).or_insert(0) += 1;
+    }
+    let mut out = store
+        .tags
+        .iter()
+        .map(|t| TagCount {
+            name: t.name.clone(),
+            files: counts.get(&t.id).copied().unwrap_or(0),
+        })
+        .collect::<Vec<_>>();
+    out.sort_by(|a, b| a.name.cmp(&b.name));
+    out
+}
+
+pub fn print_tag_counts(counts: &[TagCount], json: bool) -> Result<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(counts)?);
+        return Ok(());
+    }
+    if counts.is_empty() {
+        println!("No tags.");
+    }
+    for tag in counts {
+        println!("{}  {}", tag.name, tag.files);
+    }
+    Ok(())
+}
+
+pub fn prune_orphans(store: &mut StoreData) {
+    let mut used = HashSet::new();
+    for ft in &store.file_tags {
+        used.insert(ft.tag_id);
+    }
+    store.tags.retain(|t| used.contains(&t.id));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::FileEntry;
+
+    fn file(id: i64, abs_path: &str) -> FileEntry {
+        FileEntry {
+            id,
+            root_id: 1,
+            rel_path: abs_path.trim_start_matches("/catalog_tags_test/").to_string(),
+            abs_path: abs_path.to_string(),
+            is_dir: false,
+            is_symlink: false,
+            size: 1,
+            mtime: 1,
+            ext: None,
+            status: "active".to_string(),
+            last_seen_run: 1,
+            dev: None,
+            ino: None,
+            prev_path: None,
+            deleted_at: None,
+        }
+    }
+
+    #[test]
+    fn add_and_remove_tags() {
+        let mut store = StoreData::new();
+        store.files.push(file(1, "/catalog_tags_test/setup.dmg"));
+        store.files.push(file(2, "/catalog_tags_test/LICENSE"));
+
+        let targets = vec!["1".to_string(), "/catalog_tags_test/LICENSE".to_string()];
+        let ids = resolve_targets(&store, &targets).unwrap();
+        assert_eq!(ids, vec![1, 2]);
+        assert!(resolve_targets(&store, &["/catalog_tags_test/missing".to_string()]).is_err());
+
+        assert_eq!(add_tag(&mut store, "keep", &ids), 2);
+        assert_eq!(add_tag(&mut store, "keep", &ids), 0);
+        assert_eq!(add_tag(&mut store, "installer", &[1]), 1);
+
+        let names = names_by_file(&store);
+        assert_eq!(names[&1], vec!["installer".to_string(), "keep".to_string()]);
+        let both = files_with_all(&store, &["keep".to_string(), "installer".to_string()])
+            .unwrap()
+            .unwrap();
+        assert_eq!(both.into_iter().collect::<Vec<_>>(), vec![1]);
+
+        assert_eq!(remove_tag(&mut store, "installer", &[1]), 1);
+        assert!(store.tags.iter().all(|t| t.name != "installer"));
+        let counts = tag_counts(&store);
+        assert_eq!(counts.len(), 1);
+        assert_eq!(counts[0].name, "keep");
+        assert_eq!(counts[0].files, 2);
+    }
+
+    #[test]
+    fn normalize_name_rejects_bad_names() {
+        assert_eq!(normalize_name(" Work ").unwrap(), "work");
+        assert!(normalize_name("").is_err());
+        assert!(normalize_name("a,b").is_err());
+        assert!(normalize_name("two words").is_err());
+    }
+}
@@ -49,3 +49,26 @@ pub fn normalize_path_allow_missing(input: &str) -> Result<PathBuf> {
 pub fn path_to_string(path: &Path) -> String {
     path.to_string_lossy().to_string()
 }
+
+#[cfg(unix)]
+pub fn device_id(meta: &std::fs::Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(meta.dev())
+}
+
+#[cfg(not(unix))]
+pub fn device_id(_meta: &std::fs::Metadata) -> Option<u64> {
+    None
+}
+
+/// Device and inode numbers, which survive renames within a filesystem.
+#[cfg(unix)]
+pub fn file_identity(meta: &std::fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((meta.dev(), meta.ino()))
+}
+
+#[cfg(not(unix))]
+pub fn file_identity(_meta: &std::fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
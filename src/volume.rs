@@ -0,0 +1,223 @@
+use std::path::{Path, PathBuf};
+
+/// A mounted filesystem. `uuid` and `label` identify it across mounts; a
+/// volume without either cannot be recognized once it moves.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Volume {
+    pub uuid: Option<String>,
+    pub label: Option<String>,
+    pub mount_point: PathBuf,
+}
+
+impl Volume {
+    pub fn has_identity(&self) -> bool {
+        self.uuid.is_some() || self.label.is_some()
+    }
+}
+
+/// The volume `path` lives on: the mount with the longest mount point that
+/// contains it. Returns `None` when the mount table is unavailable.
+pub fn volume_for(path: &Path) -> Option<Volume> {
+    containing(&mounted_volumes(), path).cloned()
+}
+
+pub fn containing<'a>(volumes: &'a [Volume], path: &Path) -> Option<&'a Volume> {
+    volumes
+        .iter()
+        .filter(|v| path.starts_with(&v.mount_point))
+        .max_by_key(|v| v.mount_point.components().count())
+}
+
+#[cfg(target_os = "linux")]
+pub fn mounted_volumes() -> Vec<Volume> {
+    let Ok(mountinfo) = std::fs::read_to_string("/proc/self/mountinfo") else {
+        return Vec::new();
+    };
+    let uuids = device_links(Path::new("/dev/disk/by-uuid"));
+    let labels = device_links(Path::new("/dev/disk/by-label"));
+    parse_mountinfo(&mountinfo, &uuids, &labels)
+}
+
+#[cfg(target_os = "macos")]
+pub fn mounted_volumes() -> Vec<Volume> {
+    let mut paths = vec![PathBuf::from("/")];
+    if let Ok(entries) = std::fs::read_dir("/Volumes") {
+        paths.extend(entries.flatten().map(|e| e.path()));
+    }
+    let mut out: Vec<Volume> = Vec::new();
+    for path in paths {
+        let Some(volume) = diskutil_info(&path) else {
+            continue;
+        };
+        // `/Volumes/Macintosh HD` is a symlink back to `/`.
+        if !out.iter().any(|v| v.mount_point == volume.mount_point) {
+            out.push(volume);
+        }
+    }
+    out
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+pub fn mounted_volumes() -> Vec<Volume> {
+    Vec::new()
+}
+
+/// `(canonical device path, name)` for every symlink in a `/dev/disk/by-*`
+/// directory.
+#[cfg(target_os = "linux")]
+fn device_links(dir: &Path) -> Vec<(PathBuf, String)> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| {
+            let device = std::fs::canonicalize(entry.path()).ok()?;
+            let name = unescape_udev(&entry.file_name().to_string_lossy());
+            Some((device, name))
+        })
+        .collect()
+}
+
+/// Parses `/proc/self/mountinfo`, naming each mount's source device from the
+/// udev link tables. Bind mounts of a subdirectory are skipped since paths
+/// under them would not line up with the volume's own root.
+#[cfg(target_os = "linux")]
+fn parse_mountinfo(
+    text: &str,
+    uuids: &[(PathBuf, String)],
+    labels: &[(PathBuf, String)],
+) -> Vec<Volume> {
+    let lookup = |table: &[(PathBuf, String)], device: &Path| {
+        table
+            .iter()
+            .find(|(dev, _)| dev == device)
+            .map(|(_, name)| name.clone())
+    };
+    let mut out = Vec::new();
+    for line in text.lines() {
+        let Some((mount, fs)) = line.split_once(" - ") else {
+            continue;
+        };
+        let fields: Vec<&str> = mount.split(' ').collect();
+        let (Some(root), Some(mount_point)) = (fields.get(3), fields.get(4)) else {
+            continue;
+        };
+        if *root != "/" {
+            continue;
+        }
+        let source = fs.split(' ').nth(1).unwrap_or("");
+        let (uuid, label) = if source.starts_with("/dev/") {
+            let device = std::fs::canonicalize(source).unwrap_or_else(|_| PathBuf::from(source));
+            (lookup(uuids, &device), lookup(labels, &device))
+        } else {
+            (None, None)
+        };
+        out.push(Volume {
+            uuid,
+            label,
+            mount_point: PathBuf::from(unescape_octal(mount_point)),
+        });
+    }
+    out
+}
+
+/// Undoes the `\040`-style escaping the kernel applies to mount points.
+#[cfg(target_os = "linux")]
+fn unescape_octal(input: &str) -> String {
+    unescape(input, |code| u8::from_str_radix(code, 8).ok())
+}
+
+/// Undoes the `\x20`-style escaping udev applies to labels.
+#[cfg(target_os = "linux")]
+fn unescape_udev(input: &str) -> String {
+    unescape(input, |code| {
+        code.strip_prefix('x')
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+    })
+}
+
+/// Replaces each backslash followed by a three-character code that `decode`
+/// accepts with the decoded byte.
+#[cfg(target_os = "linux")]
+fn unescape(input: &str, decode: impl Fn(&str) -> Option<u8>) -> String {
+    let bytes = input.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'\\'
+            && let Some(byte) = input.get(i + 1..i + 4).and_then(&decode)
+        {
+            out.push(byte);
+            i += 4;
+            continue;
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).to_string()
+}
+
+#[cfg(target_os = "macos")]
+fn diskutil_info(path: &Path) -> Option<Volume> {
+    let output = std::process::Command::new("diskutil")
+        .arg("info")
+        .arg(path)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    parse_diskutil(&String::from_utf8_lossy(&output.stdout))
+}
+
+#[cfg(target_os = "macos")]
+fn parse_diskutil(text: &str) -> Option<Volume> {
+    let field = |name: &str| {
+        text.lines().find_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            let value = value.trim();
+            (key.trim() == name && !value.is_empty()).then(|| value.to_string())
+        })
+    };
+    Some(Volume {
+        uuid: field("Volume UUID"),
+        label: field("Volume Name"),
+        mount_point: PathBuf::from(field("Mount Point")?),
+    })
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mountinfo_names_volumes_by_device() {
+        let text = "\
+22 1 8:2 / / rw,relatime shared:1 - ext4 /dev/catalog-test-sda2 rw
+40 22 8:17 / /media/me/My\\040Disk rw,nosuid shared:2 - exfat /dev/catalog-test-sdb1 rw
+41 22 8:17 /photos /srv/photos rw shared:2 - exfat /dev/catalog-test-sdb1 rw
+42 22 0:30 / /tmp rw - tmpfs tmpfs rw
+";
+        let uuids = vec![
+            (PathBuf::from("/dev/catalog-test-sda2"), "1111-aaaa".to_string()),
+            (PathBuf::from("/dev/catalog-test-sdb1"), "2222-BBBB".to_string()),
+        ];
+        let labels = vec![(
+            PathBuf::from("/dev/catalog-test-sdb1"),
+            unescape_udev("My\\x20Disk"),
+        )];
+        let volumes = parse_mountinfo(text, &uuids, &labels);
+        assert_eq!(volumes.len(), 3);
+        assert_eq!(volumes[1].mount_point, PathBuf::from("/media/me/My Disk"));
+        assert_eq!(volumes[1].uuid.as_deref(), Some("2222-BBBB"));
+        assert_eq!(volumes[1].label.as_deref(), Some("My Disk"));
+        assert!(!volumes[2].has_identity());
+
+        let file = Path::new("/media/me/My Disk/photos/a.jpg");
+        let found = containing(&volumes, file).unwrap();
+        assert_eq!(found.uuid.as_deref(), Some("2222-BBBB"));
+        let tmp = containing(&volumes, Path::new("/tmp/x")).unwrap();
+        assert_eq!(tmp.mount_point, PathBuf::from("/tmp"));
+    }
+}
@@ -0,0 +1,420 @@
+use crate::store::{
+    ChangeEntry, DirSizeEntry, FileEntry, FileOverlay, FileTagEntry, RootEntry, StoreData,
+    TagEntry,
+};
+use crate::migrate;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, OpenOptions};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io::{Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+const MAGIC: &[u8; 8] = b"CATWAL01";
+const HEADER_LEN: u64 = 8 + 8 + 4;
+
+/// Compact once the log outgrows a quarter of the snapshot, or this many
+/// bytes, whichever is larger.
+const COMPACT_MIN_BYTES: u64 = 1 << 20;
+
+/// Write-ahead log of store mutations, stored next to the snapshot
+/// (`catalog.bin` -> `catalog.wal`). Each `Store::save` appends one record
+/// with the entries that changed since the last save; `Store::load` replays
+/// the records on top of the snapshot they were written against.
+///
+/// Layout: magic, length and CRC32 of the base snapshot, then records framed
+/// as `len: u32, crc: u32, bincode payload`. A torn or corrupt record ends the
+/// log; it and anything after it are dropped on the next append.
+pub fn wal_path(store_path: &Path) -> PathBuf {
+    store_path.with_extension("wal")
+}
+
+/// Identity of the snapshot a log applies to. A log whose header does not
+/// match the snapshot on disk was left behind by an interrupted compaction
+/// and is ignored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SnapshotId {
+    pub len: u64,
+    pub crc: u32,
+}
+
+impl SnapshotId {
+    pub fn of(raw: &[u8]) -> Self {
+        Self {
+            len: raw.len() as u64,
+            crc: crc32fast::hash(raw),
+        }
+    }
+}
+
+/// Where the log on disk stands relative to the in-memory data.
+#[derive(Debug, Clone, Copy)]
+pub struct WalState {
+    pub base: SnapshotId,
+    /// Bytes of header and intact records; 0 when there is no log yet.
+    pub len: u64,
+}
+
+impl WalState {
+    pub fn new(base: SnapshotId) -> Self {
+        Self { base, len: 0 }
+    }
+
+    pub fn should_compact(&self) -> bool {
+        self.len > COMPACT_MIN_BYTES.max(self.base.len / 4)
+    }
+}
+
+/// Difference between two saves. Scalars are always carried; tables other
+/// than `files` and `changes` are small and replaced whole when they change.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct WalRecord {
+    pub(crate) counters: Counters,
+    pub(crate) upserted: Vec<FileEntry>,
+    pub(crate) removed: Vec<i64>,
+    pub(crate) roots: Option<Vec<RootEntry>>,
+    pub(crate) tags: Option<Vec<TagEntry>>,
+    pub(crate) file_tags: Option<Vec<FileTagEntry>>,
+    pub(crate) dir_sizes: Option<Vec<DirSizeEntry>>,
+    /// Records dropped from the front of `changes` (pruned by age).
+    pub(crate) changes_dropped: usize,
+    pub(crate) changes_appended: Vec<ChangeEntry>,
+}
+
+impl WalRecord {
+    fn is_empty(&self) -> bool {
+        self.upserted.is_empty()
+            && self.removed.is_empty()
+            && self.roots.is_none()
+            && self.tags.is_none()
+            && self.file_tags.is_none()
+            && self.dir_sizes.is_none()
+            && self.changes_dropped == 0
+            && self.changes_appended.is_empty()
+    }
+
+    /// Ids of the file entries the record upserts or removes.
+    pub fn file_ids(&self) -> impl Iterator<Item = i64> + '_ {
+        self.upserted
+            .iter()
+            .map(|f| f.id)
+            .chain(self.removed.iter().copied())
+    }
+
+    /// Applies the record to `data`, or its file entries to `overlay` when
+    /// they live in a mapped snapshot.
+    fn apply(self, data: &mut StoreData, overlay: Option<&mut FileOverlay>) {
+        self.counters.apply(data);
+        if let Some(overlay) = overlay {
+            for id in self.removed {
+                overlay.insert(id, None);
+            }
+            for file in self.upserted {
+                overlay.insert(file.id, Some(file));
+            }
+        } else {
+            if !self.removed.is_empty() {
+                let removed: HashSet<i64> = self.removed.into_iter().collect();
+                data.files.retain(|f| !removed.contains(&f.id));
+            }
+            for file in self.upserted {
+                match data.files.binary_search_by_key(&file.id, |f| f.id) {
+                    Ok(idx) => data.files[idx] = file,
+                    Err(idx) => data.files.insert(idx, file),
+                }
+            }
+        }
+        if let Some(roots) = self.roots {
+            data.roots = roots;
+        }
+        if let Some(tags) = self.tags {
+            data.tags = tags;
+        }
+        if let Some(file_tags) = self.file_tags {
+            data.file_tags = file_tags;
+        }
+        if let Some(dir_sizes) = self.dir_sizes {
+            data.dir_sizes = dir_sizes;
+        }
+        let dropped = self.changes_dropped.min(data.changes.len());
+        data.changes.drain(..dropped);
+        data.changes.extend(self.changes_appended);
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct Counters {
+    last_run_id: i64,
+    next_root_id: i64,
+    next_file_id: i64,
+    next_tag_id: i64,
+    dir_sizes_run_id: i64,
+}
+
+impl Counters {
+    fn of(data: &StoreData) -> Self {
+        Self {
+            last_run_id: data.last_run_id,
+            next_root_id: data.next_root_id,
+            next_file_id: data.next_file_id,
+            next_tag_id: data.next_tag_id,
+            dir_sizes_run_id: data.dir_sizes_run_id,
+        }
+    }
+
+    fn apply(&self, data: &mut StoreData) {
+        data.last_run_id = self.last_run_id;
+        data.next_root_id = self.next_root_id;
+        data.next_file_id = self.next_file_id;
+        data.next_tag_id = self.next_tag_id;
+        data.dir_sizes_run_id = self.dir_sizes_run_id;
+    }
+}
+
+/// Hashes of what the snapshot and log hold, so a save can tell which
+/// entries changed without keeping a second copy of the data.
+#[derive(Debug)]
+pub struct Committed {
+    counters: Counters,
+    /// Entry hash by file id.
+    files: HashMap<i64, u64>,
+    roots: u64,
+    tags: u64,
+    file_tags: u64,
+    dir_sizes: u64,
+    changes: Vec<u64>,
+}
+
+impl Committed {
+    pub fn of(data: &StoreData) -> Self {
+        Self {
+            counters: Counters::of(data),
+            files: data.files.iter().map(|f| (f.id, hash(f))).collect(),
+            roots: hash(&data.roots),
+            tags: hash(&data.tags),
+            file_tags: hash(&data.file_tags),
+            dir_sizes: hash(&data.dir_sizes),
+            changes: data.changes.iter().map(hash).collect(),
+        }
+    }
+
+    /// Record turning the committed state into `data`, or `None` when
+    /// `files` is out of id order, which only a full snapshot can express.
+    /// With `only`, just the file entries with those ids are compared; the
+    /// caller vouches that no other entry changed.
+    pub fn diff(&self, data: &StoreData, only: Option<&HashSet<i64>>) -> Option<WalRecord> {
+        let mut record = WalRecord {
+            counters: Counters::of(data),
+            ..Default::default()
+        };
+
+        match only {
+            Some(ids) => {
+                for &id in ids {
+                    match data.file_by_id(id) {
+                        Some(file) if self.files.get(&id) != Some(&hash(file)) => {
+                            record.upserted.push(file.clone());
+                        }
+                        Some(_) => {}
+                        None if self.files.contains_key(&id) => record.removed.push(id),
+                        None => {}
+                    }
+                }
+                record.upserted.sort_by_key(|f| f.id);
+            }
+            None => {
+                if !data.files.is_sorted_by_key(|f| f.id) {
+                    return None;
+                }
+                for file in &data.files {
+                    if self.files.get(&file.id) != Some(&hash(file)) {
+                        record.upserted.push(file.clone());
+                    }
+                }
+                let kept = data.files.len() - record.upserted.len();
+                let known = record
+                    .upserted
+                    .iter()
+                    .filter(|f| self.files.contains_key(&f.id))
+                    .count();
+                if kept + known < self.files.len() {
+                    record.removed = self
+                        .files
+                        .keys()
+                        .copied()
+                        .filter(|&id| data.file_by_id(id).is_none())
+                        .collect();
+                }
+            }
+        }
+        record.removed.sort_unstable();
+
+        table(&data.roots, self.roots, &mut record.roots);
+        table(&data.tags, self.tags, &mut record.tags);
+        table(&data.file_tags, self.file_tags, &mut record.file_tags);
+        table(&data.dir_sizes, self.dir_sizes, &mut record.dir_sizes);
+
+        // `changes` only loses records at the front and gains them at the
+        // back; anything else is expressed as dropping every old record.
+        // The kept records start where the first current one sits, so only
+        // positions holding it are checked.
+        let changes: Vec<u64> = data.changes.iter().map(hash).collect();
+        let dropped = match changes.first() {
+            Some(first) => self
+                .changes
+                .iter()
+                .enumerate()
+                .filter(|&(_, h)| h == first)
+                .map(|(at, _)| at)
+                .find(|&at| changes.starts_with(&self.changes[at..])),
+            None => None,
+        }
+        .unwrap_or(self.changes.len());
+        record.changes_dropped = dropped;
+        record.changes_appended = data.changes[self.changes.len() - dropped..].to_vec();
+        Some(record)
+    }
+
+    /// Whether `record` carries nothing the committed state lacks.
+    pub fn is_noop(&self, record: &WalRecord) -> bool {
+        record.is_empty() && record.counters == self.counters
+    }
+
+    /// Takes in a record once it is on disk.
+    pub fn commit(&mut self, record: &WalRecord) {
+        self.counters = record.counters;
+        for id in &record.removed {
+            self.files.remove(id);
+        }
+        for file in &record.upserted {
+            self.files.insert(file.id, hash(file));
+        }
+        for (slot, rows) in [
+            (&mut self.roots, record.roots.as_ref().map(hash)),
+            (&mut self.tags, record.tags.as_ref().map(hash)),
+            (&mut self.file_tags, record.file_tags.as_ref().map(hash)),
+            (&mut self.dir_sizes, record.dir_sizes.as_ref().map(hash)),
+        ] {
+            if let Some(h) = rows {
+                *slot = h;
+            }
+        }
+        self.changes.drain(..record.changes_dropped);
+        self.changes
+            .extend(record.changes_appended.iter().map(hash));
+    }
+}
+
+fn table<T: Hash + Clone>(rows: &[T], committed: u64, slot: &mut Option<Vec<T>>) {
+    if hash(rows) != committed {
+        *slot = Some(rows.to_vec());
+    }
+}
+
+fn hash<T: Hash + ?Sized>(value: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Replays the log at `path` onto `data`, which must be the snapshot
+/// identified by `base` and written as store `version`; file entries go to
+/// `overlay` when one is given. Returns the bytes of intact log, or 0 when
+/// there is no usable log, and the ids of the file entries it changed.
+pub fn replay(
+    path: &Path,
+    base: SnapshotId,
+    version: u32,
+    data: &mut StoreData,
+    mut overlay: Option<&mut FileOverlay>,
+) -> Result<(u64, Vec<i64>)> {
+    let mut changed = Vec::new();
+    if !path.exists() {
+        return Ok((0, changed));
+    }
+    let raw = fs::read(path)
+        .with_context(|| format!("failed to read store log: {}", path.display()))?;
+    let header_len = HEADER_LEN as usize;
+    if raw.len() < header_len || &raw[..8] != MAGIC {
+        tracing::warn!("ignoring unreadable store log: {}", path.display());
+        return Ok((0, changed));
+    }
+    let header = SnapshotId {
+        len: u64::from_le_bytes(raw[8..16].try_into().expect("8-byte slice")),
+        crc: u32::from_le_bytes(raw[16..20].try_into().expect("4-byte slice")),
+    };
+    if header != base {
+        tracing::debug!("ignoring store log for another snapshot: {}", path.display());
+        return Ok((0, changed));
+    }
+
+    let mut pos = header_len;
+    let mut records = 0;
+    while raw.len() - pos >= 8 {
+        let len = u32::from_le_bytes(raw[pos..pos + 4].try_into().expect("4-byte slice")) as usize;
+        let crc = u32::from_le_bytes(raw[pos + 4..pos + 8].try_into().expect("4-byte slice"));
+        let Some(payload) = raw.get(pos + 8..pos + 8 + len) else {
+            break;
+        };
+        if crc32fast::hash(payload) != crc {
+            break;
+        }
+        let Ok(record) = migrate::decode_wal_record(version, payload) else {
+            break;
+        };
+        changed.extend(record.file_ids());
+        record.apply(data, overlay.as_deref_mut());
+        records += 1;
+        pos += 8 + len;
+    }
+    if pos < raw.len() {
+        tracing::warn!(
+            "dropping {} bytes of incomplete store log: {}",
+            raw.len() - pos,
+            path.display()
+        );
+    }
+    tracing::debug!("replayed {} store log record(s)", records);
+    Ok((pos as u64, changed))
+}
+
+/// Appends `record` and syncs it to disk, starting a new log when `state`
+/// has none and cutting off any torn tail first.
+pub fn append(path: &Path, state: &mut WalState, record: &WalRecord) -> Result<()> {
+    let payload = bincode::serialize(record).context("failed to serialize store log record")?;
+    let mut buf = Vec::with_capacity(HEADER_LEN as usize + 8 + payload.len());
+    if state.len == 0 {
+        buf.extend_from_slice(MAGIC);
+        buf.extend_from_slice(&state.base.len.to_le_bytes());
+        buf.extend_from_slice(&state.base.crc.to_le_bytes());
+    }
+    buf.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    buf.extend_from_slice(&crc32fast::hash(&payload).to_le_bytes());
+    buf.extend_from_slice(&payload);
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(false)
+        .open(path)
+        .with_context(|| format!("failed to open store log: {}", path.display()))?;
+    if file.metadata()?.len() != state.len {
+        file.set_len(state.len)?;
+    }
+    file.seek(SeekFrom::Start(state.len))?;
+    file.write_all(&buf)
+        .with_context(|| format!("failed to write store log: {}", path.display()))?;
+    file.sync_data()?;
+    state.len += buf.len() as u64;
+    Ok(())
+}
+
+/// Removes the log once its records are part of a new snapshot.
+pub fn remove(path: &Path) -> Result<()> {
+    if path.exists() {
+        fs::remove_file(path)
+            .with_context(|| format!("failed to remove store log: {}", path.display()))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,415 @@
+use crate::changes;
+use crate::config::Config;
+use crate::indexer::{self, IndexStats, LiveRoot, LiveStats};
+use crate::lock::WriteLock;
+use crate::store::{Store, StoreData};
+use crate::wal;
+use anyhow::Result;
+use chrono::Local;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::{BTreeSet, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Upper bound on how long a continuous burst of events is collected before
+/// it is applied, so a busy directory cannot postpone updates forever.
+const MAX_BATCH_WINDOW: Duration = Duration::from_secs(5);
+
+pub struct WatchOptions {
+    pub interval: Duration,
+    pub debounce: Duration,
+    pub full: bool,
+    pub one_filesystem: bool,
+    pub poll: bool,
+    /// Wait for the store lock at startup instead of failing when another
+    /// process holds it.
+    pub wait: bool,
+}
+
+/// The store lock is only held while an update is applied, so other
+/// commands can change the store between batches; `Writer` reloads the
+/// store when one did.
+pub fn run(store_path: &Path, cfg: &Config, opts: &WatchOptions) -> Result<()> {
+    // Busy at startup fails (or waits) as for other writers; later updates
+    // wait their turn.
+    let lock = WriteLock::acquire(store_path, "watching", opts.wait)?;
+    let mut writer = Writer {
+        seen: disk_stamp(store_path),
+    };
+    let store = &mut Store::open(store_path)?;
+    drop(lock);
+
+    if opts.poll {
+        return poll_loop(store, cfg, opts, &mut writer);
+    }
+
+    let (tx, rx) = mpsc::channel();
+    let watcher = match start_watcher(&store.data, tx) {
+        Ok(watcher) => watcher,
+        Err(err) => {
+            println!(
+                "Filesystem notifications unavailable ({:#}); falling back to polling.",
+                err
+            );
+            return poll_loop(store, cfg, opts, &mut writer);
+        }
+    };
+
+    let lock = writer.lock(store)?;
+    let stats = indexer::run(store, cfg, opts.full, opts.one_filesystem)?;
+    writer.save(store, lock, None)?;
+    print_index_stats(&stats);
+
+    println!(
+        "Watching {} root(s) for changes. Press Ctrl+C to stop.",
+        store.data.roots.len()
+    );
+    let result = event_loop(store, cfg, opts, &rx, &mut writer);
+    drop(watcher);
+    result
+}
+
+fn poll_loop(
+    store: &mut Store,
+    cfg: &Config,
+    opts: &WatchOptions,
+    writer: &mut Writer,
+) -> Result<()> {
+    println!(
+        "Watching for changes every {}s. Press Ctrl+C to stop.",
+        opts.interval.as_secs()
+    );
+    loop {
+        let lock = writer.lock(store)?;
+        let stats = indexer::run(store, cfg, opts.full, opts.one_filesystem)?;
+        writer.save(store, lock, None)?;
+        print_index_stats(&stats);
+        std::thread::sleep(opts.interval);
+    }
+}
+
+/// Takes the store lock for each update and tells whether another process
+/// saved the store since this one last did.
+struct Writer {
+    seen: Option<DiskStamp>,
+}
+
+/// Length and mtime of the snapshot, and length of its log.
+type DiskStamp = (u64, SystemTime, u64);
+
+impl Writer {
+    /// Waits for the lock, reopening `store` first when someone else saved
+    /// it. Returns whether it reloaded.
+    fn lock_and_reload(&mut self, store: &mut Store) -> Result<(WriteLock, bool)> {
+        let lock = WriteLock::acquire(&store.path, "watching", true)?;
+        let stamp = disk_stamp(&store.path);
+        if stamp == self.seen {
+            return Ok((lock, false));
+        }
+        tracing::debug!("store changed on disk; reloading");
+        *store = Store::open(&store.path)?;
+        self.seen = stamp;
+        Ok((lock, true))
+    }
+
+    fn lock(&mut self, store: &mut Store) -> Result<WriteLock> {
+        Ok(self.lock_and_reload(store)?.0)
+    }
+
+    /// Saves (only the entries in `changed`, when given) and releases the
+    /// lock.
+    fn save(
+        &mut self,
+        store: &Store,
+        lock: WriteLock,
+        changed: Option<&HashSet<i64>>,
+    ) -> Result<()> {
+        match changed {
+            Some(changed) => store.save_changed(changed)?,
+            None => store.save()?,
+        }
+        self.seen = disk_stamp(&store.path);
+        drop(lock);
+        Ok(())
+    }
+}
+
+fn disk_stamp(store_path: &Path) -> Option<DiskStamp> {
+    let meta = fs::metadata(store_path).ok()?;
+    let log = fs::metadata(wal::wal_path(store_path)).map_or(0, |m| m.len());
+    Some((meta.len(), meta.modified().ok()?, log))
+}
+
+fn start_watcher(
+    store: &StoreData,
+    tx: mpsc::Sender<notify::Result<Event>>,
+) -> Result<RecommendedWatcher> {
+    let mut watcher = notify::recommended_watcher(tx)?;
+    for root in &store.roots {
+        let path = PathBuf::from(&root.path);
+        if !path.exists() {
+            tracing::warn!("root missing: {}", root.path);
+            continue;
+        }
+        watcher.watch(&path, RecursiveMode::Recursive)?;
+    }
+    Ok(watcher)
+}
+
+fn event_loop(
+    store: &mut Store,
+    cfg: &Config,
+    opts: &WatchOptions,
+    rx: &Receiver<notify::Result<Event>>,
+    writer: &mut Writer,
+) -> Result<()> {
+    let mut live = live_roots(&mut store.data, cfg, opts.one_filesystem)?;
+    loop {
+        let batch = match next_batch(rx, opts.debounce) {
+            Some(batch) => batch,
+            None => anyhow::bail!("filesystem watcher stopped unexpectedly"),
+        };
+
+        if batch.rescan {
+            tracing::debug!("watcher requested a rescan");
+            let lock = writer.lock(store)?;
+            let stats = indexer::run(store, cfg, opts.full, opts.one_filesystem)?;
+            writer.save(store, lock, None)?;
+            print_index_stats(&stats);
+            live = live_roots(&mut store.data, cfg, opts.one_filesystem)?;
+            continue;
+        }
+
+        let (lock, reloaded) = writer.lock_and_reload(store)?;
+        if reloaded {
+            live = live_roots(&mut store.data, cfg, opts.one_filesystem)?;
+        }
+        let run_id = store.data.last_run_id + 1;
+        let stats = apply_paths(&mut store.data, &mut live, &batch.paths, run_id)?;
+        if stats.changed() {
+            store.data.last_run_id = run_id;
+            changes::prune(&mut store.data, cfg, Local::now().timestamp());
+            let changed: HashSet<i64> = live.iter_mut().flat_map(LiveRoot::take_changed).collect();
+            writer.save(store, lock, Some(&changed))?;
+            println!(
+                "Applied {} change(s) ({} updated, {} deleted, {} moved, {} skipped).",
+                stats.updated + stats.deleted + stats.moved,
+                stats.updated,
+                stats.deleted,
+                stats.moved,
+                stats.skipped
+            );
+        }
+    }
+}
+
+fn live_roots(store: &mut StoreData, cfg: &Config, one_filesystem: bool) -> Result<Vec<LiveRoot>> {
+    let roots = store.roots.clone();
+    roots
+        .iter()
+        .map(|root| LiveRoot::new(store, cfg, root, one_filesystem))
+        .collect()
+}
+
+#[derive(Default)]
+struct Batch {
+    paths: BTreeSet<PathBuf>,
+    rescan: bool,
+}
+
+impl Batch {
+    fn add(&mut self, event: notify::Result<Event>) {
+        let event = match event {
+            Ok(event) => event,
+            Err(err) => {
+                tracing::warn!("watch error: {}", err);
+                self.rescan = true;
+                return;
+            }
+        };
+        if event.need_rescan() {
+            self.rescan = true;
+        }
+        if matches!(event.kind, EventKind::Access(_)) {
+            return;
+        }
+        for path in event.paths {
+            // The parent's mtime changes whenever its entries do.
+            if let Some(parent) = path.parent() {
+                self.paths.insert(parent.to_path_buf());
+            }
+            self.paths.insert(path);
+        }
+    }
+}
+
+/// Blocks for the next event, then keeps collecting until the stream has been
+/// quiet for `debounce` (or `MAX_BATCH_WINDOW` elapses).
+fn next_batch(rx: &Receiver<notify::Result<Event>>, debounce: Duration) -> Option<Batch> {
+    let first = rx.recv().ok()?;
+    let mut batch = Batch::default();
+    batch.add(first);
+    let started = Instant::now();
+    while started.elapsed() < MAX_BATCH_WINDOW {
+        match rx.recv_timeout(debounce) {
+            Ok(event) => batch.add(event),
+            Err(RecvTimeoutError::Timeout) => break,
+            Err(RecvTimeoutError::Disconnected) => return None,
+        }
+    }
+    Some(batch)
+}
+
+/// Applies a set of changed paths to every root that contains them. Paths are
+/// visited parent-first, so descendants of a directory that was already
+/// re-walked or deleted in this batch are skipped.
+pub fn apply_paths(
+    store: &mut StoreData,
+    live: &mut [LiveRoot],
+    paths: &BTreeSet<PathBuf>,
+    run_id: i64,
+) -> Result<LiveStats> {
+    let mut stats = LiveStats::default();
+    for root in live.iter_mut() {
+        for path in paths.iter().filter(|path| root.contains(path)) {
+            root.invalidate(path);
+        }
+        let mut handled: HashSet<PathBuf> = HashSet::new();
+        for path in paths {
+            if !root.contains(path) {
+                continue;
+            }
+            if path
+                .ancestors()
+                .skip(1)
+                .take_while(|a| *a != root.root_path())
+                .any(|a| handled.contains(a))
+            {
+                continue;
+            }
+            let refreshed = root.refresh(store, path, run_id)?;
+            stats.add(refreshed.stats);
+            if refreshed.subtree {
+                handled.insert(path.clone());
+            }
+        }
+    }
+    let moved = indexer::reconcile_live_moves(store, live);
+    stats.moved += moved;
+    stats.updated = stats.updated.saturating_sub(moved);
+    stats.deleted = stats.deleted.saturating_sub(moved);
+    Ok(stats)
+}
+
+fn print_index_stats(stats: &IndexStats) {
+    println!("{}", stats.summary());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::util::path_to_string;
+    use std::fs;
+    use std::path::Path;
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    fn temp_dir(prefix: &str) -> PathBuf {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let dir = std::env::temp_dir()
+            .join(format!("catalog_test_{}_{}_{}", prefix, std::process::id(), nanos));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write_file(path: &Path, contents: &str) {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).unwrap();
+        }
+        fs::write(path, contents).unwrap();
+    }
+
+    fn status_of(store: &Store, path: &Path) -> Option<String> {
+        let path = path_to_string(path);
+        store
+            .data
+            .files
+            .iter()
+            .find(|f| f.abs_path == path)
+            .map(|f| f.status.clone())
+    }
+
+    #[test]
+    fn apply_paths_handles_creates_and_deletes() {
+        let dir = temp_dir("watch");
+        let root = fs::canonicalize(&dir).unwrap().join("root");
+        write_file(&root.join("keep.txt"), "a");
+        write_file(&root.join("old/inner.txt"), "b");
+
+        let cfg = Config {
+            roots: vec![path_to_string(&root).into()],
+            excludes: vec!["**/node_modules/**".to_string()],
+            ..Config::default()
+        };
+        let mut store = Store::load(&dir.join("catalog.bin")).unwrap();
+        indexer::run(&mut store, &cfg, false, false).unwrap();
+        let mut live = live_roots(&mut store.data, &cfg, false).unwrap();
+
+        // `new` may get the inodes of `old` and `old/inner.txt`; neither is
+        // a move (the file differs in size).
+        fs::remove_dir_all(root.join("old")).unwrap();
+        write_file(&root.join("new/nested/file.rs"), "cc");
+        write_file(&root.join("node_modules/dep.js"), "d");
+
+        let mut paths = BTreeSet::new();
+        paths.insert(root.join("old"));
+        paths.insert(root.join("old/inner.txt"));
+        paths.insert(root.join("new"));
+        paths.insert(root.join("node_modules/dep.js"));
+        let run_id = store.data.last_run_id + 1;
+        let stats = apply_paths(&mut store.data, &mut live, &paths, run_id).unwrap();
+
+        assert_eq!(stats.deleted, 2);
+        assert!(stats.updated >= 3);
+        assert_eq!(status_of(&store, &root.join("old/inner.txt")).as_deref(), Some("deleted"));
+        assert_eq!(
+            status_of(&store, &root.join("new/nested/file.rs")).as_deref(),
+            Some("active")
+        );
+        assert_eq!(status_of(&store, &root.join("node_modules/dep.js")), None);
+        assert_eq!(status_of(&store, &root.join("keep.txt")).as_deref(), Some("active"));
+
+        let again = apply_paths(&mut store.data, &mut live, &paths, run_id + 1).unwrap();
+        assert!(!again.changed());
+    }
+
+    #[test]
+    fn writer_reloads_a_store_saved_by_another_process() {
+        let dir = temp_dir("watch_writer");
+        let path = dir.join("catalog.bin");
+        Store::init(&path).unwrap();
+        let mut writer = Writer {
+            seen: disk_stamp(&path),
+        };
+        let mut store = Store::open(&path).unwrap();
+
+        let (lock, reloaded) = writer.lock_and_reload(&mut store).unwrap();
+        assert!(!reloaded);
+        store.data.last_run_id = 7;
+        writer.save(&store, lock, Some(&HashSet::new())).unwrap();
+        assert!(!writer.lock_and_reload(&mut store).unwrap().1);
+
+        let mut other = Store::open(&path).unwrap();
+        other.data.next_tag_id();
+        other.save().unwrap();
+        let (_lock, reloaded) = writer.lock_and_reload(&mut store).unwrap();
+        assert!(reloaded);
+        assert_eq!(store.data.next_tag_id, 2);
+        assert_eq!(store.data.last_run_id, 7);
+    }
+}